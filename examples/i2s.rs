@@ -0,0 +1,139 @@
+//! Example of using the I2S interface to play a tone on an external I2S DAC (e.g. MAX98357A)
+//!
+//! BCK is on GPIO26, WS on GPIO25 and the data on GPIO22.
+//! The APLL is used to get an accurate 48kHz sample rate.
+
+#![no_std]
+#![no_main]
+
+use core::{fmt::Write, panic::PanicInfo};
+
+use esp32_hal::{
+    clock_control::{ClockControl, XTAL_FREQUENCY_AUTO},
+    dport::Split,
+    dprintln,
+    i2s::{self, dma::Descriptor, I2S},
+    prelude::*,
+    serial::{self, Serial},
+    target,
+    timer::Timer,
+};
+
+const SAMPLE_RATE: u32 = 48_000;
+const TONE_FREQUENCY: u32 = 440;
+const AMPLITUDE: i32 = 8_000;
+
+const PART_LEN: usize = 1024;
+const PARTS: usize = 2;
+
+static mut BUFFER: [u8; PART_LEN * PARTS] = [0; PART_LEN * PARTS];
+static mut DESCRIPTORS: [Descriptor; PARTS] = [Descriptor::new(), Descriptor::new()];
+
+/// Triangle wave generator
+struct Tone {
+    phase: u32,
+}
+
+impl Tone {
+    fn next(&mut self) -> i16 {
+        let period = SAMPLE_RATE / TONE_FREQUENCY;
+        self.phase = (self.phase + 1) % period;
+
+        let position = (self.phase * 4 * AMPLITUDE as u32 / period) as i32;
+        let value = if position < 2 * AMPLITUDE {
+            position - AMPLITUDE
+        } else {
+            3 * AMPLITUDE - position
+        };
+        value as i16
+    }
+
+    /// Fill a buffer with stereo 16 bit samples
+    fn fill(&mut self, buffer: &mut [u8]) {
+        for frame in buffer.chunks_exact_mut(4) {
+            let sample = self.next().to_le_bytes();
+            frame[0..2].copy_from_slice(&sample);
+            frame[2..4].copy_from_slice(&sample);
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let dp = target::Peripherals::take().expect("Failed to obtain Peripherals");
+
+    let (_, dport_clock_control) = dp.DPORT.split();
+
+    let clkcntrl = ClockControl::new(
+        dp.RTCCNTL,
+        dp.APB_CTRL,
+        dport_clock_control,
+        XTAL_FREQUENCY_AUTO,
+    )
+    .unwrap();
+
+    let (clkcntrl_config, mut watchdog) = clkcntrl.freeze().unwrap();
+    let (_, _, _, mut watchdog0) = Timer::new(dp.TIMG0, clkcntrl_config);
+    let (_, _, _, mut watchdog1) = Timer::new(dp.TIMG1, clkcntrl_config);
+
+    watchdog.disable();
+    watchdog0.disable();
+    watchdog1.disable();
+
+    let pins = dp.GPIO.split();
+
+    let mut serial: Serial<_, _, _> = Serial::new(
+        dp.UART0,
+        serial::Pins {
+            tx: pins.gpio1,
+            rx: pins.gpio3,
+            cts: None,
+            rts: None,
+        },
+        serial::config::Config {
+            baudrate: 115200.Hz(),
+            ..serial::config::Config::default()
+        },
+        clkcntrl_config,
+    )
+    .unwrap();
+
+    writeln!(serial, "\n\nESP32 Started\n\n").unwrap();
+
+    let mut i2s: I2S<_, _, _, _> = I2S::new(
+        dp.I2S1,
+        i2s::Pins {
+            ws: pins.gpio25,
+            bck: Some(pins.gpio26),
+            dout: Some(pins.gpio22),
+            din: None,
+        },
+        i2s::config::Config::default()
+            .sample_rate(SAMPLE_RATE.Hz())
+            .clock_source(i2s::config::ClockSource::APLL),
+        clkcntrl_config,
+    )
+    .unwrap();
+
+    writeln!(serial, "Sample rate: {}", i2s.sample_rate()).unwrap();
+
+    let mut tone = Tone { phase: 0 };
+
+    let buffer = unsafe { &mut BUFFER };
+    tone.fill(buffer);
+
+    i2s.start_tx(buffer, unsafe { &mut DESCRIPTORS }, PART_LEN)
+        .unwrap();
+
+    loop {
+        // refill each part as soon as it has been sent
+        let part = nb::block!(i2s.tx_next_part()).unwrap();
+        tone.fill(part);
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    dprintln!("\n\n*** {:?}", info);
+    loop {}
+}
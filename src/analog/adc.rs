@@ -18,19 +18,136 @@
 //! | 8       |                      | GPIO25        |
 //! | 9       |                      | GPIO26        |
 //!
+//! Readings can be requested as raw values or as calibrated voltage in [Millivolts], see
+//! [calibration][crate::analog::calibration]:
+//!
+//! ```no_run
+//! let raw: u16 = nb::block!(adc1.read(&mut pin36)).unwrap();
+//! let voltage: Millivolts = nb::block!(adc1.read(&mut pin36)).unwrap();
+//! ```
+//!
+//! Each reading can be oversampled (see [config::Oversampling]), which averages or takes the
+//! median of multiple conversions. The raw value then has more bits than the configured
+//! resolution.
+//!
+//! ADC2 is shared with the radio, which uses it for power detection. Radio drivers claim it via
+//! [acquire_adc2_for_radio]. While it is claimed, ADC2 readings either return `WouldBlock` or
+//! [Error::RadioActive], depending on [config::Arbitration].
+//!
+//! A reading which returned `WouldBlock` keeps its conversion in progress: it has to be polled
+//! until it returns the value, or be aborted with `cancel`. Until then reads of other pins of
+//! the same ADC return `WouldBlock` and ADC2 stays claimed from the radio, so
+//! [acquire_adc2_for_radio] fails. The claim is only held during a conversion, not between
+//! readings.
 
 use core::marker::PhantomData;
 use embedded_hal::adc::{Channel, OneShot};
 
+use crate::analog::calibration::{characterize_channels, Characteristics, Millivolts};
 use crate::analog::config;
 use crate::analog::{ADC1, ADC2};
 use crate::gpio::*;
-use crate::target::{RTCIO, SENS};
+use crate::target::{APB_CTRL, RTCIO, SENS};
+
+/// ADC errors
+#[derive(Debug)]
+pub enum Error {
+    /// ADC2 is in use by the radio
+    RadioActive,
+}
 
 pub struct ADC<ADC> {
     adc: PhantomData<ADC>,
     attenuations: [Option<config::Attenuation>; 10],
+    characteristics: [Option<Characteristics<ADC>>; 10],
+    oversampling: config::Oversampling,
+    arbitration: config::Arbitration,
     active_channel: Option<u8>,
+    /// Channel of the oversampled reading in progress
+    sampling_channel: Option<u8>,
+    samples: [u16; config::MAX_OVERSAMPLING as usize],
+    sample_count: usize,
+}
+
+/// User of ADC2
+#[derive(PartialEq, Eq, Copy, Clone)]
+enum Adc2Owner {
+    None,
+    /// Conversion via the RTC controller in progress
+    Rtc,
+    Radio,
+}
+
+static ADC2_OWNER: CriticalSectionSpinLockMutex<Adc2Owner> =
+    CriticalSectionSpinLockMutex::new(Adc2Owner::None);
+
+/// Claim ADC2 for the radio
+///
+/// Returns false if a conversion is in progress, in which case the radio driver needs to try
+/// again.
+pub fn acquire_adc2_for_radio() -> bool {
+    (&ADC2_OWNER).lock(|owner| match *owner {
+        Adc2Owner::Rtc => false,
+        _ => {
+            *owner = Adc2Owner::Radio;
+            true
+        }
+    })
+}
+
+/// Release ADC2 after use by the radio
+pub fn release_adc2_for_radio() {
+    (&ADC2_OWNER).lock(|owner| {
+        if *owner == Adc2Owner::Radio {
+            *owner = Adc2Owner::None
+        }
+    });
+}
+
+impl<UNIT> ADC<UNIT> {
+    fn new(
+        attenuations: [Option<config::Attenuation>; 10],
+        characteristics: [Option<Characteristics<UNIT>>; 10],
+        oversampling: config::Oversampling,
+        arbitration: config::Arbitration,
+    ) -> Self {
+        ADC {
+            adc: PhantomData,
+            attenuations,
+            characteristics,
+            oversampling,
+            arbitration,
+            active_channel: None,
+            sampling_channel: None,
+            samples: [0; config::MAX_OVERSAMPLING as usize],
+            sample_count: 0,
+        }
+    }
+
+    /// Returns true if the channel is configured for reading
+    pub(crate) fn is_channel_enabled(&self, channel: u8) -> bool {
+        self.attenuations[channel as usize].is_some()
+    }
+}
+
+/// Combine the samples of an oversampled reading
+fn combine(samples: &mut [u16], oversampling: config::Oversampling) -> u16 {
+    let count = samples.len() as u32;
+    let extra_bits = oversampling.extra_bits();
+
+    match oversampling.filter() {
+        config::Filter::Average => {
+            let sum: u32 = samples.iter().map(|&sample| sample as u32).sum();
+            (((sum << extra_bits) + count / 2) / count) as u16
+        }
+        config::Filter::Median => {
+            samples.sort_unstable();
+            let middle = samples.len() / 2;
+            let lower = samples[(samples.len() - 1) / 2] as u32;
+            let upper = samples[middle] as u32;
+            (((lower + upper) << extra_bits) / 2) as u16
+        }
+    }
 }
 
 macro_rules! impl_adc_setup {
@@ -132,14 +249,22 @@ impl ADC<ADC1> {
             rtcio.hall_sens.modify(|_, w| w.xpd_hall().clear_bit());
         }
 
-        let adc = ADC {
-            adc: PhantomData,
-            attenuations: config.attenuations,
-            active_channel: None,
-        };
+        let adc = ADC::new(
+            config.attenuations,
+            characterize_channels(config.resolution, &config.attenuations),
+            config.oversampling,
+            config::Arbitration::Retry,
+        );
 
         Ok(adc)
     }
+
+    // ADC1 is not shared, so there is nothing to claim
+    fn claim(&mut self) -> nb::Result<(), Error> {
+        Ok(())
+    }
+
+    fn unclaim(&mut self) {}
 }
 
 impl ADC<ADC2> {
@@ -157,14 +282,49 @@ impl ADC<ADC2> {
             sar2_en_pad_force
         );
 
-        let adc = ADC {
-            adc: PhantomData,
-            attenuations: config.attenuations,
-            active_channel: None,
-        };
+        let adc = ADC::new(
+            config.attenuations,
+            characterize_channels(config.resolution, &config.attenuations),
+            config.oversampling,
+            config.arbitration,
+        );
 
         Ok(adc)
     }
+
+    /// Claim ADC2 from the radio for a conversion by the RTC controller
+    fn claim(&mut self) -> nb::Result<(), Error> {
+        let claimed = (&ADC2_OWNER).lock(|owner| match *owner {
+            Adc2Owner::Radio => false,
+            _ => {
+                *owner = Adc2Owner::Rtc;
+                true
+            }
+        });
+
+        if !claimed {
+            return match self.arbitration {
+                config::Arbitration::Retry => Err(nb::Error::WouldBlock),
+                config::Arbitration::Error => Err(nb::Error::Other(Error::RadioActive)),
+            };
+        }
+
+        // RTC controller controls ADC2 instead of the power detection (PWDET) of the radio
+        let sensors = unsafe { &*SENS::ptr() };
+        let syscon = unsafe { &*APB_CTRL::ptr() };
+        sensors
+            .sar_read_ctrl2
+            .modify(|_, w| w.sar2_pwdet_force().clear_bit());
+        syscon
+            .apb_saradc_ctrl
+            .modify(|_, w| w.saradc_sar2_mux().set_bit());
+
+        Ok(())
+    }
+
+    fn unclaim(&mut self) {
+        (&ADC2_OWNER).lock(|owner| *owner = Adc2Owner::None);
+    }
 }
 
 macro_rules! impl_adc_interface {
@@ -172,34 +332,88 @@ macro_rules! impl_adc_interface {
         $( ($pin:ident, $channel:expr) ,)+
     ]) => {
 
-        impl<WORD, PIN> OneShot<$adc, WORD, PIN> for ADC<$adc>
-        where
-        WORD: From<u16>,
-        PIN: Channel<$adc, ID=u8>,
-        {
-            type Error = ();
+        impl ADC<$adc> {
+            /// Abort the reading in progress
+            ///
+            /// Needs to be called when a reading which returned `WouldBlock` is abandoned, to
+            /// allow readings of other pins and to release ADC2 for the radio.
+            pub fn cancel(&mut self) {
+                self.sample_count = 0;
+                self.sampling_channel = None;
 
-            fn read(&mut self, _pin: &mut PIN) -> nb::Result<WORD, Self::Error> {
+                if self.active_channel.take().is_some() {
+                    let sensors = unsafe { &*SENS::ptr() };
+                    sensors.$start_reg.modify(|_, w| w.$start().clear_bit());
+
+                    self.unclaim();
+                }
+            }
+
+            /// Read the calibrated voltage of a pin
+            pub fn read_mv<PIN: Channel<$adc, ID=u8>>(
+                &mut self,
+                pin: &mut PIN,
+            ) -> nb::Result<Millivolts, Error> {
+                self.read(pin)
+            }
+
+            /// Read a channel, combining the configured number of samples
+            fn read_oversampled(&mut self, channel: u8) -> nb::Result<u16, Error> {
+                if let Some(sampling_channel) = self.sampling_channel {
+                    // Oversampled reading of another channel in progress, try again later
+                    if sampling_channel != channel {
+                        return Err(nb::Error::WouldBlock);
+                    }
+                }
+
+                let samples = self.oversampling.samples() as usize;
+                while self.sample_count < samples {
+                    match self.read_raw(channel) {
+                        Ok(sample) => {
+                            self.samples[self.sample_count] = sample;
+                            self.sample_count += 1;
+                            self.sampling_channel = Some(channel);
+                        }
+                        Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+                        Err(error) => {
+                            // Abort the oversampled reading
+                            self.sample_count = 0;
+                            self.sampling_channel = None;
+                            return Err(error);
+                        }
+                    }
+                }
+
+                let value = combine(&mut self.samples[..samples], self.oversampling);
+                self.sample_count = 0;
+                self.sampling_channel = None;
+
+                Ok(value)
+            }
+
+            fn read_raw(&mut self, channel: u8) -> nb::Result<u16, Error> {
                 let sensors = unsafe { &*SENS::ptr() };
 
-                if self.attenuations[PIN::channel() as usize] == None {
-                    panic!("Channel {} is not configured reading!", PIN::channel());
+                if self.attenuations[channel as usize] == None {
+                    panic!("Channel {} is not configured reading!", channel);
                 }
 
                 if let Some(active_channel) = self.active_channel {
                     // There is conversion in progress:
                     // - if it's for a different channel try again later
                     // - if it's for the given channel, go ahaid and check progress
-                    if active_channel != PIN::channel() {
+                    if active_channel != channel {
                         return Err(nb::Error::WouldBlock);
                     }
                 }
                 else {
+                    self.claim()?;
+
                     // If no conversions are in progress, start a new one for given channel
-                    self.active_channel = Some(PIN::channel());
+                    self.active_channel = Some(channel);
 
                     sensors.$start_reg.modify(|_, w| {
-                        unsafe { w.$en_pad().bits(1 << PIN::channel() as u8) }
+                        unsafe { w.$en_pad().bits(1 << channel) }
                     });
 
                     sensors.$start_reg.modify(|_,w| w.$start().clear_bit());
@@ -217,8 +431,39 @@ macro_rules! impl_adc_interface {
 
                 // Mark that no conversions are currently in progress
                 self.active_channel = None;
+                self.unclaim();
+
+                Ok(converted_value)
+            }
+        }
+
+        impl<WORD, PIN> OneShot<$adc, WORD, PIN> for ADC<$adc>
+        where
+        WORD: From<u16>,
+        PIN: Channel<$adc, ID=u8>,
+        {
+            type Error = Error;
+
+            fn read(&mut self, _pin: &mut PIN) -> nb::Result<WORD, Self::Error> {
+                self.read_oversampled(PIN::channel()).map(WORD::from)
+            }
+        }
+
+        impl<PIN> OneShot<$adc, Millivolts, PIN> for ADC<$adc>
+        where
+        PIN: Channel<$adc, ID=u8>,
+        {
+            type Error = Error;
+
+            fn read(&mut self, _pin: &mut PIN) -> nb::Result<Millivolts, Self::Error> {
+                let value = self.read_oversampled(PIN::channel())?;
+
+                // remove the extra bits of the oversampling
+                let extra_bits = self.oversampling.extra_bits();
+                let raw = ((value as u32 + ((1 << extra_bits) >> 1)) >> extra_bits) as u16;
 
-                Ok(converted_value.into())
+                let characteristics = self.characteristics[PIN::channel() as usize].unwrap();
+                Ok(Millivolts(characteristics.raw_to_mv(raw)))
             }
         }
 
@@ -0,0 +1,443 @@
+//! ADC calibration
+//!
+//! Converts raw ADC readings to millivolts, equivalent to `esp_adc_cal` of ESP-IDF.
+//!
+//! The characteristics of each ADC and attenuation are described by a linear function
+//! `voltage = coeff_a * reading + coeff_b`, based on (in order of preference):
+//! - The two point calibration values in the eFuse (ADC readings at 150mV and 850mV)
+//! - The reference voltage in the eFuse
+//! - A default reference voltage
+//!
+//! With 11dB attenuation the ADC is not linear above a reading of 2880, so a lookup table
+//! interpolated by the reference voltage is used there.
+//!
+//! [Characteristics::from_efuse] uses the calibration values in the eFuse of the chip. The
+//! other constructors and [Characteristics::raw_to_mv] do not access the hardware.
+//!
+//! [ADC][crate::analog::adc::ADC] characterizes the configured channels automatically and
+//! provides the calibrated readings via [Millivolts]:
+//!
+//! ```no_run
+//! let voltage: Millivolts = nb::block!(adc1.read(&mut pin36)).unwrap();
+//! ```
+
+use crate::analog::config::{Attenuation, Resolution};
+use crate::efuse::Efuse;
+use core::fmt;
+use core::marker::PhantomData;
+
+use private::Tables;
+
+/// Default reference voltage (in mV) used when no calibration is available in the eFuse
+pub const DEFAULT_VREF: u32 = 1100;
+
+/// Voltage in millivolts
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Hash, Default)]
+pub struct Millivolts(pub u32);
+
+impl fmt::Debug for Millivolts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}mV", self.0)
+    }
+}
+
+impl fmt::Display for Millivolts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}mV", self.0)
+    }
+}
+
+/// Source of the characteristics
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Source {
+    /// Two point calibration values of the eFuse
+    TwoPoint,
+    /// Reference voltage of the eFuse
+    EfuseVref,
+    /// Default reference voltage
+    DefaultVref,
+}
+
+/// Scale of coefficient A
+const LIN_COEFF_A_SCALE: u32 = 65536;
+const LIN_COEFF_A_ROUND: u32 = LIN_COEFF_A_SCALE / 2;
+
+/// Range of the reference voltage covered by the lookup tables
+const LUT_VREF_LOW: i32 = 1000;
+const LUT_VREF_HIGH: i32 = 1200;
+
+/// Lookup tables cover readings from 2880 to 4096 in steps of 64
+const LUT_ADC_STEP_SIZE: u32 = 64;
+const LUT_POINTS: usize = 20;
+const LUT_LOW_THRESH: u32 = 2880;
+const LUT_HIGH_THRESH: u32 = LUT_LOW_THRESH + LUT_ADC_STEP_SIZE;
+
+/// Full scale of a 12 bit reading
+const ADC_12_BIT_RES: u32 = 4096;
+
+/// Voltages of the two point calibration values
+const TP_LOW_VOLTAGE: u32 = 150;
+const TP_HIGH_VOLTAGE: u32 = 850;
+
+/// Characteristics of an ADC at a specific attenuation and resolution
+pub struct Characteristics<ADC> {
+    source: Source,
+    attenuation: Attenuation,
+    resolution: Resolution,
+    coeff_a: u32,
+    coeff_b: u32,
+    vref: u32,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> Clone for Characteristics<ADC> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<ADC> Copy for Characteristics<ADC> {}
+
+impl<ADC: Tables> Characteristics<ADC> {
+    /// Characterize using the calibration values in the eFuse
+    ///
+    /// `default_vref` is used when the eFuse contains no calibration values.
+    pub fn from_efuse(attenuation: Attenuation, resolution: Resolution, default_vref: u32) -> Self {
+        let efuse_vref = Efuse::get_adc_vref().map(|vref| vref as u32);
+        let vref = efuse_vref.unwrap_or(default_vref);
+
+        match (ADC::two_point_cal(), efuse_vref) {
+            (Some((low, high)), _) if low >= 0 && high >= 0 => {
+                Self::from_two_point(attenuation, resolution, low as u32, high as u32, vref)
+            }
+            (_, Some(vref)) => Characteristics {
+                source: Source::EfuseVref,
+                ..Self::from_vref(attenuation, resolution, vref)
+            },
+            (_, None) => Self::from_vref(attenuation, resolution, vref),
+        }
+    }
+
+    /// Characterize using two point calibration values
+    ///
+    /// `low` and `high` are the 12 bit readings at 150mV and 850mV with 0dB attenuation. `vref`
+    /// is only used for the non linear part at 11dB attenuation. Inconsistent values (e.g. from
+    /// corrupt eFuse data) fall back to characterizing with `vref`.
+    pub fn from_two_point(
+        attenuation: Attenuation,
+        resolution: Resolution,
+        low: u32,
+        high: u32,
+        vref: u32,
+    ) -> Self {
+        let atten = attenuation as usize;
+        let delta_x = match high.checked_sub(low) {
+            Some(delta_x) if delta_x > 0 => delta_x,
+            _ => return Self::from_vref(attenuation, resolution, vref),
+        };
+        let delta_v = TP_HIGH_VOLTAGE - TP_LOW_VOLTAGE;
+
+        // coeff_a = (delta_v / delta_x) * atten_scale, + delta_x / 2 for rounding
+        let coeff_a = (delta_v * ADC::TP_ATTEN_SCALE[atten] + delta_x / 2) / delta_x;
+
+        // coeff_b = high_v - (delta_v / delta_x) * high_adc + atten_offset
+        let coeff_b = match TP_HIGH_VOLTAGE.checked_sub((delta_v * high + delta_x / 2) / delta_x) {
+            Some(coeff_b) => coeff_b + ADC::TP_ATTEN_OFFSET[atten],
+            None => return Self::from_vref(attenuation, resolution, vref),
+        };
+
+        Characteristics {
+            source: Source::TwoPoint,
+            attenuation,
+            resolution,
+            coeff_a,
+            coeff_b,
+            vref,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Characterize using a reference voltage (in mV)
+    pub fn from_vref(attenuation: Attenuation, resolution: Resolution, vref: u32) -> Self {
+        let atten = attenuation as usize;
+
+        Characteristics {
+            source: Source::DefaultVref,
+            attenuation,
+            resolution,
+            coeff_a: vref * ADC::VREF_ATTEN_SCALE[atten] / ADC_12_BIT_RES,
+            coeff_b: ADC::VREF_ATTEN_OFFSET[atten],
+            vref,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Source of the characteristics
+    pub fn source(&self) -> Source {
+        self.source
+    }
+
+    /// Reference voltage in mV
+    pub fn vref(&self) -> u32 {
+        self.vref
+    }
+
+    /// Linear coefficients (`coeff_a` scaled by 65536, `coeff_b` in mV)
+    pub fn coefficients(&self) -> (u32, u32) {
+        (self.coeff_a, self.coeff_b)
+    }
+
+    /// Convert a raw reading to a voltage in mV
+    pub fn raw_to_mv(&self, reading: u16) -> u32 {
+        // scale the reading to 12 bit
+        let shift = Resolution::Resolution12Bit as u32 - self.resolution as u32;
+        let reading = ((reading as u32) << shift).min(ADC_12_BIT_RES - 1);
+
+        if self.attenuation != Attenuation::Attenuation11dB || reading < LUT_LOW_THRESH {
+            return self.linear_voltage(reading);
+        }
+
+        let lut_voltage = self.lut_voltage(reading);
+        if reading <= LUT_HIGH_THRESH {
+            // transition from the linear to the non linear region
+            interpolate_two_points(
+                self.linear_voltage(reading),
+                lut_voltage,
+                LUT_ADC_STEP_SIZE,
+                reading - LUT_LOW_THRESH,
+            )
+        } else {
+            lut_voltage
+        }
+    }
+
+    fn linear_voltage(&self, reading: u32) -> u32 {
+        (self.coeff_a * reading + LIN_COEFF_A_ROUND) / LIN_COEFF_A_SCALE + self.coeff_b
+    }
+
+    /// Bilinear interpolation of the lookup tables by reading and reference voltage
+    fn lut_voltage(&self, reading: u32) -> u32 {
+        let i = ((reading - LUT_LOW_THRESH) / LUT_ADC_STEP_SIZE) as usize;
+        let low_curve = &ADC::LUT_LOW_VREF;
+        let high_curve = &ADC::LUT_HIGH_VREF;
+
+        let vref = self.vref as i32;
+        let reading = reading as i32;
+        let step = LUT_ADC_STEP_SIZE as i32;
+        let lower = i as i32 * step + LUT_LOW_THRESH as i32;
+
+        let x2dist = LUT_VREF_HIGH - vref;
+        let x1dist = vref - LUT_VREF_LOW;
+        let y2dist = lower + step - reading;
+        let y1dist = reading - lower;
+
+        let q11 = low_curve[i] as i32;
+        let q12 = low_curve[i + 1] as i32;
+        let q21 = high_curve[i] as i32;
+        let q22 = high_curve[i + 1] as i32;
+
+        let voltage = q11 * x2dist * y2dist
+            + q21 * x1dist * y2dist
+            + q12 * x2dist * y1dist
+            + q22 * x1dist * y1dist;
+
+        let divisor = (LUT_VREF_HIGH - LUT_VREF_LOW) * step;
+        ((voltage + divisor / 2) / divisor) as u32
+    }
+}
+
+/// Characterize all channels with an attenuation configured, using the eFuse values
+pub(crate) fn characterize_channels<ADC: Tables>(
+    resolution: Resolution,
+    attenuations: &[Option<Attenuation>; 10],
+) -> [Option<Characteristics<ADC>>; 10] {
+    let mut characteristics = [None; 10];
+    for (channel, attenuation) in attenuations.iter().enumerate() {
+        characteristics[channel] = attenuation
+            .map(|attenuation| Characteristics::from_efuse(attenuation, resolution, DEFAULT_VREF));
+    }
+    characteristics
+}
+
+/// Interpolate between `y1` (at 0) and `y2` (at `x_step`)
+fn interpolate_two_points(y1: u32, y2: u32, x_step: u32, x: u32) -> u32 {
+    (y1 * x_step + y2 * x - y1 * x + x_step / 2) / x_step
+}
+
+#[cfg(test)]
+mod tests {
+    //! Reference values computed with the `esp_adc_cal` algorithm of ESP-IDF
+
+    use super::*;
+    use crate::analog::{ADC1, ADC2};
+
+    const R12: Resolution = Resolution::Resolution12Bit;
+
+    #[test]
+    fn two_point_coefficients() {
+        let adc1 = Characteristics::<ADC1>::from_two_point(
+            Attenuation::Attenuation0dB,
+            R12,
+            270,
+            3240,
+            1100,
+        );
+        assert_eq!(adc1.source(), Source::TwoPoint);
+        assert_eq!(adc1.coefficients(), (15439, 86));
+
+        let adc1 = Characteristics::<ADC1>::from_two_point(
+            Attenuation::Attenuation11dB,
+            R12,
+            270,
+            3240,
+            1100,
+        );
+        assert_eq!(adc1.coefficients(), (52868, 140));
+
+        let adc2 = Characteristics::<ADC2>::from_two_point(
+            Attenuation::Attenuation6dB,
+            R12,
+            420,
+            3300,
+            1100,
+        );
+        assert_eq!(adc2.coefficients(), (29268, 74));
+    }
+
+    #[test]
+    fn two_point_invalid_falls_back_to_vref() {
+        for &(low, high) in &[(3240, 270), (270, 270), (900, 1000)] {
+            let adc1 = Characteristics::<ADC1>::from_two_point(
+                Attenuation::Attenuation0dB,
+                R12,
+                low,
+                high,
+                1100,
+            );
+            assert_eq!(adc1.source(), Source::DefaultVref);
+            assert_eq!(adc1.coefficients(), (15423, 75));
+        }
+    }
+
+    #[test]
+    fn vref_coefficients() {
+        let adc1 = Characteristics::<ADC1>::from_vref(Attenuation::Attenuation0dB, R12, 1100);
+        assert_eq!(adc1.source(), Source::DefaultVref);
+        assert_eq!(adc1.coefficients(), (15423, 75));
+
+        let adc1 = Characteristics::<ADC1>::from_vref(Attenuation::Attenuation11dB, R12, 1121);
+        assert_eq!(adc1.coefficients(), (53806, 142));
+
+        let adc2 = Characteristics::<ADC2>::from_vref(Attenuation::Attenuation2p5dB, R12, 1086);
+        assert_eq!(adc2.coefficients(), (20196, 66));
+    }
+
+    #[test]
+    fn raw_to_mv_linear() {
+        let adc1 = Characteristics::<ADC1>::from_vref(Attenuation::Attenuation0dB, R12, 1100);
+        assert_eq!(adc1.raw_to_mv(0), 75);
+        assert_eq!(adc1.raw_to_mv(1000), 310);
+        assert_eq!(adc1.raw_to_mv(2000), 546);
+        assert_eq!(adc1.raw_to_mv(4095), 1039);
+
+        let adc1 = Characteristics::<ADC1>::from_two_point(
+            Attenuation::Attenuation0dB,
+            R12,
+            270,
+            3240,
+            1100,
+        );
+        assert_eq!(adc1.raw_to_mv(270), 150);
+        assert_eq!(adc1.raw_to_mv(1500), 439);
+        assert_eq!(adc1.raw_to_mv(3240), 849);
+    }
+
+    #[test]
+    fn raw_to_mv_scales_resolution() {
+        let adc1 = Characteristics::<ADC1>::from_vref(
+            Attenuation::Attenuation0dB,
+            Resolution::Resolution9Bit,
+            1100,
+        );
+        assert_eq!(adc1.raw_to_mv(100), 263);
+        assert_eq!(adc1.raw_to_mv(511), 1037);
+    }
+
+    #[test]
+    fn raw_to_mv_lut() {
+        let adc1 = Characteristics::<ADC1>::from_vref(Attenuation::Attenuation11dB, R12, 1121);
+        // linear region
+        assert_eq!(adc1.raw_to_mv(1000), 963);
+        // transition between linear and lookup table
+        assert_eq!(adc1.raw_to_mv(2880), 2507);
+        assert_eq!(adc1.raw_to_mv(2900), 2520);
+        assert_eq!(adc1.raw_to_mv(2944), 2544);
+        // lookup table
+        assert_eq!(adc1.raw_to_mv(3000), 2584);
+        assert_eq!(adc1.raw_to_mv(3500), 2881);
+        assert_eq!(adc1.raw_to_mv(4095), 3150);
+    }
+}
+
+mod private {
+    use super::LUT_POINTS;
+    use crate::analog::{ADC1, ADC2};
+    use crate::efuse::Efuse;
+
+    /// Calibration constants of an ADC
+    pub trait Tables {
+        const TP_ATTEN_SCALE: [u32; 4];
+        const TP_ATTEN_OFFSET: [u32; 4];
+        const VREF_ATTEN_SCALE: [u32; 4];
+        const VREF_ATTEN_OFFSET: [u32; 4];
+
+        /// Voltages (in mV) at 11dB for a reference voltage of 1000mV, for the readings from
+        /// 2880 in steps of 64
+        const LUT_LOW_VREF: [u32; LUT_POINTS];
+        /// Voltages (in mV) at 11dB for a reference voltage of 1200mV, for the readings from
+        /// 2880 in steps of 64
+        const LUT_HIGH_VREF: [u32; LUT_POINTS];
+
+        fn two_point_cal() -> Option<(i32, i32)>;
+    }
+
+    impl Tables for ADC1 {
+        const TP_ATTEN_SCALE: [u32; 4] = [65504, 86975, 120389, 224310];
+        const TP_ATTEN_OFFSET: [u32; 4] = [0, 1, 27, 54];
+        const VREF_ATTEN_SCALE: [u32; 4] = [57431, 76236, 105481, 196602];
+        const VREF_ATTEN_OFFSET: [u32; 4] = [75, 78, 107, 142];
+
+        const LUT_LOW_VREF: [u32; LUT_POINTS] = [
+            2240, 2297, 2352, 2405, 2457, 2512, 2564, 2616, 2664, 2709, 2754, 2795, 2832, 2868,
+            2903, 2937, 2969, 3000, 3030, 3060,
+        ];
+        const LUT_HIGH_VREF: [u32; LUT_POINTS] = [
+            2667, 2706, 2745, 2780, 2813, 2844, 2873, 2901, 2928, 2956, 2982, 3006, 3032, 3059,
+            3084, 3110, 3135, 3160, 3184, 3209,
+        ];
+
+        fn two_point_cal() -> Option<(i32, i32)> {
+            Efuse::get_adc1_two_point_cal()
+        }
+    }
+
+    impl Tables for ADC2 {
+        const TP_ATTEN_SCALE: [u32; 4] = [65467, 86861, 120416, 224708];
+        const TP_ATTEN_OFFSET: [u32; 4] = [0, 9, 26, 66];
+        const VREF_ATTEN_SCALE: [u32; 4] = [57236, 76175, 105678, 197170];
+        const VREF_ATTEN_OFFSET: [u32; 4] = [63, 66, 89, 128];
+
+        const LUT_LOW_VREF: [u32; LUT_POINTS] = [
+            2238, 2293, 2347, 2399, 2451, 2507, 2561, 2613, 2662, 2710, 2754, 2796, 2834, 2870,
+            2904, 2937, 2970, 3000, 3030, 3060,
+        ];
+        const LUT_HIGH_VREF: [u32; LUT_POINTS] = [
+            2657, 2698, 2738, 2776, 2810, 2842, 2873, 2903, 2932, 2962, 2990, 3017, 3044, 3071,
+            3097, 3122, 3148, 3174, 3199, 3225,
+        ];
+
+        fn two_point_cal() -> Option<(i32, i32)> {
+            Efuse::get_adc2_two_point_cal()
+        }
+    }
+}
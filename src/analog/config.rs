@@ -1,6 +1,7 @@
 //! Configuration of analog modules.
 
-use crate::analog::{ADC1, ADC2};
+use crate::analog::{ADC1, ADC2, TOUCH};
+use crate::units::*;
 use embedded_hal::adc::Channel;
 
 /// The sampling/readout resolution of the ADC
@@ -12,6 +13,13 @@ pub enum Resolution {
     Resolution12Bit = 0b11,
 }
 
+impl Resolution {
+    /// Number of bits of a reading
+    pub fn bits(&self) -> u8 {
+        9 + *self as u8
+    }
+}
+
 /// The attenuation of the ADC pin
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum Attenuation {
@@ -21,10 +29,82 @@ pub enum Attenuation {
     Attenuation11dB = 0b11,
 }
 
+/// Maximum number of samples per oversampled reading
+pub const MAX_OVERSAMPLING: u8 = 64;
+
+/// Filter combining the samples of an oversampled reading
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Filter {
+    /// Average of all samples
+    Average,
+    /// Median of all samples, which rejects outliers
+    Median,
+}
+
+/// Oversampling of each reading
+///
+/// Each reading takes `samples` conversions, which are combined by the `filter`. Every 4 times
+/// oversampling adds one bit to the result (e.g. 16 samples of a 12 bit reading give a 14 bit
+/// result), see [Oversampling::result_bits].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Oversampling {
+    samples: u8,
+    filter: Filter,
+}
+
+impl Oversampling {
+    /// Oversampling with 1 to [MAX_OVERSAMPLING] samples
+    pub fn new(samples: u8, filter: Filter) -> Result<Self, ()> {
+        if samples == 0 || samples > MAX_OVERSAMPLING {
+            return Err(());
+        }
+        Ok(Oversampling { samples, filter })
+    }
+
+    /// Number of samples per reading
+    pub fn samples(&self) -> u8 {
+        self.samples
+    }
+
+    /// Filter combining the samples
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Number of bits added to the reading
+    pub fn extra_bits(&self) -> u8 {
+        (7 - self.samples.leading_zeros() as u8) / 2
+    }
+
+    /// Number of bits of the oversampled reading
+    pub fn result_bits(&self, resolution: Resolution) -> u8 {
+        resolution.bits() + self.extra_bits()
+    }
+}
+
+impl Default for Oversampling {
+    fn default() -> Self {
+        Oversampling {
+            samples: 1,
+            filter: Filter::Average,
+        }
+    }
+}
+
+/// Behavior of ADC2 readings while the radio uses ADC2
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Arbitration {
+    /// Return `WouldBlock`, so the read is retried
+    Retry,
+    /// Return [Error::RadioActive][crate::analog::adc::Error::RadioActive]
+    Error,
+}
+
 pub struct Adc1Config {
     pub resolution: Resolution,
     pub hall_sensor: bool,
     pub attenuations: [Option<Attenuation>; 10],
+    pub oversampling: Oversampling,
 }
 
 impl Adc1Config {
@@ -50,6 +130,7 @@ impl Default for Adc1Config {
             resolution: Resolution::Resolution12Bit,
             hall_sensor: false,
             attenuations: [None; 10],
+            oversampling: Oversampling::default(),
         }
     }
 }
@@ -57,6 +138,8 @@ impl Default for Adc1Config {
 pub struct Adc2Config {
     pub resolution: Resolution,
     pub attenuations: [Option<Attenuation>; 10],
+    pub oversampling: Oversampling,
+    pub arbitration: Arbitration,
 }
 
 impl Adc2Config {
@@ -77,6 +160,212 @@ impl Default for Adc2Config {
         Adc2Config {
             resolution: Resolution::Resolution12Bit,
             attenuations: [None; 10],
+            oversampling: Oversampling::default(),
+            arbitration: Arbitration::Retry,
+        }
+    }
+}
+
+/// Maximum number of entries in the pattern table of the continuous ADC
+pub const PATTERN_TABLE_SIZE: usize = 16;
+
+/// One entry of the pattern table of the continuous ADC
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct PatternEntry {
+    pub channel: u8,
+    pub attenuation: Attenuation,
+}
+
+/// Configuration of the continuous sampling ADC (see [crate::analog::i2s::ContinuousADC])
+///
+/// The ADC cycles through the pattern table, converting one entry per sample.
+pub struct ContinuousAdcConfig {
+    pub resolution: Resolution,
+    pub sample_rate: Hertz,
+    pub pattern: [Option<PatternEntry>; PATTERN_TABLE_SIZE],
+}
+
+impl ContinuousAdcConfig {
+    pub fn new() -> ContinuousAdcConfig {
+        Self::default()
+    }
+
+    /// Add a pin to the pattern table
+    ///
+    /// A pin can be added multiple times to sample it more often than other pins.
+    /// Returns an error if the pattern table is full.
+    pub fn enable_pin<PIN: Channel<ADC1, ID = u8>>(
+        &mut self,
+        _pin: &PIN,
+        attenuation: Attenuation,
+    ) -> Result<(), ()> {
+        let entry = self
+            .pattern
+            .iter_mut()
+            .find(|entry| entry.is_none())
+            .ok_or(())?;
+        *entry = Some(PatternEntry {
+            channel: PIN::channel(),
+            attenuation,
+        });
+        Ok(())
+    }
+
+    /// Number of entries in the pattern table
+    pub fn pattern_len(&self) -> usize {
+        self.pattern
+            .iter()
+            .take_while(|entry| entry.is_some())
+            .count()
+    }
+}
+impl Default for ContinuousAdcConfig {
+    fn default() -> Self {
+        ContinuousAdcConfig {
+            resolution: Resolution::Resolution12Bit,
+            sample_rate: Hertz(20_000),
+            pattern: [None; PATTERN_TABLE_SIZE],
+        }
+    }
+}
+
+/// Amplitude of the cosine generator relative to full scale
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CosineScale {
+    Full = 0b00,
+    Half = 0b01,
+    Quarter = 0b10,
+    Eighth = 0b11,
+}
+
+/// Phase of the cosine generator output
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CosinePhase {
+    Phase0 = 0b10,
+    Phase180 = 0b11,
+}
+
+/// Configuration of the DAC cosine generator
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CosineConfig {
+    /// Frequency of the cosine wave
+    pub frequency: Hertz,
+    pub scale: CosineScale,
+    /// DC offset added to the wave, the output saturates at 0 and 255
+    pub offset: i8,
+    pub phase: CosinePhase,
+}
+
+impl CosineConfig {
+    pub fn new(frequency: Hertz) -> CosineConfig {
+        CosineConfig {
+            frequency,
+            scale: CosineScale::Full,
+            offset: 0,
+            phase: CosinePhase::Phase0,
+        }
+    }
+}
+
+/// Number of touch pads
+pub const TOUCH_PADS: usize = 10;
+
+/// Upper voltage of the touch pad charge cycle
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TouchHighVoltage {
+    V2_4 = 0b00,
+    V2_5 = 0b01,
+    V2_6 = 0b10,
+    V2_7 = 0b11,
+}
+
+/// Lower voltage of the touch pad discharge cycle
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TouchLowVoltage {
+    V0_5 = 0b00,
+    V0_6 = 0b01,
+    V0_7 = 0b10,
+    V0_8 = 0b11,
+}
+
+/// Attenuation of the upper voltage
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TouchHighVoltageAttenuation {
+    V1_5 = 0b00,
+    V1_0 = 0b01,
+    V0_5 = 0b10,
+    V0_0 = 0b11,
+}
+
+/// Condition for a pad to be considered touched
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TouchTrigger {
+    /// Measured value below the threshold
+    Below = 0,
+    /// Measured value above the threshold
+    Above = 1,
+}
+
+/// Level of the touch pad at the start of a measurement
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TouchInitialCharge {
+    Low = 0,
+    High = 1,
+}
+
+/// Configuration of a single touch pad
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct TouchPadConfig {
+    /// Charge/discharge speed (1 (slowest) to 7 (fastest), 0 disables the pad)
+    pub slope: u8,
+    pub initial_charge: TouchInitialCharge,
+    /// Threshold for the touch interrupt (see [TouchTrigger])
+    pub threshold: u16,
+}
+
+impl Default for TouchPadConfig {
+    fn default() -> Self {
+        TouchPadConfig {
+            slope: 7,
+            initial_charge: TouchInitialCharge::Low,
+            threshold: 0,
+        }
+    }
+}
+
+/// Configuration of the touch sensor (see [crate::analog::touch::Touch])
+pub struct TouchConfig {
+    /// Duration of a measurement in 8MHz (RTC8M) cycles
+    pub measurement_cycles: u16,
+    /// Interval between measurements in slow RTC clock cycles
+    pub sleep_cycles: u16,
+    pub high_voltage: TouchHighVoltage,
+    pub low_voltage: TouchLowVoltage,
+    pub high_voltage_attenuation: TouchHighVoltageAttenuation,
+    pub trigger: TouchTrigger,
+    pub pads: [Option<TouchPadConfig>; TOUCH_PADS],
+}
+
+impl TouchConfig {
+    pub fn new() -> TouchConfig {
+        Self::default()
+    }
+
+    pub fn enable_pin<PIN: Channel<TOUCH, ID = u8>>(&mut self, _pin: &PIN, config: TouchPadConfig) {
+        self.pads[PIN::channel() as usize] = Some(config);
+    }
+}
+
+impl Default for TouchConfig {
+    fn default() -> Self {
+        TouchConfig {
+            measurement_cycles: 0x7fff,
+            sleep_cycles: 0x1000,
+            high_voltage: TouchHighVoltage::V2_7,
+            low_voltage: TouchLowVoltage::V0_5,
+            high_voltage_attenuation: TouchHighVoltageAttenuation::V1_0,
+            trigger: TouchTrigger::Below,
+            pads: [None; TOUCH_PADS],
         }
     }
 }
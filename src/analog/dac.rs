@@ -5,12 +5,37 @@
 //!
 //! The DAC1 is avilable on the GPIO pin 25, and DAC2 on pin 26.
 //!
+//! Next to writing a fixed value, the DACs can output a cosine wave generated by hardware
+//! (e.g. for buzzer tones or test signals):
+//!
+//! ```no_run
+//! let mut config = CosineConfig::new(1.kHz().into());
+//! config.scale = CosineScale::Half;
+//! let frequency = dac1.enable_cosine(&config, clock_control_config).unwrap();
+//! ```
+//!
+//! The cosine generator is clocked by the 8MHz oscillator (RTC8M), which needs to be enabled
+//! in the clock control (e.g. as fast RTC source). There is only one generator, so the
+//! frequency is shared by both DACs, while scale, offset and phase are set per DAC.
+//!
 
 use core::marker::PhantomData;
 
+use crate::analog::config::CosineConfig;
 use crate::analog::{DAC1, DAC2};
+use crate::clock_control::ClockControlConfig;
 use crate::gpio::{Analog, Gpio25, Gpio26};
 use crate::target::{RTCIO, SENS};
+use crate::units::*;
+
+/// DAC errors
+#[derive(Debug)]
+pub enum Error {
+    /// The 8MHz oscillator (RTC8M) clocking the cosine generator is not enabled
+    ClockDisabled,
+    /// The frequency can not be generated from the 8MHz oscillator
+    FrequencyOutOfRange,
+}
 
 pub struct DAC<DAC> {
     _dac: PhantomData<DAC>,
@@ -45,6 +70,42 @@ impl DAC<DAC1> {
             .pad_dac1
             .modify(|_, w| unsafe { w.pdac1_dac().bits(value) });
     }
+
+    /// Output a cosine wave
+    ///
+    /// Returns the actual frequency, which is the closest frequency possible. This also
+    /// changes the frequency of DAC2 if it outputs a cosine wave.
+    pub fn enable_cosine(
+        &mut self,
+        config: &CosineConfig,
+        clock_control_config: ClockControlConfig,
+    ) -> Result<Hertz, Error> {
+        let frequency = set_cosine_frequency(config.frequency, clock_control_config)?;
+        let sensors = unsafe { &*SENS::ptr() };
+
+        sensors.sar_dac_ctrl2.modify(|_, w| unsafe {
+            w.dac_scale1()
+                .bits(config.scale as u8)
+                .dac_dc1()
+                .bits(config.offset as u8)
+                .dac_inv1()
+                .bits(config.phase as u8)
+                .dac_cw_en1()
+                .set_bit()
+        });
+
+        Ok(frequency)
+    }
+
+    /// Stop the cosine wave and output the value of the last [write][Self::write]
+    pub fn disable_cosine(&mut self) {
+        let sensors = unsafe { &*SENS::ptr() };
+
+        sensors
+            .sar_dac_ctrl2
+            .modify(|_, w| w.dac_cw_en1().clear_bit());
+        stop_cosine_generator_if_unused();
+    }
 }
 
 impl DAC<DAC2> {
@@ -76,4 +137,77 @@ impl DAC<DAC2> {
             .pad_dac2
             .modify(|_, w| unsafe { w.pdac2_dac().bits(value) });
     }
+
+    /// Output a cosine wave
+    ///
+    /// Returns the actual frequency, which is the closest frequency possible. This also
+    /// changes the frequency of DAC1 if it outputs a cosine wave.
+    pub fn enable_cosine(
+        &mut self,
+        config: &CosineConfig,
+        clock_control_config: ClockControlConfig,
+    ) -> Result<Hertz, Error> {
+        let frequency = set_cosine_frequency(config.frequency, clock_control_config)?;
+        let sensors = unsafe { &*SENS::ptr() };
+
+        sensors.sar_dac_ctrl2.modify(|_, w| unsafe {
+            w.dac_scale2()
+                .bits(config.scale as u8)
+                .dac_dc2()
+                .bits(config.offset as u8)
+                .dac_inv2()
+                .bits(config.phase as u8)
+                .dac_cw_en2()
+                .set_bit()
+        });
+
+        Ok(frequency)
+    }
+
+    /// Stop the cosine wave and output the value of the last [write][Self::write]
+    pub fn disable_cosine(&mut self) {
+        let sensors = unsafe { &*SENS::ptr() };
+
+        sensors
+            .sar_dac_ctrl2
+            .modify(|_, w| w.dac_cw_en2().clear_bit());
+        stop_cosine_generator_if_unused();
+    }
+}
+
+/// Set the frequency of the cosine generator and start it
+///
+/// The frequency is RTC8M * step / 65536, with a 16 bit step.
+fn set_cosine_frequency(
+    frequency: Hertz,
+    clock_control_config: ClockControlConfig,
+) -> Result<Hertz, Error> {
+    let rtc8m_frequency = u32::from(clock_control_config.rtc8m_frequency()) as u64;
+    if rtc8m_frequency == 0 {
+        return Err(Error::ClockDisabled);
+    }
+
+    let step = ((u32::from(frequency) as u64) * 65536 + rtc8m_frequency / 2) / rtc8m_frequency;
+    if step == 0 || step > u16::MAX as u64 {
+        return Err(Error::FrequencyOutOfRange);
+    }
+
+    let sensors = unsafe { &*SENS::ptr() };
+    sensors
+        .sar_dac_ctrl1
+        .modify(|_, w| unsafe { w.sw_fstep().bits(step as u16).sw_tone_en().set_bit() });
+
+    Ok(Hertz((rtc8m_frequency * step / 65536) as u32))
+}
+
+/// Stop the cosine generator if none of the DACs uses it
+fn stop_cosine_generator_if_unused() {
+    let sensors = unsafe { &*SENS::ptr() };
+
+    let ctrl2 = sensors.sar_dac_ctrl2.read();
+    if ctrl2.dac_cw_en1().bit_is_clear() && ctrl2.dac_cw_en2().bit_is_clear() {
+        sensors
+            .sar_dac_ctrl1
+            .modify(|_, w| w.sw_tone_en().clear_bit());
+    }
 }
@@ -3,14 +3,51 @@
 //! This module provides a function for reading current value of the built-in
 //! hall sensor.
 //!
+//! [HallSensor] additionally averages multiple measurements, compensates the zero-field offset
+//! of the individual chip and reports errors instead of panicking. Its [read][HallSensor::read]
+//! is non-blocking, so it can be called from a timer driven sampling loop:
+//!
+//! ```no_run
+//! let mut adc_config = Adc1Config::new();
+//! adc_config.enable_pin(&pin36, Attenuation::Attenuation0dB);
+//! adc_config.enable_pin(&pin39, Attenuation::Attenuation0dB);
+//! adc_config.enable_hall_sensor();
+//! let mut adc1 = ADC::adc1(analog.adc1, adc_config).unwrap();
+//!
+//! let mut hall = HallSensor::new(pin36, pin39, 16).unwrap();
+//! // without magnetic field
+//! hall.calibrate(&mut adc1).unwrap();
+//!
+//! let value = nb::block!(hall.read(&mut adc1)).unwrap();
+//! ```
+//!
 
-use embedded_hal::adc::OneShot;
+use embedded_hal::adc::{Channel, OneShot};
 
-use crate::analog::adc::ADC;
+use crate::analog::adc::{self, ADC};
 use crate::analog::ADC1;
 use crate::gpio::{Analog, Gpio36, Gpio39};
 use crate::target::RTCIO;
 
+/// Hall sensor errors
+#[derive(Debug)]
+pub enum Error {
+    /// The hall sensor is not enabled in the ADC configuration
+    HallSensorDisabled,
+    /// GPIO36 or GPIO39 is not enabled in the ADC configuration
+    ChannelDisabled,
+    /// The number of samples is 0
+    InvalidSamples,
+    /// Error of the ADC
+    Adc(adc::Error),
+}
+
+impl From<adc::Error> for Error {
+    fn from(error: adc::Error) -> Self {
+        Error::Adc(error)
+    }
+}
+
 impl ADC<ADC1> {
     pub fn read_hall_sensor(
         &mut self,
@@ -30,3 +67,139 @@ impl ADC<ADC1> {
         (vp2 as i32 - vp1 as i32) - (vn2 as i32 - vn1 as i32)
     }
 }
+
+/// Hall sensor with averaging and offset calibration
+pub struct HallSensor {
+    vp_pin: Gpio36<Analog>,
+    vn_pin: Gpio39<Analog>,
+    samples: u16,
+    offset: i32,
+    /// Step of the measurement in progress: VP and VN with phase 0, then with phase 1
+    step: u8,
+    readings: [u16; 4],
+    sample_count: u16,
+    sum: i32,
+}
+
+impl HallSensor {
+    /// Create a hall sensor averaging `samples` measurements per reading
+    pub fn new(
+        vp_pin: Gpio36<Analog>,
+        vn_pin: Gpio39<Analog>,
+        samples: u16,
+    ) -> Result<Self, Error> {
+        if samples == 0 {
+            return Err(Error::InvalidSamples);
+        }
+
+        Ok(HallSensor {
+            vp_pin,
+            vn_pin,
+            samples,
+            offset: 0,
+            step: 0,
+            readings: [0; 4],
+            sample_count: 0,
+            sum: 0,
+        })
+    }
+
+    /// Read the averaged value with the offset removed
+    ///
+    /// Returns `WouldBlock` until all samples have been measured. The ADC must not be used for
+    /// other channels until the reading is complete.
+    pub fn read(&mut self, adc: &mut ADC<ADC1>) -> nb::Result<i32, Error> {
+        Ok(self.read_uncalibrated(adc)? - self.offset)
+    }
+
+    /// Read the averaged value without the offset removed
+    pub fn read_uncalibrated(&mut self, adc: &mut ADC<ADC1>) -> nb::Result<i32, Error> {
+        let rtcio = unsafe { &*RTCIO::ptr() };
+
+        if rtcio.hall_sens.read().xpd_hall().bit_is_clear() {
+            return Err(nb::Error::Other(Error::HallSensorDisabled));
+        }
+        if !adc.is_channel_enabled(<Gpio36<Analog> as Channel<ADC1>>::channel())
+            || !adc.is_channel_enabled(<Gpio39<Analog> as Channel<ADC1>>::channel())
+        {
+            return Err(nb::Error::Other(Error::ChannelDisabled));
+        }
+
+        while self.sample_count < self.samples {
+            rtcio
+                .hall_sens
+                .modify(|_, w| w.hall_phase().bit(self.step >= 2));
+
+            let reading = if self.step % 2 == 0 {
+                adc.read(&mut self.vp_pin)
+            } else {
+                adc.read(&mut self.vn_pin)
+            };
+
+            self.readings[self.step as usize] = match reading {
+                Ok(value) => value,
+                Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+                Err(nb::Error::Other(error)) => {
+                    self.reset();
+                    return Err(nb::Error::Other(error.into()));
+                }
+            };
+
+            self.step += 1;
+            if self.step == 4 {
+                let [vp1, vn1, vp2, vn2] = self.readings;
+                self.sum += (vp2 as i32 - vp1 as i32) - (vn2 as i32 - vn1 as i32);
+                self.sample_count += 1;
+                self.step = 0;
+            }
+        }
+
+        let samples = self.samples as i32;
+        let sum = self.sum;
+        self.reset();
+
+        // rounded average
+        if sum >= 0 {
+            Ok((sum + samples / 2) / samples)
+        } else {
+            Ok((sum - samples / 2) / samples)
+        }
+    }
+
+    /// Measure the zero-field offset
+    ///
+    /// Must be called without magnetic field near the chip. Returns the measured offset, which
+    /// can be stored and restored later with [set_offset][Self::set_offset].
+    pub fn calibrate(&mut self, adc: &mut ADC<ADC1>) -> Result<i32, Error> {
+        self.reset();
+        self.offset = nb::block!(self.read_uncalibrated(adc))?;
+        Ok(self.offset)
+    }
+
+    /// Zero-field offset
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    /// Set the zero-field offset (e.g. restored from flash)
+    pub fn set_offset(&mut self, offset: i32) {
+        self.offset = offset;
+    }
+
+    /// Number of measurements averaged per reading
+    pub fn samples(&self) -> u16 {
+        self.samples
+    }
+
+    /// Release the pins
+    pub fn release(self) -> (Gpio36<Analog>, Gpio39<Analog>) {
+        (self.vp_pin, self.vn_pin)
+    }
+
+    /// Abort the reading in progress
+    fn reset(&mut self) {
+        self.step = 0;
+        self.sample_count = 0;
+        self.sum = 0;
+    }
+}
@@ -0,0 +1,558 @@
+//! High speed ADC sampling and DAC playback via I2S0 DMA.
+//!
+//! The SAR ADC1 controller can be driven by the digital controller, which
+//! passes the conversion results to I2S0. [ContinuousADC] uses this to fill
+//! DMA ring buffers at a fixed sample rate, cycling through a pattern table of
+//! channels and attenuations (see [ContinuousAdcConfig]).
+//!
+//! In the other direction I2S0 can feed the DACs directly: [StreamingDAC]
+//! plays a DMA ring buffer on DAC1 and/or DAC2 at a fixed sample rate.
+//!
+//! Both drivers use the LCD mode of I2S0, so they can not be used at the same
+//! time or together with other I2S0 drivers. Buffers are handled as in the
+//! [I2S driver](crate::i2s): the buffer is split into at least two equally
+//! sized parts and each part is handed back to the application once the DMA
+//! engine has finished it.
+//!
+//! # Example
+//! ```no_run
+//! static mut BUFFER: [u8; 2048] = [0; 2048];
+//! static mut DESCRIPTORS: [Descriptor; 2] = [Descriptor::new(), Descriptor::new()];
+//!
+//! let mut config = ContinuousAdcConfig::new();
+//! config.sample_rate = 100.kHz().into();
+//! config.enable_pin(&pin36, Attenuation::Attenuation11dB).unwrap();
+//! config.enable_pin(&pin39, Attenuation::Attenuation11dB).unwrap();
+//!
+//! let mut adc = ContinuousADC::new(dp.I2S0, analog.adc1, config, clkcntrl_config).unwrap();
+//! adc.start(unsafe { &mut BUFFER }, unsafe { &mut DESCRIPTORS }, 1024).unwrap();
+//!
+//! loop {
+//!     let part = nb::block!(adc.next_part()).unwrap();
+//!     for sample in ContinuousADC::samples(part) {
+//!         ...
+//!     }
+//! }
+//! ```
+
+use crate::analog::config::{ContinuousAdcConfig, PatternEntry};
+use crate::analog::dac::DAC;
+use crate::analog::{ADC1, DAC1, DAC2};
+use crate::clock_control::{dfs, ClockControlConfig};
+use crate::i2s::{
+    self, dma::Descriptor, sample_offset, ClockDividers, Instance, Ring, RingBuffers,
+};
+use crate::target::{APB_CTRL, I2S0, SENS};
+use crate::units::*;
+
+/// Errors of the I2S based ADC and DAC drivers
+#[derive(Debug)]
+pub enum Error {
+    /// No channel has been added to the pattern table
+    NoChannels,
+    /// Error of the underlying I2S peripheral or DMA
+    I2S(i2s::Error),
+}
+
+/// Minimum and maximum divider between module clock and bit clock
+const BCK_DIVIDER_MIN: u32 = 2;
+const BCK_DIVIDER_MAX: u32 = 63;
+
+/// Number of bit clock cycles per sample
+const BCK_PER_SAMPLE: u32 = 2;
+
+/// Clock divider of the SAR ADC (APB clock / 4)
+const SAR_CLK_DIV: u8 = 4;
+
+/// Configure the module clock of I2S0 for `sample_rate`
+///
+/// Returns the actual sample rate.
+fn set_sample_rate(
+    instance: &I2S0,
+    clock_control: &ClockControlConfig,
+    sample_rate: Hertz,
+) -> Result<Hertz, Error> {
+    let source = clock_control.pll_d2_frequency();
+
+    // use the highest bit clock divider possible to get the best resolution
+    let (dividers, bck_divider) = (BCK_DIVIDER_MIN..=BCK_DIVIDER_MAX)
+        .rev()
+        .find_map(|bck_divider| {
+            ClockDividers::calculate(source, sample_rate * BCK_PER_SAMPLE * bck_divider)
+                .ok()
+                .map(|dividers| (dividers, bck_divider))
+        })
+        .ok_or(Error::I2S(i2s::Error::ClockError))?;
+
+    i2s::set_clock_dividers(instance, dividers, false);
+    instance.sample_rate_conf.write(|w| unsafe {
+        w.bits(0)
+            .tx_bits_mod()
+            .bits(16)
+            .rx_bits_mod()
+            .bits(16)
+            .tx_bck_div_num()
+            .bits(bck_divider as u8)
+            .rx_bck_div_num()
+            .bits(bck_divider as u8)
+    });
+
+    Ok(dividers.frequency(source) / (BCK_PER_SAMPLE * bck_divider))
+}
+
+/// Common register setup of I2S0 for the LCD mode used by the ADC and DAC
+fn init_registers(instance: &I2S0) {
+    unsafe {
+        instance.conf2.write(|w| w.bits(0).lcd_en().set_bit());
+        instance.conf1.write(|w| {
+            w.bits(0)
+                .tx_pcm_bypass()
+                .set_bit()
+                .rx_pcm_bypass()
+                .set_bit()
+        });
+        instance.timing.write(|w| w.bits(0));
+        instance.lc_conf.write(|w| {
+            w.bits(0)
+                .out_eof_mode()
+                .set_bit()
+                .outdscr_burst_en()
+                .set_bit()
+                .out_data_burst_en()
+                .set_bit()
+                .indscr_burst_en()
+                .set_bit()
+        });
+        instance.int_ena.write(|w| w.bits(0));
+        instance.int_clr.write(|w| w.bits(0xffff_ffff));
+    }
+}
+
+/// A single ADC conversion result
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Sample {
+    /// ADC1 channel the sample was taken from
+    pub channel: u8,
+    /// Conversion result
+    pub value: u16,
+}
+
+/// Continuous sampling ADC1 using I2S0 DMA
+pub struct ContinuousADC {
+    instance: I2S0,
+    adc: ADC1,
+    sample_rate: Hertz,
+    ring: Option<Ring>,
+    _pll_lock: dfs::LockPllD2,
+}
+
+impl ContinuousADC {
+    /// Create a new continuous sampling ADC
+    ///
+    /// The pins in the pattern table need to be in analog mode.
+    pub fn new(
+        mut instance: I2S0,
+        adc: ADC1,
+        config: ContinuousAdcConfig,
+        clock_control: ClockControlConfig,
+    ) -> Result<Self, Error> {
+        let pattern_len = config.pattern_len();
+        if pattern_len == 0 {
+            return Err(Error::NoChannels);
+        }
+
+        let pll_lock = clock_control.lock_plld2();
+
+        Self::init_adc(&config, pattern_len);
+
+        instance.reset().enable();
+        init_registers(&instance);
+
+        unsafe {
+            instance.conf.write(|w| {
+                w.bits(0)
+                    .rx_msb_right()
+                    .clear_bit()
+                    .rx_right_first()
+                    .clear_bit()
+            });
+            instance
+                .conf_chan
+                .write(|w| w.bits(0).rx_chan_mod().bits(1));
+            instance.fifo_conf.write(|w| {
+                w.bits(0)
+                    .dscr_en()
+                    .set_bit()
+                    .rx_fifo_mod_force_en()
+                    .set_bit()
+                    .rx_fifo_mod()
+                    .bits(1)
+                    .rx_data_num()
+                    .bits(32)
+            });
+        }
+
+        let sample_rate = set_sample_rate(&instance, &clock_control, config.sample_rate)?;
+        i2s::reset_all(&instance);
+
+        Ok(ContinuousADC {
+            instance,
+            adc,
+            sample_rate,
+            ring: None,
+            _pll_lock: pll_lock,
+        })
+    }
+
+    /// Hand ADC1 to the digital controller and program the pattern table
+    fn init_adc(config: &ContinuousAdcConfig, pattern_len: usize) {
+        let sensors = unsafe { &*SENS::ptr() };
+        let syscon = unsafe { &*APB_CTRL::ptr() };
+
+        // digital controller controls ADC1
+        sensors
+            .sar_read_ctrl
+            .modify(|_, w| w.sar1_dig_force().set_bit().sar1_data_inv().set_bit());
+        sensors.sar_meas_start1.modify(|_, w| {
+            w.meas1_start_force()
+                .set_bit()
+                .sar1_en_pad_force()
+                .set_bit()
+        });
+        sensors
+            .sar_touch_ctrl1
+            .modify(|_, w| w.xpd_hall_force().set_bit().hall_phase_force().set_bit());
+
+        // power on the SAR ADC
+        sensors
+            .sar_meas_wait2
+            .modify(|_, w| unsafe { w.force_xpd_sar().bits(0b11) });
+
+        // pattern table, 4 entries per register, first entry in the highest byte
+        let mut table = [0u32; 4];
+        for (index, entry) in config.pattern[..pattern_len].iter().enumerate() {
+            let PatternEntry {
+                channel,
+                attenuation,
+            } = entry.unwrap();
+            let value =
+                (channel as u32) << 4 | (config.resolution as u32) << 2 | attenuation as u32;
+            table[index / 4] |= value << (24 - 8 * (index % 4));
+        }
+        syscon
+            .apb_saradc_sar1_patt_tab1
+            .write(|w| unsafe { w.bits(table[0]) });
+        syscon
+            .apb_saradc_sar1_patt_tab2
+            .write(|w| unsafe { w.bits(table[1]) });
+        syscon
+            .apb_saradc_sar1_patt_tab3
+            .write(|w| unsafe { w.bits(table[2]) });
+        syscon
+            .apb_saradc_sar1_patt_tab4
+            .write(|w| unsafe { w.bits(table[3]) });
+
+        syscon.apb_saradc_fsm.modify(|_, w| unsafe {
+            w.saradc_rstb_wait()
+                .bits(8)
+                .saradc_standby_wait()
+                .bits(100)
+                .saradc_start_wait()
+                .bits(16)
+                .saradc_sample_cycle()
+                .bits(2)
+        });
+        syscon.apb_saradc_ctrl2.modify(|_, w| {
+            w.saradc_sar1_inv()
+                .set_bit()
+                .saradc_meas_num_limit()
+                .clear_bit()
+        });
+        syscon.apb_saradc_ctrl.modify(|_, w| unsafe {
+            w.saradc_start_force()
+                .clear_bit()
+                // single channel mode on ADC1
+                .saradc_work_mode()
+                .bits(0)
+                .saradc_sar_sel()
+                .clear_bit()
+                // 12 bit data with 4 bit channel number
+                .saradc_data_sar_sel()
+                .clear_bit()
+                .saradc_data_to_i2s()
+                .set_bit()
+                .saradc_sar_clk_div()
+                .bits(SAR_CLK_DIV)
+                .saradc_sar1_patt_len()
+                .bits((pattern_len - 1) as u8)
+        });
+
+        // restart the pattern table
+        syscon
+            .apb_saradc_ctrl
+            .modify(|_, w| w.saradc_sar1_patt_p_clear().set_bit());
+        syscon
+            .apb_saradc_ctrl
+            .modify(|_, w| w.saradc_sar1_patt_p_clear().clear_bit());
+    }
+
+    /// Give ADC1 back to the RTC controller
+    fn deinit_adc() {
+        let sensors = unsafe { &*SENS::ptr() };
+        let syscon = unsafe { &*APB_CTRL::ptr() };
+
+        syscon
+            .apb_saradc_ctrl
+            .modify(|_, w| w.saradc_data_to_i2s().clear_bit());
+        sensors
+            .sar_read_ctrl
+            .modify(|_, w| w.sar1_dig_force().clear_bit());
+    }
+
+    /// The actual sample rate
+    pub fn sample_rate(&self) -> Hertz {
+        self.sample_rate
+    }
+
+    /// Start sampling into a ring buffer
+    ///
+    /// The buffer is split into at least two parts of `part_len` bytes, each part needs one
+    /// descriptor.
+    /// Every sample takes 2 bytes.
+    pub fn start(
+        &mut self,
+        buffer: &'static mut [u8],
+        descriptors: &'static mut [Descriptor],
+        part_len: usize,
+    ) -> Result<(), Error> {
+        if self.ring.is_some() {
+            return Err(Error::I2S(i2s::Error::Busy));
+        }
+
+        let (ring, address) =
+            Ring::new(buffer, descriptors, part_len, false).map_err(Error::I2S)?;
+        i2s::start_rx_dma(&self.instance, address, ring.part_len());
+        self.ring = Some(ring);
+
+        Ok(())
+    }
+
+    /// Get the part of the ring buffer which has just been filled
+    ///
+    /// Each part is only returned once. If the part is not read before the DMA engine
+    /// reaches it again, it will be overwritten. Use [ContinuousADC::samples] to decode it.
+    pub fn next_part(&mut self) -> nb::Result<&mut [u8], Error> {
+        let address = self.instance.in_eof_des_addr.read().bits();
+        let ring = self
+            .ring
+            .as_mut()
+            .ok_or(nb::Error::Other(Error::I2S(i2s::Error::NotRunning)))?;
+
+        if self
+            .instance
+            .int_raw
+            .read()
+            .in_suc_eof_int_raw()
+            .bit_is_clear()
+        {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        ring.next_part(address).map_err(|error| match error {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(error) => nb::Error::Other(Error::I2S(error)),
+        })
+    }
+
+    /// Decode the samples in a part of the ring buffer
+    pub fn samples(part: &[u8]) -> impl Iterator<Item = Sample> + '_ {
+        (0..part.len() / 2).map(move |index| {
+            let offset = (index & !1) * 2 + sample_offset(index % 2);
+            let word = u16::from_le_bytes([part[offset], part[offset + 1]]);
+            Sample {
+                channel: (word >> 12) as u8,
+                value: word & 0xfff,
+            }
+        })
+    }
+
+    /// Stop sampling and return the ring buffer
+    pub fn stop(&mut self) -> Result<RingBuffers, Error> {
+        let ring = self.ring.take().ok_or(Error::I2S(i2s::Error::NotRunning))?;
+        i2s::stop_rx_dma(&self.instance);
+        Ok(ring.into_buffers())
+    }
+
+    /// Release the I2S0 peripheral, ADC1 and the ring buffer (if running)
+    ///
+    /// A running transfer is stopped.
+    pub fn release(mut self) -> (I2S0, ADC1, Option<RingBuffers>) {
+        let ring = self.stop().ok();
+        Self::deinit_adc();
+        self.instance.disable();
+        (self.instance, self.adc, ring)
+    }
+}
+
+/// DAC playback using I2S0 DMA
+///
+/// Samples are stored in frames of 4 bytes, see [StreamingDAC::set_frame].
+pub struct StreamingDAC {
+    instance: I2S0,
+    dac1: Option<DAC<DAC1>>,
+    dac2: Option<DAC<DAC2>>,
+    sample_rate: Hertz,
+    ring: Option<Ring>,
+    _pll_lock: dfs::LockPllD2,
+}
+
+impl StreamingDAC {
+    /// Create a new DAC streaming driver for one or both DACs
+    pub fn new<T: Into<Hertz>>(
+        mut instance: I2S0,
+        dac1: Option<DAC<DAC1>>,
+        dac2: Option<DAC<DAC2>>,
+        sample_rate: T,
+        clock_control: ClockControlConfig,
+    ) -> Result<Self, Error> {
+        if dac1.is_none() && dac2.is_none() {
+            return Err(Error::NoChannels);
+        }
+
+        let pll_lock = clock_control.lock_plld2();
+
+        instance.reset().enable();
+        init_registers(&instance);
+
+        unsafe {
+            // the right channel drives DAC1, the left DAC2
+            instance
+                .conf
+                .write(|w| w.bits(0).tx_right_first().set_bit());
+            instance
+                .conf_chan
+                .write(|w| w.bits(0).tx_chan_mod().bits(0));
+            instance.fifo_conf.write(|w| {
+                w.bits(0)
+                    .dscr_en()
+                    .set_bit()
+                    .tx_fifo_mod_force_en()
+                    .set_bit()
+                    .tx_fifo_mod()
+                    .bits(0)
+                    .tx_data_num()
+                    .bits(32)
+            });
+        }
+
+        let sample_rate = set_sample_rate(&instance, &clock_control, sample_rate.into())?;
+        i2s::reset_all(&instance);
+
+        let sensors = unsafe { &*SENS::ptr() };
+        sensors
+            .sar_dac_ctrl2
+            .modify(|_, w| w.dac_cw_en1().clear_bit().dac_cw_en2().clear_bit());
+        sensors
+            .sar_dac_ctrl1
+            .modify(|_, w| w.dac_dig_force().set_bit().dac_clk_inv().set_bit());
+
+        Ok(StreamingDAC {
+            instance,
+            dac1,
+            dac2,
+            sample_rate,
+            ring: None,
+            _pll_lock: pll_lock,
+        })
+    }
+
+    /// The actual sample rate
+    pub fn sample_rate(&self) -> Hertz {
+        self.sample_rate
+    }
+
+    /// Store the values for DAC1 and DAC2 of the `index`th frame in a buffer
+    pub fn set_frame(buffer: &mut [u8], index: usize, dac1: u8, dac2: u8) {
+        // the 8 bit DAC value is taken from the upper byte of the 16 bit sample
+        buffer[4 * index + sample_offset(0) + 1] = dac1;
+        buffer[4 * index + sample_offset(1) + 1] = dac2;
+    }
+
+    /// Start playing from a ring buffer
+    ///
+    /// The buffer is split into at least two parts of `part_len` bytes, each part needs one
+    /// descriptor.
+    /// The buffer should be filled with the initial data before starting.
+    pub fn start(
+        &mut self,
+        buffer: &'static mut [u8],
+        descriptors: &'static mut [Descriptor],
+        part_len: usize,
+    ) -> Result<(), Error> {
+        if self.ring.is_some() {
+            return Err(Error::I2S(i2s::Error::Busy));
+        }
+
+        let (ring, address) = Ring::new(buffer, descriptors, part_len, true).map_err(Error::I2S)?;
+        i2s::start_tx_dma(&self.instance, address);
+        self.ring = Some(ring);
+
+        Ok(())
+    }
+
+    /// Get the part of the ring buffer which has just been played, so it can be refilled
+    ///
+    /// Each part is only returned once. If the part is not refilled before the DMA engine
+    /// reaches it again, old data will be played.
+    pub fn next_part(&mut self) -> nb::Result<&mut [u8], Error> {
+        let address = self.instance.out_eof_des_addr.read().bits();
+        let ring = self
+            .ring
+            .as_mut()
+            .ok_or(nb::Error::Other(Error::I2S(i2s::Error::NotRunning)))?;
+
+        if self
+            .instance
+            .int_raw
+            .read()
+            .out_eof_int_raw()
+            .bit_is_clear()
+        {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        ring.next_part(address).map_err(|error| match error {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(error) => nb::Error::Other(Error::I2S(error)),
+        })
+    }
+
+    /// Stop playing and return the ring buffer
+    pub fn stop(&mut self) -> Result<RingBuffers, Error> {
+        let ring = self.ring.take().ok_or(Error::I2S(i2s::Error::NotRunning))?;
+        i2s::stop_tx_dma(&self.instance);
+        Ok(ring.into_buffers())
+    }
+
+    /// Release the I2S0 peripheral, the DACs and the ring buffer (if running)
+    ///
+    /// A running transfer is stopped. The DACs are switched back to software control.
+    pub fn release(
+        mut self,
+    ) -> (
+        I2S0,
+        Option<DAC<DAC1>>,
+        Option<DAC<DAC2>>,
+        Option<RingBuffers>,
+    ) {
+        let ring = self.stop().ok();
+
+        let sensors = unsafe { &*SENS::ptr() };
+        sensors
+            .sar_dac_ctrl1
+            .modify(|_, w| w.dac_dig_force().clear_bit());
+
+        self.instance.disable();
+        (self.instance, self.dac1, self.dac2, ring)
+    }
+}
@@ -3,11 +3,24 @@
 //! Provides functionality for using analog peripherals, such as ADCs, DACs
 //! and other sesnsors.
 //!
+//! Next to the single sample interfaces in [adc] and [dac], [i2s] provides
+//! high speed sampling and playback using the DMA of the I2S0 peripheral.
+//!
+//! [calibration] converts raw ADC readings to millivolts using the calibration values in the
+//! eFuse.
+//!
+//! [touch] measures the capacitance of the touch pads and [temperature] reads the on-die
+//! temperature sensor.
+//!
 
 pub mod adc;
+pub mod calibration;
 pub mod config;
 pub mod dac;
 pub mod hall;
+pub mod i2s;
+pub mod temperature;
+pub mod touch;
 
 use crate::target::SENS;
 use core::marker::PhantomData;
@@ -27,11 +40,21 @@ pub struct DAC2 {
     _private: PhantomData<()>,
 }
 
+pub struct TOUCH {
+    _private: PhantomData<()>,
+}
+
+pub struct TSENS {
+    _private: PhantomData<()>,
+}
+
 pub struct AvailableAnalog {
     pub adc1: ADC1,
     pub adc2: ADC2,
     pub dac1: DAC1,
     pub dac2: DAC2,
+    pub touch: TOUCH,
+    pub tsens: TSENS,
 }
 
 pub trait SensExt {
@@ -53,6 +76,12 @@ impl SensExt for SENS {
             dac2: DAC2 {
                 _private: PhantomData,
             },
+            touch: TOUCH {
+                _private: PhantomData,
+            },
+            tsens: TSENS {
+                _private: PhantomData,
+            },
         }
     }
 }
@@ -0,0 +1,118 @@
+//! Internal temperature sensor
+//!
+//! The on-die temperature sensor measures the temperature of the chip, which is higher than the
+//! ambient temperature and depends on the load (CPU frequency, radio). The sensor is not
+//! calibrated, so the conversion to Celsius is only approximate. The offset can be set per board
+//! by comparing with a reference measurement.
+//!
+//! The readings can be used for thermal throttling, e.g. by dropping a
+//! [LockCPU][crate::clock_control::dfs::LockCPU] when the chip gets too hot:
+//!
+//! ```no_run
+//! let mut sensor = TemperatureSensor::new(analog.tsens);
+//! sensor.set_offset(-5.0);
+//!
+//! if sensor.read_celsius() > 80.0 {
+//!     drop(cpu_lock);
+//! }
+//! ```
+//!
+//! *Note: the conversion uses [sleep][crate::clock_control::sleep], so it is only available
+//! once the clock control is frozen.*
+
+use crate::analog::TSENS;
+use crate::clock_control::sleep;
+use crate::target::SENS;
+use crate::units::*;
+
+/// Divider of the sensor clock
+const CLOCK_DIVIDER: u8 = 10;
+
+/// Time for the sensor to settle after power up
+const POWER_UP_TIME: MicroSeconds = MicroSeconds(100);
+
+/// Time for a conversion
+const CONVERSION_TIME: MicroSeconds = MicroSeconds(5);
+
+/// Temperature sensor
+pub struct TemperatureSensor {
+    tsens: TSENS,
+    offset: f32,
+}
+
+impl TemperatureSensor {
+    /// Power up the temperature sensor
+    pub fn new(tsens: TSENS) -> Self {
+        let sensors = unsafe { &*SENS::ptr() };
+
+        // power up the SAR ADC, which is shared with the ADCs
+        sensors
+            .sar_meas_wait2
+            .modify(|_, w| unsafe { w.force_xpd_sar().bits(0b11) });
+
+        sensors.sar_tsens_ctrl.modify(|_, w| unsafe {
+            w.tsens_clk_div()
+                .bits(CLOCK_DIVIDER)
+                .tsens_dump_out()
+                .clear_bit()
+                .tsens_power_up_force()
+                .set_bit()
+                .tsens_power_up()
+                .set_bit()
+        });
+
+        sleep(POWER_UP_TIME);
+
+        TemperatureSensor { tsens, offset: 0.0 }
+    }
+
+    /// Trigger a conversion and return the raw value
+    pub fn read_raw(&mut self) -> u8 {
+        let sensors = unsafe { &*SENS::ptr() };
+
+        sensors
+            .sar_tsens_ctrl
+            .modify(|_, w| w.tsens_dump_out().set_bit());
+
+        sleep(CONVERSION_TIME);
+
+        let raw = sensors.sar_slave_addr3.read().tsens_out().bits();
+
+        sensors
+            .sar_tsens_ctrl
+            .modify(|_, w| w.tsens_dump_out().clear_bit());
+
+        raw
+    }
+
+    /// Trigger a conversion and return the approximate temperature in Celsius
+    pub fn read_celsius(&mut self) -> f32 {
+        self.raw_to_celsius(self.read_raw())
+    }
+
+    /// Convert a raw value to Celsius, including the offset
+    pub fn raw_to_celsius(&self, raw: u8) -> f32 {
+        (raw as f32 - 32.0) / 1.8 + self.offset
+    }
+
+    /// Offset in Celsius added to the converted temperature
+    pub fn offset(&self) -> f32 {
+        self.offset
+    }
+
+    /// Set the offset in Celsius added to the converted temperature
+    pub fn set_offset(&mut self, offset: f32) {
+        self.offset = offset;
+    }
+
+    /// Power down the temperature sensor
+    pub fn release(self) -> TSENS {
+        let sensors = unsafe { &*SENS::ptr() };
+
+        sensors
+            .sar_tsens_ctrl
+            .modify(|_, w| w.tsens_power_up().clear_bit());
+
+        self.tsens
+    }
+}
@@ -0,0 +1,438 @@
+//! Capacitive touch sensor
+//!
+//! The ESP32 has 10 touch pads, which measure the capacitance of the pin by counting the
+//! charge/discharge cycles between a high and a low voltage during a measurement. Touching the
+//! pad increases the capacitance and therefore decreases the count.
+//!
+//! | Pad | Pin           |
+//! |-----|---------------|
+//! | 0   | GPIO4         |
+//! | 1   | GPIO0         |
+//! | 2   | GPIO2         |
+//! | 3   | GPIO15 (MTDO) |
+//! | 4   | GPIO13 (MTCK) |
+//! | 5   | GPIO12 (MTDI) |
+//! | 6   | GPIO14 (MTMS) |
+//! | 7   | GPIO27        |
+//! | 8   | GPIO33        |
+//! | 9   | GPIO32        |
+//!
+//! The measurements are triggered by a timer and run continuously (also during sleep). The pins
+//! need to be in analog mode:
+//!
+//! ```no_run
+//! let pin4 = pins.gpio4.into_analog();
+//!
+//! let mut config = TouchConfig::new();
+//! config.enable_pin(&pin4, TouchPadConfig::default());
+//! let mut touch = Touch::new(analog.touch, config);
+//!
+//! let mut filter = TouchFilter::new(4, 8, 50);
+//! loop {
+//!     if filter.update(touch.read(&pin4)) {
+//!         // touched
+//!     }
+//! }
+//! ```
+//!
+//! A pad with a threshold triggers the RTC_CORE interrupt when its value crosses the threshold
+//! (see [Touch::enable_interrupt]).
+
+use embedded_hal::adc::Channel;
+
+use crate::analog::config::{self, TOUCH_PADS};
+use crate::analog::TOUCH;
+use crate::gpio::*;
+use crate::target::{RTCCNTL, RTCIO, SENS};
+
+/// Time between powering up a pad and the start of the measurement in 8MHz cycles
+const XPD_WAIT: u8 = 0xff;
+
+/// Touch sensor
+pub struct Touch {
+    touch: TOUCH,
+}
+
+impl Touch {
+    /// Configure the touch sensor and start the measurements of the enabled pads
+    pub fn new(touch: TOUCH, config: config::TouchConfig) -> Self {
+        let sensors = unsafe { &*SENS::ptr() };
+        let rtcio = unsafe { &*RTCIO::ptr() };
+        let rtccntl = unsafe { &*RTCCNTL::ptr() };
+
+        rtcio.touch_cfg.modify(|_, w| unsafe {
+            w.touch_xpd_bias()
+                .set_bit()
+                .touch_drefh()
+                .bits(config.high_voltage as u8)
+                .touch_drefl()
+                .bits(config.low_voltage as u8)
+                .touch_drange()
+                .bits(config.high_voltage_attenuation as u8)
+        });
+
+        sensors.sar_touch_ctrl1.modify(|_, w| unsafe {
+            w.touch_meas_delay()
+                .bits(config.measurement_cycles)
+                .touch_xpd_wait()
+                .bits(XPD_WAIT)
+                .touch_out_sel()
+                .bit(config.trigger == config::TouchTrigger::Above)
+                // interrupt if any pad of set 1 is touched
+                .touch_out_1en()
+                .set_bit()
+        });
+
+        // Measurements triggered by the timer of the FSM
+        sensors.sar_touch_ctrl2.modify(|_, w| unsafe {
+            w.touch_sleep_cycles()
+                .bits(config.sleep_cycles)
+                .touch_start_fsm_en()
+                .set_bit()
+                .touch_start_force()
+                .clear_bit()
+        });
+
+        let mut pads = 0;
+        for (channel, pad) in config.pads.iter().enumerate() {
+            if let Some(pad) = pad {
+                let channel = channel as u8;
+                pads |= 1 << hw_channel(channel);
+                init_pad(channel, pad);
+                set_threshold(channel, pad.threshold);
+            }
+        }
+
+        sensors.sar_touch_enable.modify(|_, w| unsafe {
+            w.touch_pad_worken()
+                .bits(pads)
+                .touch_pad_outen1()
+                .bits(pads)
+                .touch_pad_outen2()
+                .bits(0)
+        });
+
+        sensors
+            .sar_touch_ctrl2
+            .modify(|_, w| w.touch_meas_en_clr().set_bit());
+        rtccntl.int_clr.write(|w| w.rtc_touch_int_clr().set_bit());
+        rtccntl
+            .state0
+            .modify(|_, w| w.touch_slp_timer_en().set_bit());
+
+        Touch { touch }
+    }
+
+    /// Read the last measurement of a pad
+    ///
+    /// The value is 0 until the first measurement has finished.
+    pub fn read<PIN: Channel<TOUCH, ID = u8>>(&self, _pin: &PIN) -> u16 {
+        read_raw(PIN::channel())
+    }
+
+    /// Set the interrupt threshold of a pad
+    pub fn set_threshold<PIN: Channel<TOUCH, ID = u8>>(&mut self, _pin: &PIN, threshold: u16) {
+        set_threshold(PIN::channel(), threshold);
+    }
+
+    /// Enable/disable the touch interrupt (RTC_CORE interrupt)
+    pub fn enable_interrupt(&mut self, enable: bool) {
+        unsafe { &*RTCCNTL::ptr() }
+            .int_ena
+            .modify(|_, w| w.rtc_touch_int_ena().bit(enable));
+    }
+
+    /// Returns true if the touch interrupt is pending
+    pub fn is_interrupt_pending(&self) -> bool {
+        unsafe { &*RTCCNTL::ptr() }
+            .int_st
+            .read()
+            .rtc_touch_int_st()
+            .bit_is_set()
+    }
+
+    /// Pads which crossed the threshold since the last [clear_interrupt][Self::clear_interrupt]
+    ///
+    /// Bit `n` corresponds to touch pad n.
+    pub fn touched_pads(&self) -> u16 {
+        let pads = unsafe { &*SENS::ptr() }
+            .sar_touch_ctrl2
+            .read()
+            .touch_meas_en()
+            .bits();
+        swap_pads_8_9(pads)
+    }
+
+    /// Clear the touch interrupt and the touched pads
+    pub fn clear_interrupt(&mut self) {
+        unsafe { &*SENS::ptr() }
+            .sar_touch_ctrl2
+            .modify(|_, w| w.touch_meas_en_clr().set_bit());
+        unsafe { &*RTCCNTL::ptr() }
+            .int_clr
+            .write(|w| w.rtc_touch_int_clr().set_bit());
+    }
+
+    /// Stop the measurements and release the touch sensor
+    pub fn release(self) -> TOUCH {
+        let sensors = unsafe { &*SENS::ptr() };
+
+        unsafe { &*RTCCNTL::ptr() }
+            .state0
+            .modify(|_, w| w.touch_slp_timer_en().clear_bit());
+        sensors.sar_touch_enable.modify(|_, w| unsafe {
+            w.touch_pad_worken()
+                .bits(0)
+                .touch_pad_outen1()
+                .bits(0)
+                .touch_pad_outen2()
+                .bits(0)
+        });
+
+        for channel in 0..TOUCH_PADS as u8 {
+            power_down_pad(channel);
+        }
+
+        self.touch
+    }
+}
+
+/// Software filter for touch pad readings
+///
+/// The readings are smoothed by an IIR low pass filter. A slower IIR filter tracks the baseline
+/// (the untouched value) to compensate for drift of temperature and humidity, it is paused while
+/// the pad is touched. The pad is touched when the filtered value is more than `threshold`
+/// below the baseline and released when the difference falls below half the threshold.
+#[derive(Clone, Copy, Debug)]
+pub struct TouchFilter {
+    /// Filtered value with 8 fractional bits
+    value: i32,
+    /// Baseline with 8 fractional bits
+    baseline: i32,
+    filter_shift: u8,
+    baseline_shift: u8,
+    threshold: u16,
+    touched: bool,
+    initialized: bool,
+}
+
+impl TouchFilter {
+    /// Create a filter
+    ///
+    /// Each new reading contributes 1/2^`filter_shift` to the filtered value and each filtered
+    /// value contributes 1/2^`baseline_shift` to the baseline.
+    pub fn new(filter_shift: u8, baseline_shift: u8, threshold: u16) -> Self {
+        TouchFilter {
+            value: 0,
+            baseline: 0,
+            filter_shift: filter_shift.min(16),
+            baseline_shift: baseline_shift.min(16),
+            threshold,
+            touched: false,
+            initialized: false,
+        }
+    }
+
+    /// Add a reading and return true if the pad is touched
+    pub fn update(&mut self, raw: u16) -> bool {
+        let raw = (raw as i32) << 8;
+
+        if !self.initialized {
+            self.value = raw;
+            self.baseline = raw;
+            self.initialized = true;
+            return false;
+        }
+
+        self.value += (raw - self.value) >> self.filter_shift;
+
+        let difference = (self.baseline - self.value) >> 8;
+        let threshold = self.threshold as i32;
+        if self.touched {
+            self.touched = difference >= threshold / 2;
+        } else {
+            self.touched = difference > threshold;
+        }
+
+        if !self.touched {
+            self.baseline += (self.value - self.baseline) >> self.baseline_shift;
+        }
+
+        self.touched
+    }
+
+    /// Returns true if the pad is touched
+    pub fn is_touched(&self) -> bool {
+        self.touched
+    }
+
+    /// Filtered value
+    pub fn value(&self) -> u16 {
+        (self.value >> 8) as u16
+    }
+
+    /// Baseline (untouched value)
+    pub fn baseline(&self) -> u16 {
+        (self.baseline >> 8) as u16
+    }
+
+    /// Restart the filter with the next reading
+    pub fn reset(&mut self) {
+        self.touched = false;
+        self.initialized = false;
+    }
+}
+
+/// Pad number used in the SENS registers, which have pads 8 and 9 swapped
+fn hw_channel(channel: u8) -> u8 {
+    match channel {
+        8 => 9,
+        9 => 8,
+        channel => channel,
+    }
+}
+
+/// Swap the bits of pads 8 and 9 in a pad mask
+fn swap_pads_8_9(pads: u16) -> u16 {
+    (pads & !0x300) | ((pads >> 1) & 0x100) | ((pads << 1) & 0x200)
+}
+
+/// Power up a pad and set its charge configuration
+fn init_pad(channel: u8, config: &config::TouchPadConfig) {
+    let rtcio = unsafe { &*RTCIO::ptr() };
+
+    macro_rules! init_pad {
+        ($($channel:literal: $pad:ident,)+) => {
+            match channel {
+                $(
+                    $channel => rtcio.$pad.modify(|_, w| unsafe {
+                        w.xpd()
+                            .set_bit()
+                            .dac()
+                            .bits(config.slope & 0x7)
+                            .tie_opt()
+                            .bit(config.initial_charge == config::TouchInitialCharge::High)
+                    }),
+                )+
+                _ => unreachable!(),
+            }
+        };
+    }
+
+    // shared registers without set/clear functionality, so needs lock
+    (&RTCIO_LOCK).lock(|_| {
+        init_pad!(
+            0: touch_pad0, 1: touch_pad1, 2: touch_pad2, 3: touch_pad3, 4: touch_pad4,
+            5: touch_pad5, 6: touch_pad6, 7: touch_pad7, 8: touch_pad8, 9: touch_pad9,
+        )
+    });
+}
+
+/// Power down a pad
+fn power_down_pad(channel: u8) {
+    let rtcio = unsafe { &*RTCIO::ptr() };
+
+    macro_rules! power_down_pad {
+        ($($channel:literal: $pad:ident,)+) => {
+            match channel {
+                $(
+                    $channel => rtcio.$pad.modify(|_, w| w.xpd().clear_bit()),
+                )+
+                _ => unreachable!(),
+            }
+        };
+    }
+
+    // shared registers without set/clear functionality, so needs lock
+    (&RTCIO_LOCK).lock(|_| {
+        power_down_pad!(
+            0: touch_pad0, 1: touch_pad1, 2: touch_pad2, 3: touch_pad3, 4: touch_pad4,
+            5: touch_pad5, 6: touch_pad6, 7: touch_pad7, 8: touch_pad8, 9: touch_pad9,
+        )
+    });
+}
+
+fn set_threshold(channel: u8, threshold: u16) {
+    let sensors = unsafe { &*SENS::ptr() };
+
+    macro_rules! set_threshold {
+        ($($channel:literal: $reg:ident, $field:ident,)+) => {
+            match hw_channel(channel) {
+                $(
+                    $channel => sensors
+                        .$reg
+                        .modify(|_, w| unsafe { w.$field().bits(threshold) }),
+                )+
+                _ => unreachable!(),
+            }
+        };
+    }
+
+    set_threshold!(
+        0: sar_touch_thres1, touch_out_th0,
+        1: sar_touch_thres1, touch_out_th1,
+        2: sar_touch_thres2, touch_out_th2,
+        3: sar_touch_thres2, touch_out_th3,
+        4: sar_touch_thres3, touch_out_th4,
+        5: sar_touch_thres3, touch_out_th5,
+        6: sar_touch_thres4, touch_out_th6,
+        7: sar_touch_thres4, touch_out_th7,
+        8: sar_touch_thres5, touch_out_th8,
+        9: sar_touch_thres5, touch_out_th9,
+    );
+}
+
+fn read_raw(channel: u8) -> u16 {
+    let sensors = unsafe { &*SENS::ptr() };
+
+    macro_rules! read_raw {
+        ($($channel:literal: $reg:ident, $field:ident,)+) => {
+            match hw_channel(channel) {
+                $(
+                    $channel => sensors.$reg.read().$field().bits(),
+                )+
+                _ => unreachable!(),
+            }
+        };
+    }
+
+    read_raw!(
+        0: sar_touch_out1, touch_meas_out0,
+        1: sar_touch_out1, touch_meas_out1,
+        2: sar_touch_out2, touch_meas_out2,
+        3: sar_touch_out2, touch_meas_out3,
+        4: sar_touch_out3, touch_meas_out4,
+        5: sar_touch_out3, touch_meas_out5,
+        6: sar_touch_out4, touch_meas_out6,
+        7: sar_touch_out4, touch_meas_out7,
+        8: sar_touch_out5, touch_meas_out8,
+        9: sar_touch_out5, touch_meas_out9,
+    )
+}
+
+macro_rules! impl_touch_channel {
+    ($($pin:ident: $channel:expr,)+) => {
+        $(
+            impl Channel<TOUCH> for $pin<Analog> {
+                type ID = u8;
+
+                fn channel() -> u8 {
+                    $channel
+                }
+            }
+        )+
+    };
+}
+
+impl_touch_channel! {
+    Gpio4: 0,
+    Gpio0: 1,
+    Gpio2: 2,
+    Gpio15: 3,
+    Gpio13: 4,
+    Gpio12: 5,
+    Gpio14: 6,
+    Gpio27: 7,
+    Gpio33: 8,
+    Gpio32: 9,
+}
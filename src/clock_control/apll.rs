@@ -0,0 +1,194 @@
+//! Audio PLL control
+//!
+//! The APLL generates a configurable frequency for use by the I2S peripherals:
+//!
+//! `f_out = f_xtal * (sdm2 + sdm1 / 2^8 + sdm0 / 2^16 + 4) / (2 * (o_div + 2))`
+//!
+//! with the VCO frequency `f_xtal * (sdm2 + sdm1 / 2^8 + sdm0 / 2^16 + 4)` between
+//! 350MHz and 500MHz. On revision 0 chips the fractional part (sdm0 and sdm1) is not available.
+
+use super::Error;
+use crate::efuse::Efuse;
+use crate::prelude::*;
+
+// Address for the APLL on the internal I2C bus
+const I2C_BLOCK: u8 = 0x6d;
+
+// Register addresses for internal I2C bus
+mod i2c {
+    pub const IR_CAL_DELAY: u8 = 0;
+    pub const OR_CAL_END: u8 = 3;
+    pub const OR_OUTPUT_DIV: u8 = 4;
+    pub const SDM_STOP: u8 = 5;
+    pub const DSDM2: u8 = 7;
+    pub const DSDM1: u8 = 8;
+    pub const DSDM0: u8 = 9;
+}
+
+// Values and masks for internal I2C registers
+mod val {
+    pub const OR_CAL_END_MASK: u8 = 1 << 6;
+    pub const OR_OUTPUT_DIV_MASK: u8 = 0x1f;
+    pub const DSDM2_MASK: u8 = 0x3f;
+    pub const SDM_STOP_VAL_1: u8 = 0x09;
+    pub const SDM_STOP_VAL_2_REV0: u8 = 0x69;
+    pub const SDM_STOP_VAL_2_REV1: u8 = 0x49;
+    pub const CAL_DELAY_1: u8 = 0x0f;
+    pub const CAL_DELAY_2: u8 = 0x3f;
+    pub const CAL_DELAY_3: u8 = 0x1f;
+}
+
+// Limits of the APLL VCO
+const APLL_VCO_MIN: Hertz = Hertz(350_000_000);
+const APLL_VCO_MAX: Hertz = Hertz(500_000_000);
+
+// Maximum output divider
+const APLL_O_DIV_MAX: u32 = 31;
+
+// Maximum time to wait for the calibration to finish
+const APLL_CALIBRATION_TIMEOUT: MicroSeconds = MicroSeconds(10_000);
+const APLL_CALIBRATION_POLL: MicroSeconds = MicroSeconds(1);
+
+/// APLL coefficients
+#[derive(Debug, Copy, Clone)]
+struct Coefficients {
+    sdm0: u8,
+    sdm1: u8,
+    sdm2: u8,
+    o_div: u8,
+}
+
+impl Coefficients {
+    /// Calculate the coefficients for the requested frequency
+    fn calculate(xtal: Hertz, frequency: Hertz, fractional: bool) -> Result<Self, Error> {
+        let frequency = u32::from(frequency) as u64;
+        let vco_min = u32::from(APLL_VCO_MIN) as u64;
+        let vco_max = u32::from(APLL_VCO_MAX) as u64;
+
+        if frequency * 4 > vco_max {
+            return Err(Error::FrequencyTooHigh);
+        }
+
+        // use the smallest output divider for which the VCO is in range
+        let o_div = (0..=APLL_O_DIV_MAX as u64)
+            .find(|o_div| frequency * 2 * (o_div + 2) >= vco_min)
+            .ok_or(Error::FrequencyTooLow)?;
+
+        let vco = frequency * 2 * (o_div + 2);
+        let xtal = u32::from(xtal) as u64;
+
+        // multiplier in 16.16 fixed point notation (rounded)
+        let multiplier = (vco * 65536 + xtal / 2) / xtal;
+        if multiplier < 4 * 65536 || multiplier >= (4 + 64) * 65536 {
+            return Err(Error::UnsupportedPLLConfig);
+        }
+        let sdm = multiplier - 4 * 65536;
+
+        let mut coefficients = Coefficients {
+            sdm0: (sdm & 0xff) as u8,
+            sdm1: ((sdm >> 8) & 0xff) as u8,
+            sdm2: (sdm >> 16) as u8,
+            o_div: o_div as u8,
+        };
+
+        if !fractional {
+            // round to the nearest integer multiplier
+            if coefficients.sdm1 >= 0x80 && coefficients.sdm2 < val::DSDM2_MASK {
+                coefficients.sdm2 += 1;
+            }
+            coefficients.sdm1 = 0;
+            coefficients.sdm0 = 0;
+        }
+
+        Ok(coefficients)
+    }
+
+    /// The output frequency for these coefficients
+    fn frequency(&self, xtal: Hertz) -> Hertz {
+        let multiplier =
+            ((self.sdm2 as u64 + 4) << 16) | ((self.sdm1 as u64) << 8) | self.sdm0 as u64;
+        Hertz(((u32::from(xtal) as u64 * multiplier) / (2 * (self.o_div as u64 + 2)) >> 16) as u32)
+    }
+}
+
+impl super::ClockControl {
+    /// Set the APLL to the nearest possible frequency and enable it
+    ///
+    /// Returns the actual frequency.
+    pub(crate) fn set_apll_frequency(&mut self, frequency: Hertz) -> Result<Hertz, Error> {
+        let revision_0 = Efuse::is_chip_revision_0();
+        let coefficients = Coefficients::calculate(self.xtal_frequency, frequency, !revision_0)?;
+
+        self.apll_enable(coefficients, revision_0)?;
+        self.apll_frequency = coefficients.frequency(self.xtal_frequency);
+
+        Ok(self.apll_frequency)
+    }
+
+    /// The frequency the APLL would run at when set to the requested frequency
+    pub(crate) fn apll_frequency_for(&self, frequency: Hertz) -> Result<Hertz, Error> {
+        let revision_0 = Efuse::is_chip_revision_0();
+        Coefficients::calculate(self.xtal_frequency, frequency, !revision_0)
+            .map(|coefficients| coefficients.frequency(self.xtal_frequency))
+    }
+
+    /// Power up and configure the APLL
+    fn apll_enable(&mut self, coefficients: Coefficients, revision_0: bool) -> Result<(), Error> {
+        self.rtc_control
+            .ana_conf
+            .modify(|_, w| w.plla_force_pd().clear_bit().plla_force_pu().set_bit());
+        self.rtc_control
+            .options0
+            .modify(|_, w| w.bias_i2c_force_pd().clear_bit());
+
+        let dsdm2 = self.read_i2c_block(I2C_BLOCK, i2c::DSDM2) & !val::DSDM2_MASK;
+        self.write_i2c_block(I2C_BLOCK, i2c::DSDM2, dsdm2 | coefficients.sdm2);
+        self.write_i2c_block(I2C_BLOCK, i2c::DSDM0, coefficients.sdm0);
+        self.write_i2c_block(I2C_BLOCK, i2c::DSDM1, coefficients.sdm1);
+        self.write_i2c_block(I2C_BLOCK, i2c::SDM_STOP, val::SDM_STOP_VAL_1);
+        self.write_i2c_block(
+            I2C_BLOCK,
+            i2c::SDM_STOP,
+            if revision_0 {
+                val::SDM_STOP_VAL_2_REV0
+            } else {
+                val::SDM_STOP_VAL_2_REV1
+            },
+        );
+
+        let o_div = self.read_i2c_block(I2C_BLOCK, i2c::OR_OUTPUT_DIV) & !val::OR_OUTPUT_DIV_MASK;
+        self.write_i2c_block(I2C_BLOCK, i2c::OR_OUTPUT_DIV, o_div | coefficients.o_div);
+
+        // calibration
+        self.write_i2c_block(I2C_BLOCK, i2c::IR_CAL_DELAY, val::CAL_DELAY_1);
+        self.write_i2c_block(I2C_BLOCK, i2c::IR_CAL_DELAY, val::CAL_DELAY_2);
+        self.write_i2c_block(I2C_BLOCK, i2c::IR_CAL_DELAY, val::CAL_DELAY_3);
+
+        let mut timeout = APLL_CALIBRATION_TIMEOUT / APLL_CALIBRATION_POLL;
+        while self.read_i2c_block(I2C_BLOCK, i2c::OR_CAL_END) & val::OR_CAL_END_MASK == 0 {
+            if timeout == 0 {
+                return Err(Error::CalibrationTimeOut);
+            }
+            timeout -= 1;
+            // wait so the internal bus does not get flooded
+            self.delay(APLL_CALIBRATION_POLL);
+        }
+
+        Ok(())
+    }
+
+    /// Power down the APLL
+    pub(crate) fn apll_disable(&mut self) {
+        self.rtc_control
+            .ana_conf
+            .modify(|_, w| w.plla_force_pd().set_bit().plla_force_pu().clear_bit());
+
+        // is PLL also powered down? then also power down the internal I2C bus
+        let pll_off = self.rtc_control.options0.read().bbpll_force_pd().bit();
+        self.rtc_control
+            .options0
+            .modify(|_, w| w.bias_i2c_force_pd().bit(pll_off));
+
+        self.apll_frequency = super::FREQ_OFF;
+    }
+}
@@ -139,6 +139,16 @@ impl<'a> super::ClockControlConfig {
         unsafe { CLOCK_CONTROL.as_mut().unwrap().lock_plld2() }
     }
 
+    /// Obtain a RAII lock to run the APLL at the nearest possible frequency
+    ///
+    /// The first lock enables the APLL, the APLL is powered down when the last lock is dropped.
+    /// While locked, other locks need to request a frequency resulting in the same APLL
+    /// settings, otherwise [Error::APLLInUse] is returned. The actual frequency is returned by
+    /// [Self::apll_frequency].
+    pub fn lock_apll<T: Into<Hertz>>(&self, frequency: T) -> Result<dfs::LockApll, Error> {
+        unsafe { CLOCK_CONTROL.as_mut().unwrap().lock_apll(frequency.into()) }
+    }
+
     /// Add callback which will be called when clock speeds are changed.
     ///
     /// NOTE: these callbacks are called in an interrupt free environment,
@@ -181,6 +191,29 @@ impl<'a> super::ClockControlConfig {
             .lock(|_| unsafe { CLOCK_CONTROL.as_mut().unwrap().start_app_core(entry) })
     }
 
+    /// Set the APLL to the nearest possible frequency and enable it
+    ///
+    /// Returns the actual frequency. The APLL can be configured between about 5MHz and 125MHz.
+    /// It is used as clock source by e.g. the I2S peripherals to obtain accurate audio sample
+    /// rates. Returns [Error::APLLInUse] while the APLL is locked with [Self::lock_apll].
+    pub fn set_apll_frequency<T: Into<Hertz>>(&mut self, frequency: T) -> Result<Hertz, Error> {
+        let frequency = frequency.into();
+        (&CLOCK_CONTROL_MUTEX).lock(|_| unsafe {
+            CLOCK_CONTROL
+                .as_mut()
+                .unwrap()
+                .set_apll_frequency_checked(frequency)
+        })
+    }
+
+    /// Power down the APLL
+    ///
+    /// Returns [Error::APLLInUse] while the APLL is locked with [Self::lock_apll].
+    pub fn disable_apll(&mut self) -> Result<(), Error> {
+        (&CLOCK_CONTROL_MUTEX)
+            .lock(|_| unsafe { CLOCK_CONTROL.as_mut().unwrap().disable_apll_checked() })
+    }
+
     // The following routines handle thread and interrupt safety themselves
 
     /// Get RTC tick count since boot
@@ -9,13 +9,14 @@ use crate::prelude::*;
 /// maximum number of callbacks
 pub const MAX_CALLBACKS: usize = 10;
 
-/// number of cpu, apb, awake and pll_d2 locks
+/// number of cpu, apb, awake, pll_d2 and apll locks
 #[derive(Copy, Clone, Debug)]
 pub struct Locks {
     cpu: usize,
     apb: usize,
     awake: usize,
     pll_d2: usize,
+    apll: usize,
 }
 
 static DFS_MUTEX: CriticalSectionSpinLockMutex<Locks> = CriticalSectionSpinLockMutex::new(Locks {
@@ -23,6 +24,7 @@ static DFS_MUTEX: CriticalSectionSpinLockMutex<Locks> = CriticalSectionSpinLockM
     apb: 0,
     awake: 0,
     pll_d2: 0,
+    apll: 0,
 });
 
 /// DFS structure
@@ -62,6 +64,11 @@ pub struct LockAwake {}
 /// This structure is created by the lock_plld2 method on ClockControlConfig
 pub struct LockPllD2 {}
 
+/// A RAII implementation of a "scoped lock" for the APLL frequency.
+/// When this structure is dropped (falls out of scope), the lock will be unlocked.
+/// This structure is created by the lock_apll method on ClockControlConfig
+pub struct LockApll {}
+
 /// Drop of the RAII to unlock the CPU frequency
 impl<'a> Drop for LockCPU {
     fn drop(&mut self) {
@@ -104,6 +111,15 @@ impl<'a> Drop for LockPllD2 {
     }
 }
 
+/// Drop of the RAII to unlock the APLL frequency
+impl<'a> Drop for LockApll {
+    fn drop(&mut self) {
+        unsafe {
+            super::CLOCK_CONTROL.as_mut().unwrap().unlock_apll();
+        }
+    }
+}
+
 impl<'a> super::ClockControl {
     /// call all the callbacks
     fn do_callbacks(
@@ -317,6 +333,57 @@ impl<'a> super::ClockControl {
         });
     }
 
+    /// lock the APLL at a frequency, enabling it for the first lock
+    ///
+    /// Further locks need to request a frequency resulting in the same APLL settings.
+    pub(crate) fn lock_apll(&'a mut self, frequency: Hertz) -> Result<LockApll, Error> {
+        (&DFS_MUTEX).lock(|data| {
+            if data.apll == 0 {
+                self.set_apll_frequency(frequency)?;
+            } else if self.apll_frequency_for(frequency)? != self.apll_frequency {
+                return Err(Error::APLLInUse);
+            }
+
+            data.apll += 1;
+            Ok(LockApll {})
+        })
+    }
+
+    /// unlock the APLL frequency, powering down the APLL for the last lock
+    fn unlock_apll(&'a mut self) {
+        (&DFS_MUTEX).lock(|data| {
+            data.apll -= 1;
+
+            if data.apll == 0 {
+                self.apll_disable();
+            }
+        });
+    }
+
+    /// set the APLL frequency if it is not locked
+    pub(crate) fn set_apll_frequency_checked(
+        &'a mut self,
+        frequency: Hertz,
+    ) -> Result<Hertz, Error> {
+        (&DFS_MUTEX).lock(|data| {
+            if data.apll != 0 {
+                return Err(Error::APLLInUse);
+            }
+            self.set_apll_frequency(frequency)
+        })
+    }
+
+    /// power down the APLL if it is not locked
+    pub(crate) fn disable_apll_checked(&'a mut self) -> Result<(), Error> {
+        (&DFS_MUTEX).lock(|data| {
+            if data.apll != 0 {
+                return Err(Error::APLLInUse);
+            }
+            self.apll_disable();
+            Ok(())
+        })
+    }
+
     /// Add callback which will be called when clock speeds are changed.
     ///
     /// NOTE: these callbacks are called in an interrupt free environment,
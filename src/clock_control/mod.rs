@@ -9,7 +9,7 @@
 //! - LED clock selection in ledc peripheral
 //! - 8M and 8MD256 enable/disable
 //! - 150kHz enable/disable
-//! - APLL support
+//! - APLL as CPU clock source
 //! - Implement light sleep
 //! - 32kHz Xtal support
 //! - Allow 8.5MHz clock to be tuned
@@ -24,6 +24,7 @@ use crate::target::{APB_CTRL, RTCCNTL, TIMG0};
 use core::fmt;
 use xtensa_lx::timer::{delay, get_cycle_count};
 
+mod apll;
 pub mod config;
 pub mod cpu;
 pub mod dfs;
@@ -140,6 +141,8 @@ pub enum Error {
     InvalidRegisterValue,
     InvalidCore,
     CoreAlreadyRunning,
+    /// The APLL is locked at a different frequency
+    APLLInUse,
 }
 
 /// CPU/APB/REF clock source
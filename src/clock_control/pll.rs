@@ -66,9 +66,14 @@ impl Config {
 impl super::ClockControl {
     /// write to internal I2C PLL bus
     fn write_i2c(&mut self, address: u8, data: u8) {
+        self.write_i2c_block(I2C_BLOCK, address, data);
+    }
+
+    /// write to a block on the internal I2C bus
+    pub(super) fn write_i2c_block(&mut self, block: u8, address: u8, data: u8) {
         self.rtc_control.pll.write(|w| unsafe {
             w.block()
-                .bits(I2C_BLOCK)
+                .bits(block)
                 .addr()
                 .bits(address)
                 .data()
@@ -80,11 +85,11 @@ impl super::ClockControl {
         while self.rtc_control.pll.read().busy().bit_is_set() {}
     }
 
-    /// read from internal I2C PLL bus
-    fn _read_i2c(&mut self, address: u8) -> u8 {
+    /// read from a block on the internal I2C bus
+    pub(super) fn read_i2c_block(&mut self, block: u8, address: u8) -> u8 {
         self.rtc_control.pll.write(|w| unsafe {
             w.block()
-                .bits(I2C_BLOCK)
+                .bits(block)
                 .addr()
                 .bits(address)
                 .write()
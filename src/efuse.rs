@@ -85,6 +85,13 @@ impl Efuse {
         !efuse.blk0_rdata3.read().rd_chip_ver_dis_bt().bit()
     }
 
+    /// Returns true if the chip is a revision 0 (ECO0) chip
+    pub fn is_chip_revision_0() -> bool {
+        let efuse = unsafe { &*EFUSE::ptr() };
+
+        !efuse.blk0_rdata3.read().rd_chip_ver_rev1().bit()
+    }
+
     pub fn get_chip_type() -> ChipType {
         let efuse = unsafe { &*EFUSE::ptr() };
 
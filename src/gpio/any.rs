@@ -0,0 +1,405 @@
+//! Type erased pins
+//!
+//! Each pin has its own type, which makes it impossible to store different pins in an array or
+//! to select pins at runtime. [AnyPin] carries the pin number at runtime instead and can be
+//! created from any pin via `degrade()`:
+//!
+//! ```no_run
+//! let mut leds = [
+//!     pins.gpio2.into_push_pull_output().degrade(),
+//!     pins.gpio4.into_push_pull_output().degrade(),
+//!     pins.gpio5.into_push_pull_output().degrade(),
+//! ];
+//!
+//! for led in leds.iter_mut() {
+//!     led.set_high().unwrap();
+//! }
+//! ```
+//!
+//! [AnyPin] implements the same traits as the individual pins. As GPIO34-39 are input only,
+//! the output functions panic when called on one of these pins. Use [AnyPin::is_input_only] to
+//! check this at runtime.
+
+use super::*;
+
+/// Pin with the pin number determined at runtime
+pub struct AnyPin<MODE> {
+    number: u8,
+    _mode: PhantomData<MODE>,
+}
+
+// Call the body with `$pin` bound to the concrete pin with the number of `$self`
+macro_rules! dispatch_input {
+    ($self:ident, $pin:ident => $body:expr) => {
+        dispatch_input!(@pins $self, $pin => $body; [
+            Gpio0: 0, Gpio1: 1, Gpio2: 2, Gpio3: 3, Gpio4: 4, Gpio5: 5, Gpio6: 6, Gpio7: 7,
+            Gpio8: 8, Gpio9: 9, Gpio10: 10, Gpio11: 11, Gpio12: 12, Gpio13: 13, Gpio14: 14,
+            Gpio15: 15, Gpio16: 16, Gpio17: 17, Gpio18: 18, Gpio19: 19, Gpio20: 20, Gpio21: 21,
+            Gpio22: 22, Gpio23: 23, Gpio25: 25, Gpio26: 26, Gpio27: 27, Gpio32: 32, Gpio33: 33,
+            Gpio34: 34, Gpio35: 35, Gpio36: 36, Gpio37: 37, Gpio38: 38, Gpio39: 39,
+        ])
+    };
+    (@pins $self:ident, $pin:ident => $body:expr; [$($pxi:ident: $num:literal,)+]) => {
+        match $self.number {
+            $(
+                $num => {
+                    let $pin = &mut $pxi::<Unknown> { _mode: PhantomData };
+                    $body
+                }
+            )+
+            _ => unreachable!(),
+        }
+    };
+}
+
+// Call the body with `$pin` bound to the concrete pin with the number of `$self`,
+// panics for input only pins
+macro_rules! dispatch_output {
+    ($self:ident, $pin:ident => $body:expr) => {
+        dispatch_output!(@pins $self, $pin => $body; [
+            Gpio0: 0, Gpio1: 1, Gpio2: 2, Gpio3: 3, Gpio4: 4, Gpio5: 5, Gpio6: 6, Gpio7: 7,
+            Gpio8: 8, Gpio9: 9, Gpio10: 10, Gpio11: 11, Gpio12: 12, Gpio13: 13, Gpio14: 14,
+            Gpio15: 15, Gpio16: 16, Gpio17: 17, Gpio18: 18, Gpio19: 19, Gpio20: 20, Gpio21: 21,
+            Gpio22: 22, Gpio23: 23, Gpio25: 25, Gpio26: 26, Gpio27: 27, Gpio32: 32, Gpio33: 33,
+        ])
+    };
+    (@pins $self:ident, $pin:ident => $body:expr; [$($pxi:ident: $num:literal,)+]) => {
+        match $self.number {
+            $(
+                $num => {
+                    let $pin = &mut $pxi::<Unknown> { _mode: PhantomData };
+                    $body
+                }
+            )+
+            number => panic!("GPIO{} is input only", number),
+        }
+    };
+}
+
+impl<MODE> AnyPin<MODE> {
+    pub(super) fn new(number: u8) -> Self {
+        AnyPin {
+            number,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Returns true for the input only pins GPIO34-39
+    pub fn is_input_only(&self) -> bool {
+        self.number >= 34
+    }
+
+    fn into_mode<M>(self) -> AnyPin<M> {
+        AnyPin::new(self.number)
+    }
+
+    pub fn into_floating_input(mut self) -> AnyPin<Input<Floating>> {
+        self.set_to_input();
+        self.into_mode()
+    }
+
+    /// Configure as input with pull up
+    ///
+    /// *Note: panics for the input only pins GPIO34-39, which do not have pull up resistors*
+    pub fn into_pull_up_input(mut self) -> AnyPin<Input<PullUp>> {
+        self.set_to_input().internal_pull_up(true);
+        self.into_mode()
+    }
+
+    /// Configure as input with pull down
+    ///
+    /// *Note: panics for the input only pins GPIO34-39, which do not have pull down resistors*
+    pub fn into_pull_down_input(mut self) -> AnyPin<Input<PullDown>> {
+        self.set_to_input().internal_pull_down(true);
+        self.into_mode()
+    }
+
+    /// Configure as push/pull output
+    ///
+    /// *Note: panics for the input only pins GPIO34-39*
+    pub fn into_push_pull_output(mut self) -> AnyPin<Output<PushPull>> {
+        self.set_to_push_pull_output();
+        self.into_mode()
+    }
+
+    /// Configure as open drain output
+    ///
+    /// *Note: panics for the input only pins GPIO34-39*
+    pub fn into_open_drain_output(mut self) -> AnyPin<Output<OpenDrain>> {
+        self.set_to_open_drain_output();
+        self.into_mode()
+    }
+
+    #[inline(always)]
+    fn bit(&self) -> u32 {
+        1 << (self.number % 32)
+    }
+}
+
+impl<MODE> embedded_hal::digital::v2::InputPin for AnyPin<Input<MODE>> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        let gpio = unsafe { &*GPIO::ptr() };
+        let input = if self.number < 32 {
+            gpio.in_.read().in_data().bits()
+        } else {
+            gpio.in1.read().in1_data().bits()
+        };
+        Ok(input & self.bit() != 0)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+impl<MODE> embedded_hal::digital::v2::OutputPin for AnyPin<Output<MODE>> {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        // NOTE(unsafe) atomic write to a stateless register
+        let gpio = unsafe { &*GPIO::ptr() };
+        if self.number < 32 {
+            gpio.out_w1ts.write(|w| unsafe { w.bits(self.bit()) });
+        } else {
+            gpio.out1_w1ts.write(|w| unsafe { w.bits(self.bit()) });
+        }
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        // NOTE(unsafe) atomic write to a stateless register
+        let gpio = unsafe { &*GPIO::ptr() };
+        if self.number < 32 {
+            gpio.out_w1tc.write(|w| unsafe { w.bits(self.bit()) });
+        } else {
+            gpio.out1_w1tc.write(|w| unsafe { w.bits(self.bit()) });
+        }
+        Ok(())
+    }
+}
+
+impl<MODE> embedded_hal::digital::v2::StatefulOutputPin for AnyPin<Output<MODE>> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        let gpio = unsafe { &*GPIO::ptr() };
+        let output = if self.number < 32 {
+            gpio.out.read().bits()
+        } else {
+            gpio.out1.read().bits()
+        };
+        Ok(output & self.bit() != 0)
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_set_high()?)
+    }
+}
+
+impl<MODE> embedded_hal::digital::v2::toggleable::Default for AnyPin<Output<MODE>> {}
+
+impl<MODE> Pin for AnyPin<MODE> {
+    fn pin_number(&self) -> u8 {
+        self.number
+    }
+
+    fn sleep_mode(&mut self, on: bool) -> &mut Self {
+        dispatch_input!(self, pin => {
+            pin.sleep_mode(on);
+        });
+        self
+    }
+
+    fn set_alternate_function(&mut self, alternate: AlternateFunction) -> &mut Self {
+        dispatch_input!(self, pin => {
+            pin.set_alternate_function(alternate);
+        });
+        self
+    }
+
+    fn listen_with_options(
+        &mut self,
+        event: Event,
+        pro_int: bool,
+        app_int: bool,
+        pro_nmi: bool,
+        app_nmi: bool,
+        wake_up_from_light_sleep: bool,
+    ) {
+        dispatch_input!(self, pin => pin.listen_with_options(
+            event,
+            pro_int,
+            app_int,
+            pro_nmi,
+            app_nmi,
+            wake_up_from_light_sleep,
+        ))
+    }
+
+    fn unlisten(&mut self) {
+        dispatch_input!(self, pin => pin.unlisten())
+    }
+
+    fn clear_interrupt(&mut self) {
+        dispatch_input!(self, pin => pin.clear_interrupt())
+    }
+
+    fn is_interrupt_set(&mut self) -> bool {
+        dispatch_input!(self, pin => pin.is_interrupt_set())
+    }
+
+    fn is_non_maskable_interrupt_set(&mut self) -> bool {
+        dispatch_input!(self, pin => pin.is_non_maskable_interrupt_set())
+    }
+
+    fn enable_hold(&mut self, on: bool) {
+        dispatch_input!(self, pin => pin.enable_hold(on))
+    }
+}
+
+impl<MODE> InputPin for AnyPin<MODE> {
+    fn set_to_input(&mut self) -> &mut Self {
+        dispatch_input!(self, pin => {
+            pin.set_to_input();
+        });
+        self
+    }
+
+    fn enable_input(&mut self, on: bool) -> &mut Self {
+        dispatch_input!(self, pin => {
+            pin.enable_input(on);
+        });
+        self
+    }
+
+    fn enable_input_in_sleep_mode(&mut self, on: bool) -> &mut Self {
+        dispatch_input!(self, pin => {
+            pin.enable_input_in_sleep_mode(on);
+        });
+        self
+    }
+
+    fn is_input_high(&mut self) -> bool {
+        dispatch_input!(self, pin => pin.is_input_high())
+    }
+
+    fn connect_input_to_peripheral_with_options(
+        &mut self,
+        signal: InputSignal,
+        invert: bool,
+        force_via_gpio_mux: bool,
+    ) -> &mut Self {
+        dispatch_input!(self, pin => {
+            pin.connect_input_to_peripheral_with_options(signal, invert, force_via_gpio_mux);
+        });
+        self
+    }
+}
+
+impl<MODE> Pull for AnyPin<MODE> {
+    fn internal_pull_up(&mut self, on: bool) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.internal_pull_up(on);
+        });
+        self
+    }
+
+    fn internal_pull_down(&mut self, on: bool) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.internal_pull_down(on);
+        });
+        self
+    }
+}
+
+impl<MODE> OutputPin for AnyPin<MODE> {
+    fn set_to_open_drain_output(&mut self) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.set_to_open_drain_output();
+        });
+        self
+    }
+
+    fn set_to_push_pull_output(&mut self) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.set_to_push_pull_output();
+        });
+        self
+    }
+
+    fn enable_output(&mut self, on: bool) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.enable_output(on);
+        });
+        self
+    }
+
+    fn set_output_high(&mut self, on: bool) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.set_output_high(on);
+        });
+        self
+    }
+
+    fn set_drive_strength(&mut self, strength: DriveStrength) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.set_drive_strength(strength);
+        });
+        self
+    }
+
+    fn enable_open_drain(&mut self, on: bool) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.enable_open_drain(on);
+        });
+        self
+    }
+
+    fn enable_output_in_sleep_mode(&mut self, on: bool) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.enable_output_in_sleep_mode(on);
+        });
+        self
+    }
+
+    fn set_drive_strength_in_sleep_mode(&mut self, strength: DriveStrength) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.set_drive_strength_in_sleep_mode(strength);
+        });
+        self
+    }
+
+    fn internal_pull_up_in_sleep_mode(&mut self, on: bool) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.internal_pull_up_in_sleep_mode(on);
+        });
+        self
+    }
+
+    fn internal_pull_down_in_sleep_mode(&mut self, on: bool) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.internal_pull_down_in_sleep_mode(on);
+        });
+        self
+    }
+
+    fn connect_peripheral_to_output_with_options(
+        &mut self,
+        signal: OutputSignal,
+        invert: bool,
+        invert_enable: bool,
+        enable_from_gpio: bool,
+        force_via_gpio_mux: bool,
+    ) -> &mut Self {
+        dispatch_output!(self, pin => {
+            pin.connect_peripheral_to_output_with_options(
+                signal,
+                invert,
+                invert_enable,
+                enable_from_gpio,
+                force_via_gpio_mux,
+            );
+        });
+        self
+    }
+}
@@ -0,0 +1,174 @@
+//! GPIO interrupt dispatching
+//!
+//! Instead of handling all pins in a single `GPIO_INTR` handler, handlers can be registered per
+//! pin via [listen]. The `GPIO_INTR` handler then only needs to call [dispatch], which reads the
+//! interrupt status, calls the registered handlers and clears the interrupts:
+//!
+//! ```no_run
+//! fn button_pressed(pin: u8) {
+//!     ...
+//! }
+//!
+//! gpio::interrupt::listen(&mut button, Event::FallingEdge, Core::PRO, button_pressed);
+//! interrupt::enable_with_priority(Core::PRO, Interrupt::GPIO_INTR, InterruptLevel(1)).unwrap();
+//!
+//! #[interrupt]
+//! fn GPIO_INTR() {
+//!     gpio::interrupt::dispatch();
+//! }
+//! ```
+//!
+//! Handlers are called from the interrupt on the core they were registered for, so the
+//! `GPIO_INTR` interrupt needs to be enabled on that core.
+//!
+//! To work around erratum 3.14 (edge triggered interrupts are lost when multiple pins in a
+//! group are configured for edge triggering), edge events are emulated: the pin is configured
+//! for level triggering on the level opposite to its current state and the level is swapped
+//! every time the interrupt fires. Because the level is swapped before the interrupt is cleared,
+//! the interrupt line is released after every event, giving the same behavior as edge
+//! triggering on the CPU. The handler is only called for the requested edges.
+//!
+//! For level events the handler is called as long as the level is present, so the handler
+//! needs to remove the cause of the interrupt or stop listening.
+//!
+//! *Note: the dispatcher and handler table are stored in RAM because otherwise it may lead
+//! to exceptions when the flash is programmed or erased while the interrupt is called.*
+
+use super::{Event, InputPin};
+use crate::prelude::*;
+use crate::ram;
+use crate::target::GPIO;
+use crate::{get_core, Core};
+
+/// Number of GPIO pins
+const PIN_COUNT: usize = 40;
+
+/// Interrupt handler, called with the number of the pin that caused the interrupt
+pub type Handler = fn(pin: u8);
+
+#[derive(Copy, Clone)]
+struct Registration {
+    handler: Handler,
+    event: Event,
+    core: Core,
+}
+
+#[ram]
+static HANDLERS: CriticalSectionSpinLockMutex<[Option<Registration>; PIN_COUNT]> =
+    CriticalSectionSpinLockMutex::new([None; PIN_COUNT]);
+
+/// Register a handler for a pin and start listening to the event on the given core
+///
+/// Replaces any previously registered handler of the pin.
+pub fn listen<PIN: InputPin>(pin: &mut PIN, event: Event, core: Core, handler: Handler) {
+    let number = pin.pin_number() as usize;
+
+    (&HANDLERS).lock(|handlers| {
+        handlers[number] = Some(Registration {
+            handler,
+            event,
+            core,
+        })
+    });
+
+    let trigger = match event {
+        Event::RisingEdge | Event::FallingEdge | Event::AnyEdge => {
+            opposite_level(pin.is_input_high())
+        }
+        Event::LowLevel | Event::HighLevel => event,
+    };
+
+    pin.unlisten();
+    pin.clear_interrupt();
+    pin.listen_with_options(
+        trigger,
+        core == Core::PRO,
+        core == Core::APP,
+        false,
+        false,
+        false,
+    );
+}
+
+/// Stop listening and remove the registered handler of a pin
+pub fn unlisten<PIN: InputPin>(pin: &mut PIN) {
+    pin.unlisten();
+    pin.clear_interrupt();
+
+    let number = pin.pin_number() as usize;
+    (&HANDLERS).lock(|handlers| handlers[number] = None);
+}
+
+/// Call the handlers of all pending pin interrupts of the current core and clear them
+///
+/// This function needs to be called from the `GPIO_INTR` interrupt handler.
+#[ram]
+pub fn dispatch() {
+    let gpio = unsafe { &*GPIO::ptr() };
+    let core = get_core();
+
+    let mut pending = gpio.status.read().bits() as u64 | (gpio.status1.read().bits() as u64) << 32;
+
+    while pending != 0 {
+        let number = pending.trailing_zeros() as u8;
+        pending &= !(1 << number);
+
+        let registration = match (&HANDLERS).lock(|handlers| handlers[number as usize]) {
+            Some(registration) if registration.core == core => registration,
+            _ => continue,
+        };
+
+        let call = match registration.event {
+            Event::RisingEdge | Event::FallingEdge | Event::AnyEdge => {
+                // swap the level before clearing, otherwise the interrupt fires again
+                let high = is_input_high(number);
+                gpio.pin[number as usize]
+                    .modify(|_, w| unsafe { w.int_type().bits(opposite_level(high) as u8) });
+
+                match registration.event {
+                    Event::RisingEdge => high,
+                    Event::FallingEdge => !high,
+                    _ => true,
+                }
+            }
+            Event::LowLevel | Event::HighLevel => true,
+        };
+
+        clear_interrupt(number);
+
+        if call {
+            (registration.handler)(number);
+        }
+    }
+}
+
+/// The level trigger which fires on the next change of the input
+#[ram]
+fn opposite_level(high: bool) -> Event {
+    if high {
+        Event::LowLevel
+    } else {
+        Event::HighLevel
+    }
+}
+
+#[ram]
+fn is_input_high(number: u8) -> bool {
+    let gpio = unsafe { &*GPIO::ptr() };
+    if number < 32 {
+        gpio.in_.read().in_data().bits() & (1 << number) != 0
+    } else {
+        gpio.in1.read().in1_data().bits() & (1 << (number - 32)) != 0
+    }
+}
+
+#[ram]
+fn clear_interrupt(number: u8) {
+    let gpio = unsafe { &*GPIO::ptr() };
+    if number < 32 {
+        gpio.status_w1tc.write(|w| unsafe { w.bits(1 << number) });
+    } else {
+        gpio.status1_w1tc
+            .write(|w| unsafe { w.bits(1 << (number - 32)) });
+    }
+}
@@ -9,6 +9,14 @@
 //!
 //! The advantage of using the dedicated traits in peripherals is that the configuration of the
 //! IO can be done inside the peripheral instead of having to be done upfront.
+//!
+//! Pins can be converted into an [AnyPin] to store pins of different types in an array or to
+//! select pins at runtime. Multiple of these pins can be grouped in an [OutputPort] or
+//! [InputPort] to write or read them at once.
+//!
+//! Signals can be routed at runtime (IO_MUX or GPIO matrix, loopback) via [route]. Pad hold
+//! and isolation for deep sleep is provided by [sleep]. The latched strapping pins and the GPIOs
+//! reserved for flash and external RAM are available via [strapping].
 
 use {
     crate::target::{GPIO, IO_MUX, RTCIO},
@@ -17,9 +25,17 @@ use {
     embedded_hal::digital::v2::{OutputPin as _, StatefulOutputPin as _},
 };
 
+mod any;
+pub mod interrupt;
 mod mux;
+mod port;
+pub mod route;
+pub mod sleep;
+pub mod strapping;
 pub use crate::prelude::*;
+pub use any::*;
 pub use mux::*;
+pub use port::*;
 
 /// Extension trait to split a GPIO peripheral into independent pins and registers
 pub trait GpioExt {
@@ -32,6 +48,9 @@ pub trait GpioExt {
 
 /// Functions available on all pins
 pub trait Pin {
+    /// GPIO number of the pin
+    fn pin_number(&self) -> u8;
+
     /// Enable/Disable the sleep mode of the pad
     fn sleep_mode(&mut self, on: bool) -> &mut Self;
 
@@ -257,7 +276,8 @@ pub trait RTCOutputPin {
 /// *Note: ESP32 has a bug (3.14), which prevents correct triggering of interrupts when
 /// multiple GPIO's are configured for edge triggering in a group (GPIO0-31 is one group,
 /// GPIO32-39 is the other group). This can be worked around by using level triggering on the
-/// GPIO with edge triggering on the CPU.*
+/// GPIO with edge triggering on the CPU. The dispatcher in [interrupt] implements this
+/// workaround.*
 //
 // Value must correspond to values in the register
 #[derive(Copy, Clone)]
@@ -430,6 +450,7 @@ macro_rules! impl_output {
                 let gpio = unsafe { &*GPIO::ptr() };
                 let iomux = unsafe { &*IO_MUX::ptr() };
 
+                strapping::check_output_allowed($pin_num);
                 self.disable_analog();
 
                 // NOTE(unsafe) atomic read to a stateless register
@@ -690,7 +711,8 @@ macro_rules! impl_input {
                 force_via_gpio_mux: bool,
             ) -> &mut Self {
 
-                let af = if force_via_gpio_mux
+                // inversion is only possible in the gpio matrix
+                let af = if force_via_gpio_mux || invert
                 {
                     AlternateFunction::Function3
                 }
@@ -710,9 +732,10 @@ macro_rules! impl_input {
                 self.set_alternate_function(af);
 
                 if (signal as usize) < 256 {
+                    // bypass the gpio matrix when the signal is routed directly via the io mux
                     unsafe { &*GPIO::ptr() }.func_in_sel_cfg[signal as usize].modify(|_, w| unsafe {
                         w.sel()
-                            .set_bit()
+                            .bit(af == AlternateFunction::Function3)
                             .in_inv_sel()
                             .bit(invert)
                             .in_sel()
@@ -724,6 +747,10 @@ macro_rules! impl_input {
         }
 
         impl<MODE> Pin for $pxi<MODE> {
+            fn pin_number(&self) -> u8 {
+                $pin_num
+            }
+
             fn sleep_mode(&mut self, on: bool) -> &mut Self {
                 unsafe { &*IO_MUX::ptr() }
                     .$iomux
@@ -838,7 +865,7 @@ macro_rules! impl_output_wrap {
     };
 }
 
-static RTCIO_LOCK: CriticalSectionSpinLockMutex<()> = CriticalSectionSpinLockMutex::new(());
+pub(crate) static RTCIO_LOCK: CriticalSectionSpinLockMutex<()> = CriticalSectionSpinLockMutex::new(());
 
 macro_rules! impl_no_rtc {
     ($pxi:ident, $pin_num:expr, $bank:ident, $iomux:ident, IO, RTC) => {
@@ -881,7 +908,7 @@ macro_rules! impl_no_rtc {
                 (&RTCIO_LOCK).lock(|_| unsafe {
                     // shared register without set/clear functionality, so needs lock
                     (&*RTCIO::ptr()).dig_pad_hold.modify(|r, w| {
-                        w.bits(r.bits() & !((on as u32) << $hold_bit) | ((on as u32) << $hold_bit))
+                        w.bits((r.bits() & !(1 << $hold_bit)) | ((on as u32) << $hold_bit))
                     })
                 });
             }
@@ -928,12 +955,49 @@ macro_rules! gpio {
                 _mode: PhantomData<MODE>,
             }
 
+            impl<MODE> $pxi<MODE> {
+                /// Convert into a pin with the pin number determined at runtime
+                pub fn degrade(self) -> AnyPin<MODE> {
+                    AnyPin::new($pin_num)
+                }
+            }
+
             impl_pin_wrap!($pxi, $pin_num, $bank, $iomux, $type
                 $( ,( $( $af_input_signal: $af_input ),* ) )? );
             impl_output_wrap!($pxi, $pin_num, $bank, $iomux, $type
                 $($( ,( $( $af_output_signal: $af_output ),* ) )? )? );
             impl_no_rtc!($pxi, $pin_num, $bank, $iomux, $type, $rtc);
         )+
+
+        /// IO_MUX function which connects the pin directly to the peripheral input signal
+        fn direct_input_function(pin: u8, signal: InputSignal) -> Option<AlternateFunction> {
+            match pin {
+                $(
+                    $pin_num => match signal {
+                        $( $(
+                            InputSignal::$af_input_signal => Some(AlternateFunction::$af_input),
+                        )* )?
+                        _ => None,
+                    },
+                )+
+                _ => None,
+            }
+        }
+
+        /// IO_MUX function which connects the peripheral output signal directly to the pin
+        fn direct_output_function(pin: u8, signal: OutputSignal) -> Option<AlternateFunction> {
+            match pin {
+                $(
+                    $pin_num => match signal {
+                        $( $( $(
+                            OutputSignal::$af_output_signal => Some(AlternateFunction::$af_output),
+                        )* )? )?
+                        _ => None,
+                    },
+                )+
+                _ => None,
+            }
+        }
     };
 }
 
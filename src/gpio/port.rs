@@ -0,0 +1,226 @@
+//! Multi-pin ports
+//!
+//! [OutputPort] and [InputPort] group up to 32 owned pins, so they can be written or read with a
+//! single register access. Bit `i` of the port value corresponds to the `i`th pin passed to the
+//! constructor.
+//!
+//! The mapping between port bits and GPIO register bits is calculated when the port is created,
+//! as runs of pins which are consecutive GPIOs in ascending order. Each run is converted with a
+//! single shift and mask, so the fast path is a port of consecutive GPIOs (one run). Pins in
+//! arbitrary order need one step per run, up to one per pin.
+//!
+//! Each operation does one store to the set (`out_w1ts`) and/or clear (`out_w1tc`) register of
+//! each bank containing affected pins, so pins in the same bank change state at exactly the same
+//! time. With [OutputPort::write] the pins going high change state one register access before
+//! the pins going low.
+//!
+//! ```no_run
+//! let mut bus = OutputPort::new([
+//!     pins.gpio12.into_push_pull_output().degrade(),
+//!     pins.gpio13.into_push_pull_output().degrade(),
+//!     pins.gpio14.into_push_pull_output().degrade(),
+//!     pins.gpio15.into_push_pull_output().degrade(),
+//! ]);
+//!
+//! bus.write(0b1010);
+//! ```
+
+use super::*;
+
+/// Maximum number of pins in a port
+pub const MAX_PORT_PINS: usize = 32;
+
+/// Port bits which map to consecutive GPIO register bits
+#[derive(Copy, Clone, Default)]
+struct Run {
+    /// Port bits of the run
+    port_mask: u32,
+    /// GPIO register bits of the run
+    gpio_mask: u64,
+    /// GPIO register bit minus port bit
+    shift: i32,
+}
+
+/// Mapping between port bits and GPIO register bits (GPIO0-39 in a 64 bit value)
+///
+/// The pins are split into runs of consecutive GPIOs in ascending order, each of which is
+/// converted with a single shift.
+struct Mapping {
+    runs: [Run; MAX_PORT_PINS],
+    len: usize,
+    /// All pins in the port
+    all: u64,
+}
+
+impl Mapping {
+    fn new<MODE>(pins: &[AnyPin<MODE>]) -> Self {
+        if pins.len() > MAX_PORT_PINS {
+            panic!("A port can hold at most {} pins", MAX_PORT_PINS);
+        }
+
+        let mut runs = [Run::default(); MAX_PORT_PINS];
+        let mut len = 0;
+        let mut all = 0;
+        for (index, pin) in pins.iter().enumerate() {
+            let number = pin.pin_number();
+            let shift = number as i32 - index as i32;
+
+            if len == 0 || runs[len - 1].shift != shift {
+                runs[len].shift = shift;
+                len += 1;
+            }
+            runs[len - 1].port_mask |= 1 << index;
+            runs[len - 1].gpio_mask |= 1 << number;
+            all |= 1 << number;
+        }
+
+        Mapping { runs, len, all }
+    }
+
+    /// Convert a port value to GPIO register bits
+    #[inline(always)]
+    fn to_gpio(&self, value: u32) -> u64 {
+        self.runs[..self.len].iter().fold(0, |gpio, run| {
+            let bits = (value & run.port_mask) as u64;
+            gpio | if run.shift >= 0 {
+                bits << run.shift
+            } else {
+                bits >> -run.shift
+            }
+        })
+    }
+
+    /// Convert GPIO register bits to a port value
+    #[inline(always)]
+    fn from_gpio(&self, gpio: u64) -> u32 {
+        self.runs[..self.len].iter().fold(0, |value, run| {
+            let bits = gpio & run.gpio_mask;
+            let bits = if run.shift >= 0 {
+                bits >> run.shift
+            } else {
+                bits << -run.shift
+            };
+            value | bits as u32
+        })
+    }
+}
+
+/// Set the GPIO outputs in `gpio` high (register bits GPIO0-39)
+#[inline(always)]
+fn set_gpio(gpio: u64) {
+    // NOTE(unsafe) atomic write to a stateless register
+    let registers = unsafe { &*GPIO::ptr() };
+    if gpio as u32 != 0 {
+        registers.out_w1ts.write(|w| unsafe { w.bits(gpio as u32) });
+    }
+    if (gpio >> 32) as u32 != 0 {
+        registers
+            .out1_w1ts
+            .write(|w| unsafe { w.bits((gpio >> 32) as u32) });
+    }
+}
+
+/// Set the GPIO outputs in `gpio` low (register bits GPIO0-39)
+#[inline(always)]
+fn clear_gpio(gpio: u64) {
+    // NOTE(unsafe) atomic write to a stateless register
+    let registers = unsafe { &*GPIO::ptr() };
+    if gpio as u32 != 0 {
+        registers.out_w1tc.write(|w| unsafe { w.bits(gpio as u32) });
+    }
+    if (gpio >> 32) as u32 != 0 {
+        registers
+            .out1_w1tc
+            .write(|w| unsafe { w.bits((gpio >> 32) as u32) });
+    }
+}
+
+/// Group of output pins which are written at once
+pub struct OutputPort<PINS> {
+    pins: PINS,
+    mapping: Mapping,
+}
+
+impl<PINS> OutputPort<PINS> {
+    /// Create a port from an array or slice of (at most 32) output pins
+    pub fn new<MODE>(pins: PINS) -> Self
+    where
+        PINS: AsRef<[AnyPin<Output<MODE>>]>,
+    {
+        let mapping = Mapping::new(pins.as_ref());
+        OutputPort { pins, mapping }
+    }
+
+    /// Set all pins of the port according to the bits of `value`
+    pub fn write(&mut self, value: u32) {
+        let high = self.mapping.to_gpio(value);
+        set_gpio(high);
+        clear_gpio(self.mapping.all & !high);
+    }
+
+    /// Set the pins selected by `mask` according to the bits of `value`
+    pub fn write_masked(&mut self, value: u32, mask: u32) {
+        let high = self.mapping.to_gpio(value & mask);
+        set_gpio(high);
+        clear_gpio(self.mapping.to_gpio(!value & mask));
+    }
+
+    /// Set the pins selected by `mask` high
+    pub fn set_high(&mut self, mask: u32) {
+        set_gpio(self.mapping.to_gpio(mask));
+    }
+
+    /// Set the pins selected by `mask` low
+    pub fn set_low(&mut self, mask: u32) {
+        clear_gpio(self.mapping.to_gpio(mask));
+    }
+
+    /// Read back the current output state of the pins
+    pub fn read_output(&self) -> u32 {
+        let registers = unsafe { &*GPIO::ptr() };
+        let gpio = registers.out.read().bits() as u64 | (registers.out1.read().bits() as u64) << 32;
+        self.mapping.from_gpio(gpio)
+    }
+
+    /// Release the pins
+    pub fn release(self) -> PINS {
+        self.pins
+    }
+}
+
+/// Group of input pins which are read at once
+pub struct InputPort<PINS> {
+    pins: PINS,
+    mapping: Mapping,
+}
+
+impl<PINS> InputPort<PINS> {
+    /// Create a port from an array or slice of (at most 32) input pins
+    pub fn new<MODE>(pins: PINS) -> Self
+    where
+        PINS: AsRef<[AnyPin<Input<MODE>>]>,
+    {
+        let mapping = Mapping::new(pins.as_ref());
+        InputPort { pins, mapping }
+    }
+
+    /// Read the state of all pins
+    ///
+    /// If all pins are in the same bank, the inputs are read in a single register access.
+    pub fn read(&self) -> u32 {
+        let registers = unsafe { &*GPIO::ptr() };
+        let mut gpio = 0;
+        if self.mapping.all as u32 != 0 {
+            gpio |= registers.in_.read().in_data().bits() as u64;
+        }
+        if (self.mapping.all >> 32) as u32 != 0 {
+            gpio |= (registers.in1.read().in1_data().bits() as u64) << 32;
+        }
+        self.mapping.from_gpio(gpio)
+    }
+
+    /// Release the pins
+    pub fn release(self) -> PINS {
+        self.pins
+    }
+}
@@ -0,0 +1,137 @@
+//! Runtime routing of peripheral signals
+//!
+//! Peripheral signals can reach a pin in two ways:
+//! - Directly via the IO_MUX, which is only possible for some signal/pin combinations, but has
+//!   the lowest delay (e.g. needed for SPI at 80MHz)
+//! - Via the GPIO matrix, which can connect almost any signal to any pin
+//!
+//! The connect functions of [InputPin] and [OutputPin] use the IO_MUX when possible, except for
+//! inverted signals, as inversion is only possible in the GPIO matrix. The functions in this
+//! module allow to select the [Routing] explicitly, to disconnect signals again and to loop a
+//! peripheral output back into a peripheral input for self tests:
+//!
+//! ```no_run
+//! // UART1 TX -> RX loopback
+//! gpio::route::loopback(
+//!     &mut pins.gpio18,
+//!     OutputSignal::U1TXD,
+//!     InputSignal::U1RXD,
+//! )
+//! .unwrap();
+//! ```
+//!
+//! *Note: the GPIO matrix can only feed peripheral inputs from pins, so a loopback always
+//! uses a pin with both output and input enabled. The pin does not need to be connected
+//! externally, but it is driven by the peripheral.*
+
+use super::*;
+
+/// Route errors
+#[derive(Debug)]
+pub enum Error {
+    /// Signal can not be routed directly via the IO_MUX to/from this pin
+    NoDirectRoute,
+    /// Signal is only available via the IO_MUX
+    NoMatrixRoute,
+}
+
+/// Routing of a signal between pin and peripheral
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Routing {
+    /// Directly via the IO_MUX when possible, otherwise via the GPIO matrix
+    Auto,
+    /// Directly via the IO_MUX
+    IoMux,
+    /// Via the GPIO matrix
+    Matrix,
+}
+
+/// Connect a pin to a peripheral input signal with the given routing
+pub fn connect_input<PIN: InputPin>(
+    pin: &mut PIN,
+    signal: InputSignal,
+    routing: Routing,
+) -> Result<(), Error> {
+    let direct = direct_input_function(pin.pin_number(), signal).is_some();
+    let via_matrix = check_routing(routing, direct, signal as usize)?;
+
+    pin.connect_input_to_peripheral_with_options(signal, false, via_matrix);
+    Ok(())
+}
+
+/// Connect a peripheral output signal to a pin with the given routing
+pub fn connect_output<PIN: OutputPin>(
+    pin: &mut PIN,
+    signal: OutputSignal,
+    routing: Routing,
+) -> Result<(), Error> {
+    let direct = direct_output_function(pin.pin_number(), signal).is_some();
+    let via_matrix = check_routing(routing, direct, signal as usize)?;
+
+    pin.connect_peripheral_to_output_with_options(signal, false, false, false, via_matrix);
+    Ok(())
+}
+
+/// Disconnect a peripheral input signal from any pin
+///
+/// The peripheral receives a constant level instead, which should be the idle level of the
+/// signal (e.g. high for UART RX).
+pub fn disconnect_input(signal: InputSignal, idle_high: bool) {
+    if signal as usize >= 256 {
+        return;
+    }
+
+    if idle_high {
+        connect_high_to_peripheral(signal);
+    } else {
+        connect_low_to_peripheral(signal);
+    }
+}
+
+/// Disconnect the pin from any peripheral output signal
+///
+/// The pin is switched back to GPIO function, driven by the GPIO output register.
+pub fn disconnect_output<PIN: OutputPin>(pin: &mut PIN) {
+    pin.set_alternate_function(AlternateFunction::Function3);
+
+    unsafe { &*GPIO::ptr() }.func_out_sel_cfg[pin.pin_number() as usize].modify(|_, w| unsafe {
+        w.out_sel()
+            .bits(OutputSignal::GPIO as u16)
+            .out_inv_sel()
+            .clear_bit()
+            .oen_sel()
+            .clear_bit()
+            .oen_inv_sel()
+            .clear_bit()
+    });
+}
+
+/// Loop a peripheral output signal back into a peripheral input signal via the GPIO matrix
+///
+/// The pin is configured as push/pull output with the input enabled.
+pub fn loopback<PIN: InputPin + OutputPin>(
+    pin: &mut PIN,
+    output: OutputSignal,
+    input: InputSignal,
+) -> Result<(), Error> {
+    if output as usize >= 256 || input as usize >= 256 {
+        return Err(Error::NoMatrixRoute);
+    }
+
+    pin.set_to_push_pull_output()
+        .connect_peripheral_to_output_with_options(output, false, false, false, true)
+        .enable_input(true)
+        .connect_input_to_peripheral_with_options(input, false, true);
+    Ok(())
+}
+
+/// Check the routing and return whether the GPIO matrix needs to be used
+fn check_routing(routing: Routing, direct: bool, signal: usize) -> Result<bool, Error> {
+    match routing {
+        Routing::Auto if direct => Ok(false),
+        Routing::IoMux if direct => Ok(false),
+        Routing::IoMux => Err(Error::NoDirectRoute),
+        Routing::Auto | Routing::Matrix if signal >= 256 => Err(Error::NoMatrixRoute),
+        Routing::Auto | Routing::Matrix => Ok(true),
+    }
+}
@@ -0,0 +1,154 @@
+//! Pad hold and isolation for deep sleep
+//!
+//! During deep sleep the digital domain is powered down, so the pads lose their configuration
+//! unless they are held. A held pad keeps its current state (output level, pull up/down, etc.)
+//! until the hold is released, even through deep sleep and the reset on wake-up.
+//!
+//! - [hold] latches the selected pads immediately: RTC capable pads via the hold bit of the RTC
+//!   pad, other pads via `dig_pad_hold`.
+//! - [enable_deep_sleep_hold] latches all digital pads automatically when entering deep sleep.
+//! - [isolate_unused] disconnects all pads which are not used during deep sleep (input, output
+//!   and pull up/down disabled) and holds them, which avoids leakage through floating inputs
+//!   and external pull up/down resistors.
+//! - [release_all] releases all holds and should be called after wake-up once the pins are
+//!   configured again.
+//!
+//! Pads are selected via a bit mask, bit `n` corresponds to GPIOn:
+//!
+//! ```no_run
+//! let mut power_enable = pins.gpio25.into_push_pull_output();
+//! power_enable.set_high().unwrap();
+//!
+//! let used = 1 << power_enable.pin_number();
+//! gpio::sleep::hold(used);
+//! gpio::sleep::isolate_unused(used);
+//! gpio::sleep::enable_deep_sleep_hold(true);
+//!
+//! // enter deep sleep
+//!
+//! // after wake-up and reconfiguration of the pins
+//! gpio::sleep::release_all();
+//! ```
+//!
+//! *Note: the pins used by the flash and external RAM ([strapping::reserved_pins]) are never
+//! isolated.*
+
+use super::*;
+use crate::target::RTCCNTL;
+
+/// All existing GPIOs
+pub const ALL_PINS: u64 = 0xff_0eff_ffff;
+
+/// GPIOs with RTC functionality
+pub const RTC_PINS: u64 = 0xff_0e00_f015;
+
+/// Hold the current state of the pads in `pins`
+pub fn hold(pins: u64) {
+    for_each_pin(pins, |pin| pin.enable_hold(true));
+}
+
+/// Release the hold of the pads in `pins`
+pub fn unhold(pins: u64) {
+    for_each_pin(pins, |pin| pin.enable_hold(false));
+}
+
+/// Enable/Disable automatic hold of all digital pads when entering deep sleep
+///
+/// The hold is kept after wake-up until [release_all] is called.
+pub fn enable_deep_sleep_hold(on: bool) {
+    // shared register without set/clear functionality, so needs lock
+    (&RTCIO_LOCK).lock(|_| {
+        unsafe { &*RTCCNTL::ptr() }.dig_iso.modify(|_, w| {
+            w.dg_pad_force_unhold()
+                .clear_bit()
+                .dg_pad_force_hold()
+                .clear_bit()
+                .dg_pad_autohold_en()
+                .bit(on)
+        })
+    });
+}
+
+/// Disconnect the pads in `pins` and hold them in the disconnected state
+///
+/// Input, output and pull up/down are disabled and RTC capable pads are switched to the RTC
+/// mux. The pins used by the flash and external RAM are skipped.
+pub fn isolate(pins: u64) {
+    let pins = pins & !strapping::reserved_pins();
+
+    for_each_pin(pins & !RTC_PINS, |pin| {
+        pin.enable_input(false)
+            .enable_output(false)
+            .internal_pull_up(false)
+            .internal_pull_down(false)
+            .enable_hold(true);
+    });
+
+    let mut rtc_pins = pins & RTC_PINS;
+    while rtc_pins != 0 {
+        let number = rtc_pins.trailing_zeros() as u8;
+        rtc_pins &= !(1 << number);
+        isolate_rtc_pad(number);
+    }
+}
+
+/// Isolate all pads which are not in `used`
+pub fn isolate_unused(used: u64) {
+    isolate(ALL_PINS & !used);
+}
+
+/// Release the hold of all pads, including the automatic deep sleep hold
+pub fn release_all() {
+    (&RTCIO_LOCK).lock(|_| {
+        let rtccntl = unsafe { &*RTCCNTL::ptr() };
+        rtccntl
+            .dig_iso
+            .modify(|_, w| w.dg_pad_autohold_en().clear_bit());
+        rtccntl
+            .dig_iso
+            .modify(|_, w| w.clr_dg_pad_autohold().set_bit());
+
+        unsafe { &*RTCIO::ptr() }
+            .dig_pad_hold
+            .write(|w| unsafe { w.bits(0) });
+    });
+
+    for_each_pin(RTC_PINS, |pin| pin.enable_hold(false));
+}
+
+/// Call `f` for each existing pin in `pins`
+fn for_each_pin(mut pins: u64, mut f: impl FnMut(&mut AnyPin<Unknown>)) {
+    if pins & !ALL_PINS != 0 {
+        panic!("GPIO{} does not exist", (pins & !ALL_PINS).trailing_zeros());
+    }
+
+    while pins != 0 {
+        let number = pins.trailing_zeros() as u8;
+        pins &= !(1 << number);
+        f(&mut AnyPin::new(number));
+    }
+}
+
+/// Switch an RTC pad to the RTC mux with everything disabled and hold it
+fn isolate_rtc_pad(number: u8) {
+    macro_rules! rtc_pads {
+        ($($pxi:ident: $num:literal,)+) => {
+            match number {
+                $(
+                    $num => {
+                        let mut pin = $pxi::<Unknown> { _mode: PhantomData };
+                        pin.init_rtc(false, false, false, false, false);
+                        pin.enable_hold_internal(true);
+                    }
+                )+
+                _ => unreachable!(),
+            }
+        };
+    }
+
+    rtc_pads!(
+        Gpio0: 0, Gpio2: 2, Gpio4: 4, Gpio12: 12, Gpio13: 13, Gpio14: 14, Gpio15: 15,
+        Gpio25: 25, Gpio26: 26, Gpio27: 27, Gpio32: 32, Gpio33: 33, Gpio34: 34, Gpio35: 35,
+        Gpio36: 36, Gpio37: 37, Gpio38: 38, Gpio39: 39,
+    );
+}
@@ -0,0 +1,188 @@
+//! Boot strapping pins and reserved GPIOs
+//!
+//! The levels of the strapping pins MTDI (GPIO12), GPIO0, GPIO2, MTDO (GPIO15) and GPIO5 are
+//! latched at reset and select the boot mode, the flash voltage and the SDIO slave timing.
+//! [Strapping::read] returns the latched values:
+//!
+//! ```no_run
+//! let strapping = gpio::strapping::Strapping::read();
+//! writeln!(serial, "Boot mode: {:?}", strapping.boot_mode()).unwrap();
+//! writeln!(serial, "Flash voltage: {:?}", strapping.flash_voltage()).unwrap();
+//! ```
+//!
+//! Some GPIOs are connected to the flash or external RAM (PSRAM) and can not be used by the
+//! application, see [reserved_pins]. Configuring a pin as output panics if it is used by the
+//! flash (e.g. GPIO6-11) or the external RAM, as driving it crashes the chip.
+
+use crate::efuse::{ChipType, Efuse};
+use crate::target::{EFUSE, GPIO};
+
+/// Boot mode selected by GPIO0 and GPIO2
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum BootMode {
+    /// Boot from SPI flash (GPIO0 high)
+    SpiFlash,
+    /// Serial download (GPIO0 and GPIO2 low)
+    Download,
+    /// Invalid combination (GPIO0 low and GPIO2 high)
+    Invalid,
+}
+
+/// Flash (VDD_SDIO) voltage
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum FlashVoltage {
+    V3_3,
+    V1_8,
+}
+
+/// Sampling and output edges of the SDIO slave selected by MTDO and GPIO5
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SdioTiming {
+    FallingSampleFallingOutput,
+    FallingSampleRisingOutput,
+    RisingSampleFallingOutput,
+    RisingSampleRisingOutput,
+}
+
+/// Bits of the strapping pins in the GPIO_STRAP register
+const STRAP_GPIO5: u16 = 1 << 0;
+const STRAP_MTDO: u16 = 1 << 1;
+const STRAP_GPIO2: u16 = 1 << 3;
+const STRAP_GPIO0: u16 = 1 << 4;
+const STRAP_MTDI: u16 = 1 << 5;
+
+/// GPIOs connected to the flash
+const FLASH_PINS: u64 = 0x0fc0;
+
+/// GPIOs connected to the internal flash of the ESP32-PICO
+const PICO_FLASH_PINS: u64 = 1 << 6 | 1 << 7 | 1 << 8 | 1 << 11 | 1 << 16 | 1 << 17;
+
+/// GPIOs connected to the external RAM
+#[cfg(feature = "external_ram")]
+const EXTERNAL_RAM_PINS: u64 = 1 << 16 | 1 << 17;
+
+/// Latched values of the strapping pins
+#[derive(Copy, Clone, Debug)]
+pub struct Strapping {
+    bits: u16,
+}
+
+impl Strapping {
+    /// Read the values latched at reset
+    pub fn read() -> Self {
+        let gpio = unsafe { &*GPIO::ptr() };
+        Strapping {
+            bits: gpio.strap.read().strapping().bits(),
+        }
+    }
+
+    /// Raw value of the GPIO_STRAP register
+    pub fn bits(&self) -> u16 {
+        self.bits
+    }
+
+    /// Level of MTDI (GPIO12)
+    pub fn mtdi(&self) -> bool {
+        self.bits & STRAP_MTDI != 0
+    }
+
+    /// Level of GPIO0
+    pub fn gpio0(&self) -> bool {
+        self.bits & STRAP_GPIO0 != 0
+    }
+
+    /// Level of GPIO2
+    pub fn gpio2(&self) -> bool {
+        self.bits & STRAP_GPIO2 != 0
+    }
+
+    /// Level of MTDO (GPIO15)
+    pub fn mtdo(&self) -> bool {
+        self.bits & STRAP_MTDO != 0
+    }
+
+    /// Level of GPIO5
+    pub fn gpio5(&self) -> bool {
+        self.bits & STRAP_GPIO5 != 0
+    }
+
+    /// Boot mode selected at reset
+    pub fn boot_mode(&self) -> BootMode {
+        match (self.gpio0(), self.gpio2()) {
+            (true, _) => BootMode::SpiFlash,
+            (false, false) => BootMode::Download,
+            (false, true) => BootMode::Invalid,
+        }
+    }
+
+    /// Flash voltage
+    ///
+    /// Selected by MTDI, unless it is overridden by the eFuse.
+    pub fn flash_voltage(&self) -> FlashVoltage {
+        let efuse = unsafe { &*EFUSE::ptr() }.blk0_rdata4.read();
+
+        let tieh = if efuse.rd_sdio_force().bit() {
+            efuse.rd_sdio_tieh().bit()
+        } else {
+            !self.mtdi()
+        };
+
+        if tieh {
+            FlashVoltage::V3_3
+        } else {
+            FlashVoltage::V1_8
+        }
+    }
+
+    /// Returns true if the boot messages are printed on U0TXD (MTDO high)
+    pub fn boot_messages_enabled(&self) -> bool {
+        self.mtdo()
+    }
+
+    /// SDIO slave timing
+    pub fn sdio_timing(&self) -> SdioTiming {
+        match (self.mtdo(), self.gpio5()) {
+            (false, false) => SdioTiming::FallingSampleFallingOutput,
+            (false, true) => SdioTiming::FallingSampleRisingOutput,
+            (true, false) => SdioTiming::RisingSampleFallingOutput,
+            (true, true) => SdioTiming::RisingSampleRisingOutput,
+        }
+    }
+}
+
+/// GPIOs used by the flash and external RAM (bit `n` corresponds to GPIOn)
+///
+/// The external RAM pins are only included when the external RAM is in use.
+pub fn reserved_pins() -> u64 {
+    let flash = match Efuse::get_chip_type() {
+        ChipType::ESP32_PICOD2 | ChipType::ESP32_PICOD4 => PICO_FLASH_PINS,
+        _ => FLASH_PINS,
+    };
+
+    flash | external_ram_pins()
+}
+
+/// Returns true if the GPIO is used by the flash or external RAM
+pub fn is_reserved(pin: u8) -> bool {
+    reserved_pins() & (1 << pin) != 0
+}
+
+/// GPIOs used by the external RAM, if it is in use
+fn external_ram_pins() -> u64 {
+    #[cfg(feature = "external_ram")]
+    {
+        if crate::external_ram::get_size() != 0 {
+            return EXTERNAL_RAM_PINS;
+        }
+    }
+    0
+}
+
+/// Panic if the GPIO is used by the flash or external RAM
+///
+/// Driving these pins crashes the chip, so configuring them as output is refused.
+pub(super) fn check_output_allowed(pin: u8) {
+    if is_reserved(pin) {
+        panic!("GPIO{} is used by the flash or external RAM", pin);
+    }
+}
@@ -0,0 +1,224 @@
+//! DMA linked list descriptors used by the I2S peripherals
+//!
+//! The I2S DMA engine walks a linked list of descriptors, each pointing to a buffer of at most
+//! 4095 bytes. The buffers and descriptors need to be located in internal RAM and need to live
+//! as long as the transfer is running, therefore the drivers only accept `'static` references.
+
+use super::Error;
+
+/// Maximum number of bytes handled by one descriptor (rounded down to a word boundary)
+pub const MAX_BYTES_PER_DESCRIPTOR: usize = 4092;
+
+/// Start of the DMA capable internal data RAM
+const DMA_RAM_START: usize = 0x3FFA_E000;
+/// End of the DMA capable internal data RAM
+const DMA_RAM_END: usize = 0x4000_0000;
+
+const SIZE_SHIFT: u32 = 0;
+const LENGTH_SHIFT: u32 = 12;
+const LENGTH_SIZE_MASK: u32 = 0xfff;
+const EOF_BIT: u32 = 1 << 30;
+const OWNER_DMA_BIT: u32 = 1 << 31;
+
+/// DMA linked list descriptor (`lldesc_t`)
+#[repr(C, align(4))]
+pub struct Descriptor {
+    config: u32,
+    buffer: *const u8,
+    next: *const Descriptor,
+}
+
+impl Descriptor {
+    /// Create a new empty descriptor
+    ///
+    /// This is a const function so descriptors can be placed in statics.
+    pub const fn new() -> Self {
+        Descriptor {
+            config: 0,
+            buffer: core::ptr::null(),
+            next: core::ptr::null(),
+        }
+    }
+
+    /// Number of valid bytes in the buffer of this descriptor
+    pub fn length(&self) -> usize {
+        ((unsafe { core::ptr::read_volatile(&self.config) } >> LENGTH_SHIFT) & LENGTH_SIZE_MASK)
+            as usize
+    }
+
+    /// Returns true if the descriptor is (still) owned by the DMA engine
+    pub fn is_owned_by_dma(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&self.config) & OWNER_DMA_BIT != 0 }
+    }
+
+    /// Returns true if this is the last descriptor of a transfer
+    pub fn is_eof(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&self.config) & EOF_BIT != 0 }
+    }
+
+    fn set(&mut self, buffer: *const u8, size: usize, length: usize, eof: bool) {
+        let mut config = ((size as u32 & LENGTH_SIZE_MASK) << SIZE_SHIFT)
+            | ((length as u32 & LENGTH_SIZE_MASK) << LENGTH_SHIFT)
+            | OWNER_DMA_BIT;
+        if eof {
+            config |= EOF_BIT;
+        }
+        self.buffer = buffer;
+        unsafe { core::ptr::write_volatile(&mut self.config, config) };
+    }
+}
+
+impl Default for Descriptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Number of descriptors needed to cover a buffer of `len` bytes
+pub const fn descriptors_needed(len: usize) -> usize {
+    (len + MAX_BYTES_PER_DESCRIPTOR - 1) / MAX_BYTES_PER_DESCRIPTOR
+}
+
+/// Check if a buffer is located in DMA capable memory and is word aligned
+pub(crate) fn check_buffer(buffer: *const u8, len: usize) -> Result<(), Error> {
+    let start = buffer as usize;
+    if start % 4 != 0 || len % 4 != 0 {
+        return Err(Error::BufferNotAligned);
+    }
+    if start < DMA_RAM_START || start + len > DMA_RAM_END {
+        return Err(Error::BufferNotDmaCapable);
+    }
+    Ok(())
+}
+
+/// Link the descriptors to cover `len` bytes of `buffer`
+///
+/// For transmit chains `tx` is set and the length of each descriptor is set to the number of
+/// bytes to send, for receive chains the length is filled in by the DMA engine.
+/// If `circular` is set the last descriptor points back to the first, otherwise the last
+/// descriptor gets the eof flag set.
+///
+/// Returns the address of the first descriptor.
+pub(crate) fn link(
+    descriptors: &mut [Descriptor],
+    buffer: *const u8,
+    len: usize,
+    tx: bool,
+    circular: bool,
+) -> Result<u32, Error> {
+    link_chunked(
+        descriptors,
+        buffer,
+        len,
+        MAX_BYTES_PER_DESCRIPTOR,
+        tx,
+        circular,
+    )
+}
+
+/// Link the descriptors to cover `len` bytes of `buffer` in chunks of at most `chunk` bytes
+///
+/// See [link] for the details.
+pub(crate) fn link_chunked(
+    descriptors: &mut [Descriptor],
+    buffer: *const u8,
+    len: usize,
+    chunk: usize,
+    tx: bool,
+    circular: bool,
+) -> Result<u32, Error> {
+    let chunk = core::cmp::min(chunk, MAX_BYTES_PER_DESCRIPTOR);
+    let count = (len + chunk - 1) / chunk;
+
+    if len == 0 {
+        return Err(Error::BufferTooSmall);
+    }
+    if count > descriptors.len() {
+        return Err(Error::TooFewDescriptors);
+    }
+    check_buffer(descriptors.as_ptr() as *const u8, 0)?;
+
+    let first = descriptors.as_ptr();
+    for (index, descriptor) in descriptors[..count].iter_mut().enumerate() {
+        let offset = index * chunk;
+        let size = core::cmp::min(chunk, len - offset);
+        let last = index == count - 1;
+
+        descriptor.set(
+            unsafe { buffer.add(offset) },
+            size,
+            if tx { size } else { 0 },
+            last && !circular,
+        );
+        descriptor.next = if !last {
+            unsafe { (descriptor as *const Descriptor).add(1) }
+        } else if circular {
+            first
+        } else {
+            core::ptr::null()
+        };
+    }
+
+    Ok(first as u32)
+}
+
+/// Link the descriptors into a ring of `parts` parts of `part_len` bytes each
+///
+/// Each part is covered by a single descriptor, so `part_len` is limited to
+/// [MAX_BYTES_PER_DESCRIPTOR]. For transmit rings every descriptor gets the eof flag, so an
+/// eof interrupt is generated after each part.
+///
+/// Returns the address of the first descriptor.
+pub(crate) fn link_ring(
+    descriptors: &mut [Descriptor],
+    buffer: *const u8,
+    part_len: usize,
+    parts: usize,
+    tx: bool,
+) -> Result<u32, Error> {
+    if part_len == 0 || parts == 0 {
+        return Err(Error::BufferTooSmall);
+    }
+    if part_len > MAX_BYTES_PER_DESCRIPTOR || part_len % 4 != 0 {
+        return Err(Error::BufferNotAligned);
+    }
+    if parts > descriptors.len() {
+        return Err(Error::TooFewDescriptors);
+    }
+    check_buffer(buffer, part_len * parts)?;
+    check_buffer(descriptors.as_ptr() as *const u8, 0)?;
+
+    let first = descriptors.as_ptr();
+    for (index, descriptor) in descriptors[..parts].iter_mut().enumerate() {
+        descriptor.set(
+            unsafe { buffer.add(index * part_len) },
+            part_len,
+            if tx { part_len } else { 0 },
+            tx,
+        );
+        descriptor.next = if index == parts - 1 {
+            first
+        } else {
+            unsafe { (descriptor as *const Descriptor).add(1) }
+        };
+    }
+
+    Ok(first as u32)
+}
+
+/// Find the index of the descriptor at `address` (as reported by the DMA engine)
+pub(crate) fn descriptor_index(descriptors: &[Descriptor], address: u32) -> Option<usize> {
+    let first = descriptors.as_ptr() as u32;
+    let size = core::mem::size_of::<Descriptor>() as u32;
+
+    if address < first || (address - first) % size != 0 {
+        return None;
+    }
+
+    let index = ((address - first) / size) as usize;
+    if index < descriptors.len() {
+        Some(index)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,1092 @@
+//! I2S peripheral control
+//!
+//! The ESP32 has two I2S peripherals, both with DMA support. [I2S] implements the serial audio
+//! modes: master and slave mode, Philips, MSB aligned and PCM frame formats, 8 to 32 bit samples,
+//! mono and stereo. I2S0 also supports PDM transmit and receive. Next to the serial audio
+//! modes, I2S0 supports a parallel LCD/camera mode (see [parallel]).
+//!
+//! All transfers use DMA, therefore buffers and descriptors (see [dma]) need to be located in
+//! internal RAM and need to have a `'static` lifetime.
+//!
+//! Audio data is transferred via ring buffers consisting of at least two equally sized parts
+//! (e.g. two halves). After each part the DMA engine generates an interrupt
+//! ([Event::TxPartDone], [Event::RxPartDone]) and the part can be refilled or read via
+//! [I2S::tx_next_part] and [I2S::rx_next_part].
+//!
+//! Samples are stored in the buffers in 16 bit (8 and 16 bit samples) or 32 bit (24 and 32 bit
+//! samples) words, with the left channel first. 8 and 24 bit samples are MSB aligned.
+//!
+//! The peripheral clock is derived from either the PLL/2 clock (160MHz) or the APLL. As the
+//! PLL/2 clock only allows for fractional division, the APLL is needed for accurate audio sample
+//! rates. When using the PLL/2 clock the drivers obtain a PLL/2 lock for as long as they are
+//! alive. When using the APLL they obtain an APLL lock, so both I2S peripherals can only use the
+//! APLL at the same time when their sample rates need the same APLL frequency.
+//!
+//! To handle the DMA interrupts enable the interrupt via e.g.
+//! `interrupt::enable(Interrupt::I2S0)` and implement the `I2S0` interrupt handler.
+//!
+//! # TODO
+//! - MCLK output
+
+use {
+    crate::{
+        clock_control::{dfs, ClockControlConfig},
+        gpio::{InputPin, OutputPin},
+        units::*,
+    },
+    dma::Descriptor,
+};
+
+pub mod dma;
+pub mod parallel;
+
+pub(crate) use private::Instance;
+
+/// I2S Errors
+#[derive(Debug)]
+pub enum Error {
+    FrequencyTooHigh,
+    FrequencyTooLow,
+    BufferTooSmall,
+    BufferNotAligned,
+    BufferNotDmaCapable,
+    TooFewDescriptors,
+    Busy,
+    NotRunning,
+    PdmNotSupported,
+    NoDataPin,
+    ClockError,
+}
+
+/// I2S configuration
+pub mod config {
+    use crate::units::*;
+
+    /// Master or slave mode
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum Mode {
+        /// Clocks (BCK & WS) are generated by the peripheral
+        Master,
+        /// Clocks (BCK & WS) are received from an external device
+        Slave,
+    }
+
+    /// Frame format
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum Format {
+        /// Philips standard I2S: data delayed by one bit clock after the WS transition
+        Philips,
+        /// MSB aligned (left justified)
+        MSB,
+        /// PCM with a one bit clock wide frame sync
+        PCMShort,
+        /// PCM with a frame sync as wide as a sample
+        PCMLong,
+    }
+
+    /// Number of bits per sample
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum Bits {
+        Bits8 = 8,
+        Bits16 = 16,
+        Bits24 = 24,
+        Bits32 = 32,
+    }
+
+    /// Channel configuration
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum Channels {
+        /// Both left and right channel
+        Stereo,
+        /// Only the left channel is stored/sent
+        MonoLeft,
+        /// Only the right channel is stored/sent
+        MonoRight,
+    }
+
+    /// Clock source for the I2S module clock
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum ClockSource {
+        /// PLL/2 (160MHz) with a fractional divider
+        PLL,
+        /// Audio PLL (is configured by the driver)
+        APLL,
+    }
+
+    /// I2S configuration
+    #[derive(Copy, Clone)]
+    pub struct Config {
+        pub mode: Mode,
+        pub format: Format,
+        pub bits: Bits,
+        pub channels: Channels,
+        pub sample_rate: Hertz,
+        pub clock_source: ClockSource,
+        /// Use PDM instead of PCM (only on I2S0 in master mode)
+        pub pdm: bool,
+    }
+
+    impl Config {
+        pub fn mode(mut self, mode: Mode) -> Self {
+            self.mode = mode;
+            self
+        }
+
+        pub fn format(mut self, format: Format) -> Self {
+            self.format = format;
+            self
+        }
+
+        pub fn bits(mut self, bits: Bits) -> Self {
+            self.bits = bits;
+            self
+        }
+
+        pub fn channels(mut self, channels: Channels) -> Self {
+            self.channels = channels;
+            self
+        }
+
+        pub fn sample_rate(mut self, sample_rate: Hertz) -> Self {
+            self.sample_rate = sample_rate;
+            self
+        }
+
+        pub fn clock_source(mut self, clock_source: ClockSource) -> Self {
+            self.clock_source = clock_source;
+            self
+        }
+
+        pub fn pdm(mut self, pdm: bool) -> Self {
+            self.pdm = pdm;
+            self
+        }
+    }
+
+    impl Default for Config {
+        fn default() -> Config {
+            Config {
+                mode: Mode::Master,
+                format: Format::Philips,
+                bits: Bits::Bits16,
+                channels: Channels::Stereo,
+                sample_rate: Hertz(44_100),
+                clock_source: ClockSource::PLL,
+                pdm: false,
+            }
+        }
+    }
+}
+
+/// Interrupt events
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Event {
+    /// A part of the transmit ring buffer has been sent
+    TxPartDone,
+    /// A part of the receive ring buffer has been filled
+    RxPartDone,
+    /// The transmit DMA engine ran out of descriptors
+    TxDescriptorEmpty,
+    /// The receive DMA engine ran out of descriptors
+    RxDescriptorEmpty,
+}
+
+/// Pins used by the I2S interface
+///
+/// At least one of `dout` and `din` needs to be given. In PDM mode `ws` is the PDM clock and
+/// `bck` is not used.
+pub struct Pins<
+    WS: InputPin + OutputPin,
+    // default pins to allow type inference
+    BCK: InputPin + OutputPin = crate::gpio::Gpio26<crate::gpio::Output<crate::gpio::PushPull>>,
+    DOUT: OutputPin = crate::gpio::Gpio2<crate::gpio::Output<crate::gpio::PushPull>>,
+    DIN: InputPin = crate::gpio::Gpio1<crate::gpio::Input<crate::gpio::Floating>>,
+> {
+    pub ws: WS,
+    pub bck: Option<BCK>,
+    pub dout: Option<DOUT>,
+    pub din: Option<DIN>,
+}
+
+/// DMA ring buffer
+pub(crate) struct Ring {
+    buffer: &'static mut [u8],
+    descriptors: &'static mut [Descriptor],
+    part_len: usize,
+    parts: usize,
+    last_part: Option<usize>,
+}
+
+impl Ring {
+    /// Split the buffer into parts of `part_len` bytes and link the descriptors into a ring
+    ///
+    /// At least two parts are needed, as finished parts are told apart by their index.
+    /// Returns the ring and the address of the first descriptor.
+    pub(crate) fn new(
+        buffer: &'static mut [u8],
+        descriptors: &'static mut [Descriptor],
+        part_len: usize,
+        tx: bool,
+    ) -> Result<(Self, u32), Error> {
+        let parts = if part_len == 0 {
+            0
+        } else {
+            buffer.len() / part_len
+        };
+        if parts < 2 {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let address = dma::link_ring(descriptors, buffer.as_ptr(), part_len, parts, tx)?;
+
+        Ok((
+            Ring {
+                buffer,
+                descriptors,
+                part_len,
+                parts,
+                last_part: None,
+            },
+            address,
+        ))
+    }
+
+    /// Length of a part in bytes
+    pub(crate) fn part_len(&self) -> usize {
+        self.part_len
+    }
+
+    /// Get the part finished by the DMA engine, if not yet returned
+    ///
+    /// `address` is the address of the last descriptor finished by the DMA engine.
+    pub(crate) fn next_part(&mut self, address: u32) -> nb::Result<&mut [u8], Error> {
+        let part = dma::descriptor_index(&self.descriptors[..self.parts], address)
+            .ok_or(nb::Error::WouldBlock)?;
+
+        if self.last_part == Some(part) {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.last_part = Some(part);
+
+        let start = part * self.part_len;
+        Ok(&mut self.buffer[start..start + self.part_len])
+    }
+
+    /// Return the buffer and descriptors
+    pub(crate) fn into_buffers(self) -> RingBuffers {
+        (self.buffer, self.descriptors)
+    }
+}
+
+/// Ring buffer handed back when stopping a transfer
+pub type RingBuffers = (&'static mut [u8], &'static mut [Descriptor]);
+
+/// Bit clock to sample rate ratio in PDM mode
+const PDM_BCK_FACTOR: u32 = 64;
+/// Up sampling parameters for PDM transmit
+const PDM_TX_FP: u32 = 960;
+/// Bit clock divider in PDM mode
+const PDM_BCK_DIVIDER: u32 = 8;
+/// Minimum and maximum bit clock divider
+const BCK_DIVIDER_MIN: u32 = 2;
+const BCK_DIVIDER_MAX: u32 = 8;
+/// Minimum APLL frequency
+const APLL_FREQ_MIN: Hertz = Hertz(5_400_000);
+
+/// I2S abstraction
+pub struct I2S<
+    INSTANCE: Instance,
+    WS: InputPin + OutputPin,
+    // default pins to allow type inference
+    BCK: InputPin + OutputPin = crate::gpio::Gpio26<crate::gpio::Output<crate::gpio::PushPull>>,
+    DOUT: OutputPin = crate::gpio::Gpio2<crate::gpio::Output<crate::gpio::PushPull>>,
+    DIN: InputPin = crate::gpio::Gpio1<crate::gpio::Input<crate::gpio::Floating>>,
+> {
+    instance: INSTANCE,
+    pins: Pins<WS, BCK, DOUT, DIN>,
+    config: config::Config,
+    clock_control: ClockControlConfig,
+    sample_rate: Hertz,
+    tx: Option<Ring>,
+    rx: Option<Ring>,
+    _pll_lock: Option<dfs::LockPllD2>,
+    _apll_lock: Option<dfs::LockApll>,
+}
+
+impl<
+        INSTANCE: Instance,
+        WS: InputPin + OutputPin,
+        BCK: InputPin + OutputPin,
+        DOUT: OutputPin,
+        DIN: InputPin,
+    > I2S<INSTANCE, WS, BCK, DOUT, DIN>
+{
+    /// Create new instance of the I2S driver
+    pub fn new(
+        mut instance: INSTANCE,
+        pins: Pins<WS, BCK, DOUT, DIN>,
+        config: config::Config,
+        clock_control: ClockControlConfig,
+    ) -> Result<Self, Error> {
+        if pins.dout.is_none() && pins.din.is_none() {
+            return Err(Error::NoDataPin);
+        }
+        if config.pdm && (!INSTANCE::PDM || config.mode == config::Mode::Slave) {
+            return Err(Error::PdmNotSupported);
+        }
+
+        instance.reset().enable();
+
+        let mut i2s = I2S {
+            instance,
+            pins,
+            config,
+            clock_control,
+            sample_rate: Hertz(0),
+            tx: None,
+            rx: None,
+            _pll_lock: None,
+            _apll_lock: None,
+        };
+
+        i2s.init_pins();
+        i2s.init_registers();
+        i2s.change_sample_rate(config.sample_rate)?;
+        reset_all(&i2s.instance);
+
+        Ok(i2s)
+    }
+
+    /// Connect the pins to the peripheral
+    fn init_pins(&mut self) {
+        let tx = self.pins.dout.is_some();
+        let master = self.config.mode == config::Mode::Master;
+
+        if master {
+            let (bck, ws) = if tx {
+                (INSTANCE::TX_BCK_OUT, INSTANCE::TX_WS_OUT)
+            } else {
+                (INSTANCE::RX_BCK_OUT, INSTANCE::RX_WS_OUT)
+            };
+            self.pins
+                .ws
+                .set_to_push_pull_output()
+                .connect_peripheral_to_output(ws);
+            if let Some(pin) = &mut self.pins.bck {
+                pin.set_to_push_pull_output()
+                    .connect_peripheral_to_output(bck);
+            }
+        } else {
+            let (bck, ws) = if tx {
+                (INSTANCE::TX_BCK_IN, INSTANCE::TX_WS_IN)
+            } else {
+                (INSTANCE::RX_BCK_IN, INSTANCE::RX_WS_IN)
+            };
+            self.pins.ws.set_to_input().connect_input_to_peripheral(ws);
+            if let Some(pin) = &mut self.pins.bck {
+                pin.set_to_input().connect_input_to_peripheral(bck);
+            }
+        }
+
+        if let Some(pin) = &mut self.pins.dout {
+            pin.set_to_push_pull_output()
+                .connect_peripheral_to_output(INSTANCE::DATA_OUT);
+        }
+
+        if let Some(pin) = &mut self.pins.din {
+            pin.set_to_input()
+                .connect_input_to_peripheral(INSTANCE::DATA_IN);
+        }
+    }
+
+    /// Initialize the registers according to the configuration
+    fn init_registers(&mut self) {
+        let tx = self.pins.dout.is_some();
+        let rx = self.pins.din.is_some();
+        let config = self.config;
+
+        let slave = config.mode == config::Mode::Slave;
+        let msb_shift = config.format == config::Format::Philips;
+        let short_sync = config.format == config::Format::PCMShort;
+        let bits32 = config.bits as u8 > 16;
+        let mono = config.channels != config::Channels::Stereo;
+        let fifo_mod = (bits32 as u8) << 1 | mono as u8;
+        let chan_mod = match config.channels {
+            config::Channels::Stereo => 0,
+            config::Channels::MonoRight => 1,
+            config::Channels::MonoLeft => 2,
+        };
+
+        unsafe {
+            self.instance.conf.write(|w| {
+                w.bits(0)
+                    .tx_slave_mod()
+                    .bit(slave)
+                    // in full duplex master mode the receiver uses the clocks of the transmitter
+                    .rx_slave_mod()
+                    .bit(slave || (tx && rx))
+                    .sig_loopback()
+                    .bit(tx && rx)
+                    .tx_msb_shift()
+                    .bit(msb_shift)
+                    .rx_msb_shift()
+                    .bit(msb_shift)
+                    .tx_short_sync()
+                    .bit(short_sync)
+                    .rx_short_sync()
+                    .bit(short_sync)
+                    .tx_right_first()
+                    .clear_bit()
+                    .rx_right_first()
+                    .clear_bit()
+                    .tx_msb_right()
+                    .clear_bit()
+                    .rx_msb_right()
+                    .clear_bit()
+            });
+            self.instance.conf1.write(|w| {
+                w.bits(0)
+                    .tx_pcm_bypass()
+                    .set_bit()
+                    .rx_pcm_bypass()
+                    .set_bit()
+            });
+            self.instance.conf2.write(|w| w.bits(0));
+            self.instance.timing.write(|w| w.bits(0));
+            self.instance.fifo_conf.write(|w| {
+                w.bits(0)
+                    .dscr_en()
+                    .set_bit()
+                    .tx_fifo_mod_force_en()
+                    .set_bit()
+                    .rx_fifo_mod_force_en()
+                    .set_bit()
+                    .tx_fifo_mod()
+                    .bits(fifo_mod)
+                    .rx_fifo_mod()
+                    .bits(fifo_mod)
+                    .tx_data_num()
+                    .bits(32)
+                    .rx_data_num()
+                    .bits(32)
+            });
+            self.instance.conf_chan.write(|w| {
+                w.bits(0)
+                    .tx_chan_mod()
+                    .bits(chan_mod)
+                    .rx_chan_mod()
+                    .bits(chan_mod)
+            });
+            self.instance.lc_conf.write(|w| {
+                w.bits(0)
+                    .out_eof_mode()
+                    .set_bit()
+                    .outdscr_burst_en()
+                    .set_bit()
+                    .out_data_burst_en()
+                    .set_bit()
+                    .indscr_burst_en()
+                    .set_bit()
+            });
+            self.instance.int_ena.write(|w| w.bits(0));
+            self.instance.int_clr.write(|w| w.bits(0xffff_ffff));
+
+            if INSTANCE::PDM {
+                self.instance.pdm_conf.modify(|_, w| {
+                    w.tx_pdm_en()
+                        .bit(config.pdm && tx)
+                        .pcm2pdm_conv_en()
+                        .bit(config.pdm && tx)
+                        .rx_pdm_en()
+                        .bit(config.pdm && rx)
+                        .pdm2pcm_conv_en()
+                        .bit(config.pdm && rx)
+                        .rx_pdm_sinc_dsr_16_en()
+                        .clear_bit()
+                });
+            }
+        }
+    }
+
+    /// Change the sample rate
+    ///
+    /// Returns the actual sample rate, which can differ slightly from the requested one due to
+    /// the limited divider resolution.
+    pub fn change_sample_rate<T: Into<Hertz>>(&mut self, sample_rate: T) -> Result<Hertz, Error> {
+        let sample_rate = sample_rate.into();
+        let config = self.config;
+
+        // bit clock frequency and divider between module clock and bit clock
+        let (bck_frequency, bck_dividers) = if config.pdm {
+            let bck_frequency = if self.pins.dout.is_some() {
+                let fs = u32::from(sample_rate) / 100;
+                if fs == 0 {
+                    return Err(Error::FrequencyTooLow);
+                }
+                self.instance.pdm_freq_conf.write(|w| unsafe {
+                    w.tx_pdm_fp()
+                        .bits(PDM_TX_FP as u16)
+                        .tx_pdm_fs()
+                        .bits(fs as u16)
+                });
+                self.instance
+                    .pdm_conf
+                    .modify(|_, w| unsafe { w.tx_pdm_sinc_osr2().bits((PDM_TX_FP / fs) as u8) });
+                Hertz(
+                    (u32::from(sample_rate) as u64 * (PDM_BCK_FACTOR * PDM_TX_FP) as u64
+                        / fs as u64) as u32,
+                )
+            } else {
+                sample_rate * PDM_BCK_FACTOR
+            };
+            (bck_frequency, PDM_BCK_DIVIDER..=PDM_BCK_DIVIDER)
+        } else {
+            // a frame always holds two slots, also in mono mode
+            (
+                sample_rate * 2 * config.bits as u32,
+                BCK_DIVIDER_MIN..=BCK_DIVIDER_MAX,
+            )
+        };
+
+        if config.mode == config::Mode::Slave {
+            // the module clock needs to be much faster than the external bit clock
+            self._pll_lock = Some(self.clock_control.lock_plld2());
+            set_clock_dividers(&self.instance, ClockDividers { num: 2, b: 0, a: 1 }, false);
+            self.set_bck_divider(BCK_DIVIDER_MIN);
+            self.sample_rate = sample_rate;
+            return Ok(sample_rate);
+        }
+
+        let mut result = Err(Error::ClockError);
+        for bck_divider in bck_dividers.rev() {
+            let module_frequency = bck_frequency * bck_divider;
+
+            result = match config.clock_source {
+                config::ClockSource::PLL => {
+                    if self._pll_lock.is_none() {
+                        self._pll_lock = Some(self.clock_control.lock_plld2());
+                    }
+                    let source = self.clock_control.pll_d2_frequency();
+                    ClockDividers::calculate(source, module_frequency)
+                        .map(|dividers| (dividers, source, bck_divider))
+                }
+                config::ClockSource::APLL => {
+                    self._pll_lock = None;
+                    self._apll_lock = None;
+                    // run the APLL at a multiple of the module clock, the APLL can be shared
+                    // with the other I2S when it runs at the same frequency
+                    let multiplier = core::cmp::max(
+                        2,
+                        (APLL_FREQ_MIN + module_frequency - Hertz(1)) / module_frequency,
+                    );
+                    match self.clock_control.lock_apll(module_frequency * multiplier) {
+                        Ok(lock) => {
+                            let source = self.clock_control.apll_frequency();
+                            let result = ClockDividers::calculate(source, module_frequency)
+                                .map(|dividers| (dividers, source, bck_divider));
+                            if result.is_ok() {
+                                self._apll_lock = Some(lock);
+                            }
+                            result
+                        }
+                        Err(_) => Err(Error::ClockError),
+                    }
+                }
+            };
+
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        let (dividers, source, bck_divider) = result?;
+        set_clock_dividers(
+            &self.instance,
+            dividers,
+            config.clock_source == config::ClockSource::APLL,
+        );
+        self.set_bck_divider(bck_divider);
+
+        // scale the requested sample rate with the error of the module clock
+        self.sample_rate = Hertz(
+            (u32::from(sample_rate) as u64 * u32::from(dividers.frequency(source)) as u64
+                / u32::from(bck_frequency * bck_divider) as u64) as u32,
+        );
+        Ok(self.sample_rate)
+    }
+
+    /// Set the divider between module clock and bit clock and the sample width
+    fn set_bck_divider(&mut self, bck_divider: u32) {
+        let bits = self.config.bits as u8;
+        self.instance.sample_rate_conf.write(|w| unsafe {
+            w.tx_bits_mod()
+                .bits(bits)
+                .rx_bits_mod()
+                .bits(bits)
+                .tx_bck_div_num()
+                .bits(bck_divider as u8)
+                .rx_bck_div_num()
+                .bits(bck_divider as u8)
+        });
+    }
+
+    /// The actual sample rate
+    pub fn sample_rate(&self) -> Hertz {
+        self.sample_rate
+    }
+
+    /// Start transmitting from a ring buffer
+    ///
+    /// The buffer is split into at least two parts of `part_len` bytes, each part needs one
+    /// descriptor. The buffer should be filled with the initial data before starting.
+    pub fn start_tx(
+        &mut self,
+        buffer: &'static mut [u8],
+        descriptors: &'static mut [Descriptor],
+        part_len: usize,
+    ) -> Result<(), Error> {
+        if self.pins.dout.is_none() {
+            return Err(Error::NoDataPin);
+        }
+        if self.tx.is_some() {
+            return Err(Error::Busy);
+        }
+
+        let (ring, address) = Ring::new(buffer, descriptors, part_len, true)?;
+        start_tx_dma(&self.instance, address);
+        self.tx = Some(ring);
+
+        Ok(())
+    }
+
+    /// Start receiving into a ring buffer
+    ///
+    /// The buffer is split into at least two parts of `part_len` bytes, each part needs one
+    /// descriptor.
+    pub fn start_rx(
+        &mut self,
+        buffer: &'static mut [u8],
+        descriptors: &'static mut [Descriptor],
+        part_len: usize,
+    ) -> Result<(), Error> {
+        if self.pins.din.is_none() {
+            return Err(Error::NoDataPin);
+        }
+        if self.rx.is_some() {
+            return Err(Error::Busy);
+        }
+
+        let (ring, address) = Ring::new(buffer, descriptors, part_len, false)?;
+        start_rx_dma(&self.instance, address, ring.part_len());
+        self.rx = Some(ring);
+
+        Ok(())
+    }
+
+    /// Get the part of the transmit ring buffer which has just been sent, so it can be refilled
+    ///
+    /// Each part is only returned once. If the part is not refilled before the DMA engine
+    /// reaches it again, old data will be sent.
+    pub fn tx_next_part(&mut self) -> nb::Result<&mut [u8], Error> {
+        let address = self.instance.out_eof_des_addr.read().bits();
+        let ring = self
+            .tx
+            .as_mut()
+            .ok_or(nb::Error::Other(Error::NotRunning))?;
+
+        if self
+            .instance
+            .int_raw
+            .read()
+            .out_eof_int_raw()
+            .bit_is_clear()
+        {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        ring.next_part(address)
+    }
+
+    /// Get the part of the receive ring buffer which has just been filled
+    ///
+    /// Each part is only returned once. If the part is not read before the DMA engine
+    /// reaches it again, it will be overwritten.
+    pub fn rx_next_part(&mut self) -> nb::Result<&mut [u8], Error> {
+        let address = self.instance.in_eof_des_addr.read().bits();
+        let ring = self
+            .rx
+            .as_mut()
+            .ok_or(nb::Error::Other(Error::NotRunning))?;
+
+        if self
+            .instance
+            .int_raw
+            .read()
+            .in_suc_eof_int_raw()
+            .bit_is_clear()
+        {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        ring.next_part(address)
+    }
+
+    /// Stop transmitting and return the ring buffer
+    pub fn stop_tx(&mut self) -> Result<RingBuffers, Error> {
+        let ring = self.tx.take().ok_or(Error::NotRunning)?;
+        stop_tx_dma(&self.instance);
+        Ok(ring.into_buffers())
+    }
+
+    /// Stop receiving and return the ring buffer
+    pub fn stop_rx(&mut self) -> Result<RingBuffers, Error> {
+        let ring = self.rx.take().ok_or(Error::NotRunning)?;
+        stop_rx_dma(&self.instance);
+        Ok(ring.into_buffers())
+    }
+
+    /// Starts listening for an [Event]
+    pub fn listen(&mut self, event: Event) {
+        self.instance.int_ena.modify(|_, w| match event {
+            Event::TxPartDone => w.out_eof_int_ena().set_bit(),
+            Event::RxPartDone => w.in_suc_eof_int_ena().set_bit(),
+            Event::TxDescriptorEmpty => w.out_dscr_err_int_ena().set_bit(),
+            Event::RxDescriptorEmpty => w.in_dscr_empty_int_ena().set_bit(),
+        });
+    }
+
+    /// Stops listening for an [Event]
+    pub fn unlisten(&mut self, event: Event) {
+        self.instance.int_ena.modify(|_, w| match event {
+            Event::TxPartDone => w.out_eof_int_ena().clear_bit(),
+            Event::RxPartDone => w.in_suc_eof_int_ena().clear_bit(),
+            Event::TxDescriptorEmpty => w.out_dscr_err_int_ena().clear_bit(),
+            Event::RxDescriptorEmpty => w.in_dscr_empty_int_ena().clear_bit(),
+        });
+    }
+
+    /// Clear the interrupt status of an [Event]
+    pub fn clear_interrupt(&mut self, event: Event) {
+        self.instance.int_clr.write(|w| match event {
+            Event::TxPartDone => w.out_eof_int_clr().set_bit(),
+            Event::RxPartDone => w.in_suc_eof_int_clr().set_bit(),
+            Event::TxDescriptorEmpty => w.out_dscr_err_int_clr().set_bit(),
+            Event::RxDescriptorEmpty => w.in_dscr_empty_int_clr().set_bit(),
+        });
+    }
+
+    /// Check if the interrupt status of an [Event] is set
+    pub fn is_interrupt_set(&self, event: Event) -> bool {
+        let status = self.instance.int_raw.read();
+        match event {
+            Event::TxPartDone => status.out_eof_int_raw().bit_is_set(),
+            Event::RxPartDone => status.in_suc_eof_int_raw().bit_is_set(),
+            Event::TxDescriptorEmpty => status.out_dscr_err_int_raw().bit_is_set(),
+            Event::RxDescriptorEmpty => status.in_dscr_empty_int_raw().bit_is_set(),
+        }
+    }
+
+    /// Release and return the raw interface to the underlying I2S peripheral, the pins and the
+    /// transmit and receive ring buffers (if running)
+    ///
+    /// Running transfers are stopped. The APLL lock of this driver is released, which powers
+    /// the APLL down if no other driver uses it.
+    pub fn release(
+        mut self,
+    ) -> (
+        INSTANCE,
+        Pins<WS, BCK, DOUT, DIN>,
+        Option<RingBuffers>,
+        Option<RingBuffers>,
+    ) {
+        let tx = self.stop_tx().ok();
+        let rx = self.stop_rx().ok();
+        self.instance.disable();
+        self._apll_lock = None;
+        (self.instance, self.pins, tx, rx)
+    }
+}
+
+/// Dividers for the I2S module clock: `f = source / (num + b / a)`
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub(crate) struct ClockDividers {
+    pub num: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl ClockDividers {
+    /// Calculate the (fractional) dividers for the module clock closest to `frequency`
+    pub(crate) fn calculate(source: Hertz, frequency: Hertz) -> Result<Self, Error> {
+        let source = u32::from(source) as u64;
+        let frequency = u32::from(frequency) as u64;
+
+        if frequency == 0 || source / frequency > 255 {
+            return Err(Error::FrequencyTooLow);
+        }
+        if source / frequency < 2 {
+            return Err(Error::FrequencyTooHigh);
+        }
+
+        let num = source / frequency;
+        let remainder = source % frequency;
+
+        let mut best = ClockDividers {
+            num: num as u8,
+            b: 0,
+            a: 1,
+        };
+        if remainder == 0 {
+            return Ok(best);
+        }
+
+        let mut best_error = remainder;
+        for a in 1..=63u64 {
+            let b = (remainder * a + frequency / 2) / frequency;
+            if b >= a {
+                continue;
+            }
+            // error of the resulting divider scaled by frequency * a
+            let error = (remainder * a).max(b * frequency) - (remainder * a).min(b * frequency);
+            if error * (best.a as u64) < best_error * a {
+                best = ClockDividers {
+                    num: num as u8,
+                    b: b as u8,
+                    a: a as u8,
+                };
+                best_error = error;
+                if error == 0 {
+                    break;
+                }
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// The resulting module clock frequency for `source`
+    pub(crate) fn frequency(&self, source: Hertz) -> Hertz {
+        let source = u32::from(source) as u64;
+        Hertz((source * self.a as u64 / (self.num as u64 * self.a as u64 + self.b as u64)) as u32)
+    }
+}
+
+/// Reset the transmit and receive units, fifos and DMA engine of an I2S peripheral
+pub(crate) fn reset_all(i2s: &crate::target::i2s::RegisterBlock) {
+    i2s.lc_conf.modify(|_, w| {
+        w.in_rst()
+            .set_bit()
+            .out_rst()
+            .set_bit()
+            .ahbm_rst()
+            .set_bit()
+            .ahbm_fifo_rst()
+            .set_bit()
+    });
+    i2s.lc_conf.modify(|_, w| {
+        w.in_rst()
+            .clear_bit()
+            .out_rst()
+            .clear_bit()
+            .ahbm_rst()
+            .clear_bit()
+            .ahbm_fifo_rst()
+            .clear_bit()
+    });
+
+    i2s.conf.modify(|_, w| {
+        w.tx_reset()
+            .set_bit()
+            .rx_reset()
+            .set_bit()
+            .tx_fifo_reset()
+            .set_bit()
+            .rx_fifo_reset()
+            .set_bit()
+    });
+    i2s.conf.modify(|_, w| {
+        w.tx_reset()
+            .clear_bit()
+            .rx_reset()
+            .clear_bit()
+            .tx_fifo_reset()
+            .clear_bit()
+            .rx_fifo_reset()
+            .clear_bit()
+    });
+}
+
+/// Reset the transmitter and start the transmit DMA engine at the descriptor at `address`
+pub(crate) fn start_tx_dma(i2s: &crate::target::i2s::RegisterBlock, address: u32) {
+    i2s.conf.modify(|_, w| w.tx_start().clear_bit());
+    i2s.conf
+        .modify(|_, w| w.tx_reset().set_bit().tx_fifo_reset().set_bit());
+    i2s.conf
+        .modify(|_, w| w.tx_reset().clear_bit().tx_fifo_reset().clear_bit());
+    i2s.lc_conf.modify(|_, w| w.out_rst().set_bit());
+    i2s.lc_conf.modify(|_, w| w.out_rst().clear_bit());
+    i2s.int_clr.write(|w| {
+        w.out_eof_int_clr()
+            .set_bit()
+            .out_dscr_err_int_clr()
+            .set_bit()
+            .out_total_eof_int_clr()
+            .set_bit()
+    });
+
+    i2s.out_link
+        .modify(|_, w| unsafe { w.outlink_addr().bits(address & 0xfffff) });
+    i2s.out_link.modify(|_, w| w.outlink_start().set_bit());
+    i2s.conf.modify(|_, w| w.tx_start().set_bit());
+}
+
+/// Reset the receiver and start the receive DMA engine at the descriptor at `address`
+///
+/// An eof is generated after every `eof_len` bytes.
+pub(crate) fn start_rx_dma(i2s: &crate::target::i2s::RegisterBlock, address: u32, eof_len: usize) {
+    i2s.conf.modify(|_, w| w.rx_start().clear_bit());
+    i2s.conf
+        .modify(|_, w| w.rx_reset().set_bit().rx_fifo_reset().set_bit());
+    i2s.conf
+        .modify(|_, w| w.rx_reset().clear_bit().rx_fifo_reset().clear_bit());
+    i2s.lc_conf.modify(|_, w| w.in_rst().set_bit());
+    i2s.lc_conf.modify(|_, w| w.in_rst().clear_bit());
+    i2s.int_clr.write(|w| {
+        w.in_suc_eof_int_clr()
+            .set_bit()
+            .in_dscr_empty_int_clr()
+            .set_bit()
+    });
+
+    i2s.rxeof_num
+        .write(|w| unsafe { w.rx_eof_num().bits((eof_len / 4) as u32) });
+    i2s.in_link
+        .modify(|_, w| unsafe { w.inlink_addr().bits(address & 0xfffff) });
+    i2s.in_link.modify(|_, w| w.inlink_start().set_bit());
+    i2s.conf.modify(|_, w| w.rx_start().set_bit());
+}
+
+/// Stop the transmitter and the transmit DMA engine
+pub(crate) fn stop_tx_dma(i2s: &crate::target::i2s::RegisterBlock) {
+    i2s.conf.modify(|_, w| w.tx_start().clear_bit());
+    i2s.out_link.modify(|_, w| w.outlink_stop().set_bit());
+}
+
+/// Stop the receiver and the receive DMA engine
+pub(crate) fn stop_rx_dma(i2s: &crate::target::i2s::RegisterBlock) {
+    i2s.conf.modify(|_, w| w.rx_start().clear_bit());
+    i2s.in_link.modify(|_, w| w.inlink_stop().set_bit());
+}
+
+/// Byte offset in a DMA buffer of the `index`th 16 bit sample
+///
+/// The peripheral handles the upper half word of each 32 bit word first.
+pub(crate) fn sample_offset(index: usize) -> usize {
+    (index ^ 1) * 2
+}
+
+/// Set the module clock dividers and select the PLL/2 clock or APLL as source
+pub(crate) fn set_clock_dividers(
+    i2s: &crate::target::i2s::RegisterBlock,
+    div: ClockDividers,
+    apll: bool,
+) {
+    i2s.clkm_conf.write(|w| unsafe {
+        w.clka_ena()
+            .bit(apll)
+            .clk_en()
+            .set_bit()
+            .clkm_div_num()
+            .bits(div.num)
+            .clkm_div_b()
+            .bits(div.b)
+            .clkm_div_a()
+            .bits(div.a)
+    });
+}
+
+mod private {
+    use crate::gpio::{InputSignal, OutputSignal};
+    use crate::prelude::*;
+    use crate::target::{i2s, I2S0, I2S1};
+    use core::ops::Deref;
+
+    pub trait Instance: Deref<Target = i2s::RegisterBlock> {
+        /// Supports PDM
+        const PDM: bool;
+
+        /// Clock signals in master mode
+        const TX_BCK_OUT: OutputSignal;
+        const TX_WS_OUT: OutputSignal;
+        const RX_BCK_OUT: OutputSignal;
+        const RX_WS_OUT: OutputSignal;
+
+        /// Clock signals in slave mode
+        const TX_BCK_IN: InputSignal;
+        const TX_WS_IN: InputSignal;
+        const RX_BCK_IN: InputSignal;
+        const RX_WS_IN: InputSignal;
+
+        /// Serial data signals
+        const DATA_OUT: OutputSignal;
+        const DATA_IN: InputSignal;
+
+        fn ptr() -> *const i2s::RegisterBlock;
+        /// Enable peripheral
+        fn enable(&mut self) -> &mut Self;
+        /// Disable peripheral
+        fn disable(&mut self) -> &mut Self;
+        /// Reset peripheral
+        fn reset(&mut self) -> &mut Self;
+    }
+
+    macro_rules! modules {
+        ($(
+            $MODULE:ident: ($pdm:literal, $tx_bck:ident, $tx_ws:ident, $rx_bck:ident, $rx_ws:ident,
+                $data_out:ident, $data_in:ident),
+        )+) => {
+            $(
+                impl Instance for $MODULE {
+                    const PDM: bool = $pdm;
+
+                    const TX_BCK_OUT: OutputSignal = OutputSignal::$tx_bck;
+                    const TX_WS_OUT: OutputSignal = OutputSignal::$tx_ws;
+                    const RX_BCK_OUT: OutputSignal = OutputSignal::$rx_bck;
+                    const RX_WS_OUT: OutputSignal = OutputSignal::$rx_ws;
+
+                    const TX_BCK_IN: InputSignal = InputSignal::$tx_bck;
+                    const TX_WS_IN: InputSignal = InputSignal::$tx_ws;
+                    const RX_BCK_IN: InputSignal = InputSignal::$rx_bck;
+                    const RX_WS_IN: InputSignal = InputSignal::$rx_ws;
+
+                    const DATA_OUT: OutputSignal = OutputSignal::$data_out;
+                    const DATA_IN: InputSignal = InputSignal::$data_in;
+
+                    fn ptr() -> *const i2s::RegisterBlock {
+                        $MODULE::ptr()
+                    }
+
+                    fn reset(&mut self) -> &mut Self {
+                        dport::reset_peripheral(dport::Peripheral::$MODULE);
+                        self
+                    }
+
+                    fn enable(&mut self) -> &mut Self {
+                        dport::enable_peripheral(dport::Peripheral::$MODULE);
+                        self
+                    }
+
+                    fn disable(&mut self) -> &mut Self {
+                        dport::disable_peripheral(dport::Peripheral::$MODULE);
+                        self
+                    }
+                }
+            )+
+        }
+    }
+
+    modules! {
+        I2S0: (true, I2S0O_BCK, I2S0O_WS, I2S0I_BCK, I2S0I_WS, I2S0O_DATA_23, I2S0I_DATA_15),
+        I2S1: (false, I2S1O_BCK, I2S1O_WS, I2S1I_BCK, I2S1I_WS, I2S1O_DATA_23, I2S1I_DATA_15),
+    }
+}
@@ -0,0 +1,657 @@
+//! Parallel 8/16-bit LCD and camera interface using the LCD mode of I2S0
+//!
+//! # LCD
+//! [ParallelLcd] drives 8080-style LCD controllers (e.g. ILI9341, ST7789) with an 8 or 16 bit
+//! data bus, a WR strobe (generated by the peripheral) and a DC pin (controlled in software).
+//! It implements [WriteOnlyDataCommand] so it can be used with display drivers based on
+//! `display-interface`. CS and RD are not handled by the driver: tie CS low and RD high or
+//! control them as normal GPIO pins.
+//!
+//! Data is copied into a DMA buffer and sent in chunks of the buffer size. Every bus write
+//! takes 2 bytes of DMA buffer, independent of the bus width. The DMA sends whole 32 bit
+//! words, so a write of an odd number of bytes (or 16 bit values on a 16 bit bus) repeats the
+//! last one.
+//!
+//! # Camera
+//! [Camera] captures data from DVP (parallel) camera sensors like the OV2640. The sensor clock
+//! (XCLK) is not generated by this driver, use for example an LEDC channel for this.
+//!
+//! Every captured byte is stored in a 16 bit word in the DMA buffer, [Camera::compact] can be
+//! used to convert the captured data in place to a plain byte stream.
+//!
+//! # Example
+//! ```no_run
+//! static mut BUFFER: [u8; 4092] = [0; 4092];
+//! static mut DESCRIPTORS: [Descriptor; 1] = [Descriptor::new()];
+//!
+//! let mut lcd = ParallelLcd::new(
+//!     dp.I2S0,
+//!     Bus8 { d0: pins.gpio12, d1: pins.gpio13, ... d7: pins.gpio27 },
+//!     Pins { wr: pins.gpio4, dc: pins.gpio2 },
+//!     unsafe { &mut BUFFER },
+//!     unsafe { &mut DESCRIPTORS },
+//!     config::Config::default().frequency(20.MHz().into()),
+//!     clkcntrl_config,
+//! )
+//! .unwrap();
+//! ```
+
+use {
+    super::{dma, dma::Descriptor, sample_offset, ClockDividers, Error, Instance},
+    crate::{
+        clock_control::{dfs, ClockControlConfig},
+        gpio::{self, InputPin, InputSignal, OutputPin, OutputSignal},
+        prelude::*,
+        target::I2S0,
+    },
+    display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand},
+};
+
+/// Divider between the I2S module clock and the WR clock
+const BCK_DIVIDER: u32 = 2;
+
+/// Parallel bus configuration
+pub mod config {
+    use crate::units::*;
+
+    /// LCD configuration
+    #[derive(Copy, Clone)]
+    pub struct Config {
+        /// Frequency of the WR strobe
+        pub frequency: Hertz,
+    }
+
+    impl Config {
+        pub fn frequency(mut self, frequency: Hertz) -> Self {
+            self.frequency = frequency;
+            self
+        }
+    }
+
+    impl Default for Config {
+        fn default() -> Config {
+            Config {
+                frequency: Hertz(10_000_000),
+            }
+        }
+    }
+}
+
+/// Data bus which can be driven by the peripheral
+pub trait OutputBus {
+    /// Width of the bus in bits
+    const WIDTH: u32;
+
+    /// Configure the pins as outputs and connect them to the peripheral
+    fn connect_output(&mut self);
+}
+
+/// Data bus which can be read by the peripheral
+pub trait InputBus {
+    /// Width of the bus in bits
+    const WIDTH: u32;
+
+    /// Configure the pins as inputs and connect them to the peripheral
+    fn connect_input(&mut self);
+}
+
+macro_rules! bus {
+    ($Bus:ident, $width:literal, $doc:literal: $( ($pin:ident, $PIN:ident, $out:ident, $in:ident), )+) => {
+        #[doc = $doc]
+        pub struct $Bus<$($PIN),+> {
+            $(pub $pin: $PIN,)+
+        }
+
+        impl<$($PIN: OutputPin),+> OutputBus for $Bus<$($PIN),+> {
+            const WIDTH: u32 = $width;
+
+            fn connect_output(&mut self) {
+                $(
+                    self.$pin
+                        .set_to_push_pull_output()
+                        .connect_peripheral_to_output(OutputSignal::$out);
+                )+
+            }
+        }
+
+        impl<$($PIN: InputPin),+> InputBus for $Bus<$($PIN),+> {
+            const WIDTH: u32 = $width;
+
+            fn connect_input(&mut self) {
+                $(
+                    self.$pin
+                        .set_to_input()
+                        .connect_input_to_peripheral(InputSignal::$in);
+                )+
+            }
+        }
+    };
+}
+
+// The output data is aligned to the top of the 24 bit output bus
+bus!(Bus8, 8, "8-bit parallel data bus":
+    (d0, D0, I2S0O_DATA_16, I2S0I_DATA_0),
+    (d1, D1, I2S0O_DATA_17, I2S0I_DATA_1),
+    (d2, D2, I2S0O_DATA_18, I2S0I_DATA_2),
+    (d3, D3, I2S0O_DATA_19, I2S0I_DATA_3),
+    (d4, D4, I2S0O_DATA_20, I2S0I_DATA_4),
+    (d5, D5, I2S0O_DATA_21, I2S0I_DATA_5),
+    (d6, D6, I2S0O_DATA_22, I2S0I_DATA_6),
+    (d7, D7, I2S0O_DATA_23, I2S0I_DATA_7),
+);
+
+bus!(Bus16, 16, "16-bit parallel data bus":
+    (d0, D0, I2S0O_DATA_8, I2S0I_DATA_0),
+    (d1, D1, I2S0O_DATA_9, I2S0I_DATA_1),
+    (d2, D2, I2S0O_DATA_10, I2S0I_DATA_2),
+    (d3, D3, I2S0O_DATA_11, I2S0I_DATA_3),
+    (d4, D4, I2S0O_DATA_12, I2S0I_DATA_4),
+    (d5, D5, I2S0O_DATA_13, I2S0I_DATA_5),
+    (d6, D6, I2S0O_DATA_14, I2S0I_DATA_6),
+    (d7, D7, I2S0O_DATA_15, I2S0I_DATA_7),
+    (d8, D8, I2S0O_DATA_16, I2S0I_DATA_8),
+    (d9, D9, I2S0O_DATA_17, I2S0I_DATA_9),
+    (d10, D10, I2S0O_DATA_18, I2S0I_DATA_10),
+    (d11, D11, I2S0O_DATA_19, I2S0I_DATA_11),
+    (d12, D12, I2S0O_DATA_20, I2S0I_DATA_12),
+    (d13, D13, I2S0O_DATA_21, I2S0I_DATA_13),
+    (d14, D14, I2S0O_DATA_22, I2S0I_DATA_14),
+    (d15, D15, I2S0O_DATA_23, I2S0I_DATA_15),
+);
+
+/// Control pins used by the LCD interface
+pub struct Pins<WR: OutputPin, DC: OutputPin> {
+    pub wr: WR,
+    pub dc: DC,
+}
+
+/// Parallel 8080-style LCD interface
+pub struct ParallelLcd<BUS: OutputBus, WR: OutputPin, DC: OutputPin> {
+    instance: I2S0,
+    bus: BUS,
+    pins: Pins<WR, DC>,
+    buffer: &'static mut [u8],
+    descriptors: &'static mut [Descriptor],
+    dividers: ClockDividers,
+    clock_control: ClockControlConfig,
+    _pll_lock: dfs::LockPllD2,
+}
+
+impl<BUS: OutputBus, WR: OutputPin, DC: OutputPin> ParallelLcd<BUS, WR, DC> {
+    /// Create new parallel LCD interface on I2S0
+    ///
+    /// The buffer needs to be word aligned, located in internal RAM and
+    /// `descriptors` needs to hold at least [dma::descriptors_needed] entries for the buffer.
+    pub fn new(
+        mut instance: I2S0,
+        mut bus: BUS,
+        mut pins: Pins<WR, DC>,
+        buffer: &'static mut [u8],
+        descriptors: &'static mut [Descriptor],
+        config: config::Config,
+        clock_control: ClockControlConfig,
+    ) -> Result<Self, Error> {
+        dma::check_buffer(buffer.as_ptr(), buffer.len())?;
+        if buffer.len() < 4 {
+            return Err(Error::BufferTooSmall);
+        }
+        if descriptors.len() < dma::descriptors_needed(buffer.len()) {
+            return Err(Error::TooFewDescriptors);
+        }
+
+        let pll_lock = clock_control.lock_plld2();
+        let dividers = ClockDividers::calculate(
+            clock_control.pll_d2_frequency(),
+            config.frequency * BCK_DIVIDER,
+        )?;
+
+        instance.reset().enable();
+
+        bus.connect_output();
+        pins.wr
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::I2S0O_WS);
+        pins.dc.set_to_push_pull_output().set_output_high(true);
+
+        unsafe {
+            instance
+                .conf
+                .write(|w| w.bits(0).tx_right_first().set_bit());
+            instance
+                .conf2
+                .write(|w| w.bits(0).lcd_en().set_bit().camera_en().clear_bit());
+            instance.conf1.write(|w| {
+                w.bits(0)
+                    .tx_pcm_bypass()
+                    .set_bit()
+                    .rx_pcm_bypass()
+                    .set_bit()
+                    .tx_stop_en()
+                    .set_bit()
+            });
+            instance
+                .conf_chan
+                .write(|w| w.bits(0).tx_chan_mod().bits(1));
+            instance.fifo_conf.write(|w| {
+                w.bits(0)
+                    .dscr_en()
+                    .set_bit()
+                    .tx_fifo_mod_force_en()
+                    .set_bit()
+                    .tx_fifo_mod()
+                    .bits(1)
+                    .tx_data_num()
+                    .bits(32)
+            });
+            instance.sample_rate_conf.write(|w| {
+                w.bits(0)
+                    .tx_bits_mod()
+                    .bits(16)
+                    .tx_bck_div_num()
+                    .bits(BCK_DIVIDER as u8)
+            });
+            instance.lc_conf.write(|w| {
+                w.bits(0)
+                    .out_eof_mode()
+                    .set_bit()
+                    .outdscr_burst_en()
+                    .set_bit()
+                    .out_data_burst_en()
+                    .set_bit()
+            });
+            instance.int_ena.write(|w| w.bits(0));
+            instance.int_clr.write(|w| w.bits(0xffff_ffff));
+            instance.timing.write(|w| w.bits(0));
+        }
+
+        super::set_clock_dividers(&instance, dividers, false);
+        super::reset_all(&instance);
+
+        Ok(ParallelLcd {
+            instance,
+            bus,
+            pins,
+            buffer,
+            descriptors,
+            dividers,
+            clock_control,
+            _pll_lock: pll_lock,
+        })
+    }
+
+    /// The actual frequency of the WR strobe
+    pub fn frequency(&self) -> Hertz {
+        self.dividers
+            .frequency(self.clock_control.pll_d2_frequency())
+            / BCK_DIVIDER
+    }
+
+    /// Write commands (DC low) to the bus
+    pub fn write_commands(&mut self, commands: &[u8]) -> Result<(), Error> {
+        self.pins.dc.set_output_high(false);
+        self.write_samples(commands.iter().map(|&b| b as u16))
+    }
+
+    /// Write data bytes (DC high) to the bus, one byte per bus write
+    pub fn write_data(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.pins.dc.set_output_high(true);
+        self.write_samples(data.iter().map(|&b| b as u16))
+    }
+
+    /// Write 16 bit data (DC high) to the bus
+    ///
+    /// On a 16 bit bus each value takes one bus write, on an 8 bit bus the value is sent
+    /// as two writes, most significant byte first.
+    pub fn write_data_u16(&mut self, data: &[u16]) -> Result<(), Error> {
+        self.pins.dc.set_output_high(true);
+        self.write_u16_iter(data.iter().copied(), true)
+    }
+
+    /// Release the peripheral, pins and buffers
+    pub fn release(
+        mut self,
+    ) -> (
+        I2S0,
+        BUS,
+        Pins<WR, DC>,
+        &'static mut [u8],
+        &'static mut [Descriptor],
+    ) {
+        self.instance.disable();
+        (
+            self.instance,
+            self.bus,
+            self.pins,
+            self.buffer,
+            self.descriptors,
+        )
+    }
+
+    /// Write 16 bit values as bus writes, split into two bytes on an 8 bit bus
+    fn write_u16_iter<I: Iterator<Item = u16>>(
+        &mut self,
+        data: I,
+        big_endian: bool,
+    ) -> Result<(), Error> {
+        if BUS::WIDTH == 16 {
+            self.write_samples(data)
+        } else {
+            self.write_samples(data.flat_map(move |value| {
+                let bytes = if big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                };
+                core::iter::once(bytes[0] as u16).chain(core::iter::once(bytes[1] as u16))
+            }))
+        }
+    }
+
+    /// Write samples to the bus in chunks of the DMA buffer size
+    ///
+    /// The DMA sends whole 32 bit words (two samples), so an odd number of samples is padded
+    /// by repeating the last sample.
+    fn write_samples<I: Iterator<Item = u16>>(&mut self, samples: I) -> Result<(), Error> {
+        // the buffer length is a multiple of 4, so the capacity is even
+        let capacity = self.buffer.len() / 2;
+        let mut count = 0;
+        let mut last = 0;
+
+        for sample in samples {
+            self.store_sample(count, sample);
+            last = sample;
+
+            count += 1;
+            if count == capacity {
+                self.transmit(count * 2)?;
+                count = 0;
+            }
+        }
+
+        if count % 2 != 0 {
+            self.store_sample(count, last);
+            count += 1;
+        }
+        if count > 0 {
+            self.transmit(count * 2)?;
+        }
+
+        Ok(())
+    }
+
+    /// Store a sample at position `index` of the DMA buffer
+    fn store_sample(&mut self, index: usize, sample: u16) {
+        let offset = sample_offset(index);
+        let value = sample << (16 - BUS::WIDTH);
+        self.buffer[offset] = value as u8;
+        self.buffer[offset + 1] = (value >> 8) as u8;
+    }
+
+    /// Send `len` bytes of the DMA buffer and wait till the transfer is finished
+    fn transmit(&mut self, len: usize) -> Result<(), Error> {
+        let i2s = &self.instance;
+
+        i2s.conf.modify(|_, w| w.tx_start().clear_bit());
+        i2s.conf
+            .modify(|_, w| w.tx_reset().set_bit().tx_fifo_reset().set_bit());
+        i2s.conf
+            .modify(|_, w| w.tx_reset().clear_bit().tx_fifo_reset().clear_bit());
+        i2s.lc_conf.modify(|_, w| w.out_rst().set_bit());
+        i2s.lc_conf.modify(|_, w| w.out_rst().clear_bit());
+
+        let address = dma::link(self.descriptors, self.buffer.as_ptr(), len, true, false)?;
+
+        i2s.int_clr.write(|w| w.out_total_eof_int_clr().set_bit());
+        i2s.out_link
+            .modify(|_, w| unsafe { w.outlink_addr().bits(address & 0xfffff) });
+        i2s.out_link.modify(|_, w| w.outlink_start().set_bit());
+        i2s.conf.modify(|_, w| w.tx_start().set_bit());
+
+        while i2s.int_raw.read().out_total_eof_int_raw().bit_is_clear() {}
+        while i2s.state.read().tx_idle().bit_is_clear() {}
+
+        i2s.conf.modify(|_, w| w.tx_start().clear_bit());
+        i2s.int_clr.write(|w| w.out_total_eof_int_clr().set_bit());
+
+        Ok(())
+    }
+
+    /// Send data in any of the display-interface formats
+    fn send(&mut self, data: DataFormat<'_>) -> Result<(), DisplayError> {
+        match data {
+            DataFormat::U8(slice) => self.write_samples(slice.iter().map(|&b| b as u16)),
+            DataFormat::U16(slice) => {
+                self.write_u16_iter(slice.iter().copied(), cfg!(target_endian = "big"))
+            }
+            DataFormat::U16BE(slice) => self.write_u16_iter(slice.iter().copied(), true),
+            DataFormat::U16LE(slice) => self.write_u16_iter(slice.iter().copied(), false),
+            DataFormat::U8Iter(iter) => self.write_samples(iter.map(|b| b as u16)),
+            DataFormat::U16BEIter(iter) => self.write_u16_iter(iter, true),
+            DataFormat::U16LEIter(iter) => self.write_u16_iter(iter, false),
+            _ => return Err(DisplayError::DataFormatNotImplemented),
+        }
+        .map_err(|_| DisplayError::BusWriteError)
+    }
+}
+
+impl<BUS: OutputBus, WR: OutputPin, DC: OutputPin> WriteOnlyDataCommand
+    for ParallelLcd<BUS, WR, DC>
+{
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.pins.dc.set_output_high(false);
+        self.send(cmd)
+    }
+
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.pins.dc.set_output_high(true);
+        self.send(buf)
+    }
+}
+
+/// Synchronization pins used by the camera interface
+pub struct CameraPins<PCLK: InputPin, VSYNC: InputPin, HREF: InputPin> {
+    pub pclk: PCLK,
+    pub vsync: VSYNC,
+    pub href: HREF,
+}
+
+/// Parallel (DVP) camera interface
+pub struct Camera<BUS: InputBus, PCLK: InputPin, VSYNC: InputPin, HREF: InputPin> {
+    instance: I2S0,
+    bus: BUS,
+    pins: CameraPins<PCLK, VSYNC, HREF>,
+    descriptors: &'static mut [Descriptor],
+    buffer: Option<&'static mut [u8]>,
+    _pll_lock: dfs::LockPllD2,
+}
+
+impl<BUS: InputBus, PCLK: InputPin, VSYNC: InputPin, HREF: InputPin>
+    Camera<BUS, PCLK, VSYNC, HREF>
+{
+    /// Create new camera interface on I2S0
+    ///
+    /// `descriptors` needs to hold enough entries (see [dma::descriptors_needed]) for the
+    /// largest buffer passed to [Camera::start_capture].
+    pub fn new(
+        mut instance: I2S0,
+        mut bus: BUS,
+        mut pins: CameraPins<PCLK, VSYNC, HREF>,
+        descriptors: &'static mut [Descriptor],
+        clock_control: ClockControlConfig,
+    ) -> Result<Self, Error> {
+        let pll_lock = clock_control.lock_plld2();
+
+        instance.reset().enable();
+
+        bus.connect_input();
+        pins.pclk
+            .set_to_input()
+            .connect_input_to_peripheral(InputSignal::I2S0I_WS);
+        pins.vsync
+            .set_to_input()
+            .connect_input_to_peripheral(InputSignal::I2S0I_V_SYNC);
+        pins.href
+            .set_to_input()
+            .connect_input_to_peripheral(InputSignal::I2S0I_H_ENABLE);
+        gpio::connect_high_to_peripheral(InputSignal::I2S0I_H_SYNC);
+
+        unsafe {
+            instance.conf.write(|w| {
+                w.bits(0)
+                    .rx_slave_mod()
+                    .set_bit()
+                    .rx_msb_right()
+                    .clear_bit()
+            });
+            instance
+                .conf2
+                .write(|w| w.bits(0).lcd_en().set_bit().camera_en().set_bit());
+            instance
+                .conf1
+                .write(|w| w.bits(0).rx_pcm_bypass().set_bit());
+            instance
+                .conf_chan
+                .write(|w| w.bits(0).rx_chan_mod().bits(1));
+            instance.fifo_conf.write(|w| {
+                w.bits(0)
+                    .dscr_en()
+                    .set_bit()
+                    .rx_fifo_mod_force_en()
+                    .set_bit()
+                    .rx_fifo_mod()
+                    .bits(1)
+                    .rx_data_num()
+                    .bits(32)
+            });
+            instance
+                .sample_rate_conf
+                .write(|w| w.bits(0).rx_bits_mod().bits(0).rx_bck_div_num().bits(1));
+            instance
+                .lc_conf
+                .write(|w| w.bits(0).indscr_burst_en().set_bit());
+            instance.int_ena.write(|w| w.bits(0));
+            instance.int_clr.write(|w| w.bits(0xffff_ffff));
+            instance.timing.write(|w| w.bits(0));
+        }
+
+        super::set_clock_dividers(&instance, ClockDividers { num: 2, b: 0, a: 1 }, false);
+        super::reset_all(&instance);
+
+        Ok(Camera {
+            instance,
+            bus,
+            pins,
+            descriptors,
+            buffer: None,
+            _pll_lock: pll_lock,
+        })
+    }
+
+    /// Start capturing a frame into `buffer`
+    ///
+    /// Waits for the start of the next frame (falling edge of VSYNC) and then captures
+    /// `buffer.len() / 2` bytes. Use [Camera::wait_capture] to get the buffer back.
+    pub fn start_capture(&mut self, buffer: &'static mut [u8]) -> Result<(), Error> {
+        if self.buffer.is_some() {
+            return Err(Error::Busy);
+        }
+        dma::check_buffer(buffer.as_ptr(), buffer.len())?;
+
+        let address = dma::link(
+            self.descriptors,
+            buffer.as_ptr(),
+            buffer.len(),
+            false,
+            false,
+        )?;
+
+        let i2s = &self.instance;
+        i2s.conf.modify(|_, w| w.rx_start().clear_bit());
+        i2s.conf
+            .modify(|_, w| w.rx_reset().set_bit().rx_fifo_reset().set_bit());
+        i2s.conf
+            .modify(|_, w| w.rx_reset().clear_bit().rx_fifo_reset().clear_bit());
+        i2s.lc_conf.modify(|_, w| w.in_rst().set_bit());
+        i2s.lc_conf.modify(|_, w| w.in_rst().clear_bit());
+
+        i2s.rxeof_num
+            .write(|w| unsafe { w.rx_eof_num().bits((buffer.len() / 4) as u32) });
+        i2s.int_clr.write(|w| w.in_suc_eof_int_clr().set_bit());
+        i2s.in_link
+            .modify(|_, w| unsafe { w.inlink_addr().bits(address & 0xfffff) });
+        i2s.in_link.modify(|_, w| w.inlink_start().set_bit());
+
+        // wait for the start of the frame
+        while !self.pins.vsync.is_input_high() {}
+        while self.pins.vsync.is_input_high() {}
+
+        self.instance.conf.modify(|_, w| w.rx_start().set_bit());
+
+        self.buffer = Some(buffer);
+
+        Ok(())
+    }
+
+    /// Wait for the capture to finish and return the buffer
+    ///
+    /// Returns [Error::NotRunning] if no capture has been started.
+    pub fn wait_capture(&mut self) -> nb::Result<&'static mut [u8], Error> {
+        if self.buffer.is_none() {
+            return Err(nb::Error::Other(Error::NotRunning));
+        }
+
+        if self
+            .instance
+            .int_raw
+            .read()
+            .in_suc_eof_int_raw()
+            .bit_is_clear()
+        {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.instance.conf.modify(|_, w| w.rx_start().clear_bit());
+        self.instance
+            .int_clr
+            .write(|w| w.in_suc_eof_int_clr().set_bit());
+
+        Ok(self.buffer.take().unwrap())
+    }
+
+    /// Convert captured data in place into a plain byte stream
+    ///
+    /// Returns the number of valid bytes at the start of the buffer.
+    pub fn compact(buffer: &mut [u8]) -> usize {
+        let words = buffer.len() / 4;
+        for word in 0..words {
+            let first = buffer[4 * word + sample_offset(0)];
+            let second = buffer[4 * word + sample_offset(1)];
+            buffer[2 * word] = first;
+            buffer[2 * word + 1] = second;
+        }
+        words * 2
+    }
+
+    /// Release the peripheral, pins, descriptors and the buffer of a running capture
+    ///
+    /// A running capture is stopped.
+    pub fn release(
+        mut self,
+    ) -> (
+        I2S0,
+        BUS,
+        CameraPins<PCLK, VSYNC, HREF>,
+        &'static mut [Descriptor],
+        Option<&'static mut [u8]>,
+    ) {
+        super::stop_rx_dma(&self.instance);
+        self.instance.disable();
+        (
+            self.instance,
+            self.bus,
+            self.pins,
+            self.descriptors,
+            self.buffer,
+        )
+    }
+}
@@ -1,11 +1,20 @@
 use super::{
     timer::{TimerIFace, TimerSpeed},
-    HighSpeed, LowSpeed,
+    HighSpeed, LowSpeed, INTERRUPT_LOCK,
 };
 use crate::gpio::{OutputPin, OutputSignal};
+use crate::prelude::*;
+use core::cell::Cell;
 use esp32::ledc::RegisterBlock;
 use paste::paste;
 
+/// Maximum number of duty steps of a fade
+const FADE_STEPS_MAX: u32 = 0x3ff;
+
+/// Maximum duty change per step and number of PWM cycles per step of a fade
+const FADE_SCALE_MAX: u32 = 0x3ff;
+const FADE_CYCLES_MAX: u32 = 0x3ff;
+
 /// Channel errors
 #[derive(Debug)]
 pub enum Error {
@@ -15,6 +24,10 @@ pub enum Error {
     Timer,
     /// Channel not configured
     Channel,
+    /// Fade can not be done with the given duration or step parameters
+    Fade,
+    /// Invalid hpoint value
+    Hpoint,
 }
 
 /// Channel number
@@ -55,6 +68,42 @@ pub trait ChannelIFace<
 
     /// Set channel duty HW
     fn set_duty(&self, duty_pct: f32) -> Result<(), Error>;
+
+    /// Set channel duty in timer ticks (0 to [`TimerIFace::get_max_duty`])
+    fn set_duty_raw(&self, duty: u32) -> Result<(), Error>;
+
+    /// Set the hpoint (the tick at which the output goes high), which shifts the phase of the
+    /// output relative to other channels of the same timer
+    ///
+    /// *Note: [`Self::configure`] resets the hpoint to 0.*
+    fn set_hpoint(&self, hpoint: u32) -> Result<(), Error>;
+
+    /// Start a hardware fade from the current duty to `duty_pct` taking `duration`
+    fn start_fade(&self, duty_pct: f32, duration: MilliSeconds) -> Result<(), Error>;
+
+    /// Start a hardware fade from the current duty to `duty_pct`, changing the duty by `scale`
+    /// every `cycles` PWM periods
+    fn start_fade_with_step(&self, duty_pct: f32, scale: u16, cycles: u16) -> Result<(), Error>;
+
+    /// Check if the last fade has finished, true if no fade has been started
+    fn is_fade_done(&self) -> bool;
+
+    /// Wait until the last fade has finished, returns immediately if no fade is in progress
+    fn wait_for_fade(&self) {
+        while !self.is_fade_done() {}
+    }
+
+    /// Start listening for the fade end interrupt
+    fn listen(&mut self);
+
+    /// Stop listening for the fade end interrupt
+    fn unlisten(&mut self);
+
+    /// Clear the fade end interrupt
+    fn clear_interrupt(&mut self);
+
+    /// Check if the fade end interrupt is pending (only while listening)
+    fn is_interrupt_set(&self) -> bool;
 }
 
 /// Channel HW interface
@@ -62,8 +111,36 @@ pub trait ChannelHW<O: OutputPin> {
     /// Configure Channel HW except for the duty which is set via [`Self::set_duty_hw`].
     fn configure_hw(&mut self) -> Result<(), Error>;
 
+    /// Connect the channel to a timer and route the output pin in HW
+    fn connect_hw(&mut self, timer_number: u8);
+
     /// Set channel duty HW
     fn set_duty_hw(&self, duty: u32);
+
+    /// Set the hpoint in HW
+    fn set_hpoint_hw(&self, hpoint: u32);
+
+    /// Enable/disable the output in HW
+    fn enable_output_hw(&self, enable: bool);
+
+    /// Return the current duty from HW
+    fn get_duty_hw(&self) -> u32;
+
+    /// Start a fade in HW: starting at `start_duty` the duty is changed `steps` times by `scale`
+    /// every `cycles` PWM periods
+    fn start_fade_hw(&self, start_duty: u32, increase: bool, steps: u32, cycles: u32, scale: u32);
+
+    /// Check if the fade has finished in HW
+    fn is_fade_done_hw(&self) -> bool;
+
+    /// Enable/disable the fade end interrupt in HW
+    fn enable_interrupt_hw(&self, enable: bool);
+
+    /// Clear the fade end interrupt in HW
+    fn clear_interrupt_hw(&self);
+
+    /// Check if the fade end interrupt is pending in HW
+    fn is_interrupt_set_hw(&self) -> bool;
 }
 
 /// Channel struct
@@ -72,6 +149,8 @@ pub struct Channel<'a, S: TimerSpeed, O: OutputPin> {
     timer: Option<&'a dyn TimerIFace<S>>,
     number: Number,
     output_pin: O,
+    /// A fade has been started and its end has not been seen yet
+    fading: Cell<bool>,
 }
 
 impl<'a, S: TimerSpeed, O: OutputPin> Channel<'a, S, O> {
@@ -83,8 +162,14 @@ impl<'a, S: TimerSpeed, O: OutputPin> Channel<'a, S, O> {
             timer: None,
             number,
             output_pin,
+            fading: Cell::new(false),
         }
     }
+
+    /// Return the output pin, used by [`super::pwm::Pwm`] to release its channels
+    pub(super) fn into_pin(self) -> O {
+        self.output_pin
+    }
 }
 
 impl<'a, S: TimerSpeed, O: OutputPin> ChannelIFace<'a, S, O> for Channel<'a, S, O>
@@ -103,18 +188,7 @@ where
 
     /// Set duty % of channel
     fn set_duty(&self, duty_pct: f32) -> Result<(), Error> {
-        let duty_exp;
-        if let Some(timer) = self.timer {
-            if let Some(timer_duty) = timer.get_duty() {
-                duty_exp = timer_duty as u32;
-            } else {
-                return Err(Error::Timer);
-            }
-        } else {
-            return Err(Error::Channel);
-        }
-
-        let duty_range = 2_u32.pow(duty_exp);
+        let duty_range = self.get_duty_range()?;
         let duty_value = (duty_range as f32 * duty_pct) as u32;
 
         if duty_value == 0 || duty_pct > 1.0 {
@@ -126,11 +200,182 @@ where
 
         Ok(())
     }
+
+    /// Set channel duty in timer ticks (0 to [`TimerIFace::get_max_duty`])
+    fn set_duty_raw(&self, duty: u32) -> Result<(), Error> {
+        if duty > self.get_duty_range()? {
+            return Err(Error::Duty);
+        }
+
+        self.set_duty_hw(duty);
+
+        Ok(())
+    }
+
+    /// Set the hpoint (the tick at which the output goes high)
+    fn set_hpoint(&self, hpoint: u32) -> Result<(), Error> {
+        if hpoint >= self.get_duty_range()? {
+            return Err(Error::Hpoint);
+        }
+
+        self.set_hpoint_hw(hpoint);
+
+        Ok(())
+    }
+
+    /// Start a hardware fade from the current duty to `duty_pct` taking `duration`
+    ///
+    /// The duty change per step and the number of PWM cycles per step are calculated from the
+    /// duty resolution and frequency of the timer.
+    fn start_fade(&self, duty_pct: f32, duration: MilliSeconds) -> Result<(), Error> {
+        let target = self.get_fade_target(duty_pct)?;
+        let frequency: u32 = self
+            .timer
+            .and_then(|timer| timer.get_pwm_freq())
+            .ok_or(Error::Timer)?
+            .into();
+
+        let current = self.get_duty_hw();
+        let delta = if target > current {
+            target - current
+        } else {
+            current - target
+        };
+
+        if delta == 0 {
+            self.set_duty_hw(target);
+            return Ok(());
+        }
+
+        let duration: u32 = duration.into();
+        let total_cycles = core::cmp::max(1, (frequency as u64 * duration as u64 / 1000) as u32);
+
+        // duty change per step, limited by the maximum number of steps
+        let scale = core::cmp::max(
+            core::cmp::max(1, delta / total_cycles),
+            (delta + FADE_STEPS_MAX - 1) / FADE_STEPS_MAX,
+        );
+        if scale > FADE_SCALE_MAX {
+            return Err(Error::Fade);
+        }
+
+        let steps = delta / scale;
+        let cycles = core::cmp::max(1, total_cycles / steps);
+        if cycles > FADE_CYCLES_MAX {
+            return Err(Error::Fade);
+        }
+
+        self.start_fade_to(current, target, steps, cycles, scale);
+
+        Ok(())
+    }
+
+    /// Start a hardware fade from the current duty to `duty_pct`, changing the duty by `scale`
+    /// every `cycles` PWM periods
+    ///
+    /// `scale` and `cycles` must be in the range 1 to 1023.
+    fn start_fade_with_step(&self, duty_pct: f32, scale: u16, cycles: u16) -> Result<(), Error> {
+        let target = self.get_fade_target(duty_pct)?;
+        let scale = scale as u32;
+        let cycles = cycles as u32;
+
+        if scale == 0 || scale > FADE_SCALE_MAX || cycles == 0 || cycles > FADE_CYCLES_MAX {
+            return Err(Error::Fade);
+        }
+
+        let current = self.get_duty_hw();
+        let delta = if target > current {
+            target - current
+        } else {
+            current - target
+        };
+
+        let steps = delta / scale;
+        if steps > FADE_STEPS_MAX {
+            return Err(Error::Fade);
+        }
+
+        if steps == 0 {
+            self.set_duty_hw(target);
+            return Ok(());
+        }
+
+        self.start_fade_to(current, target, steps, cycles, scale);
+
+        Ok(())
+    }
+
+    /// Check if the last fade has finished, true if no fade has been started
+    fn is_fade_done(&self) -> bool {
+        if self.fading.get() && self.is_fade_done_hw() {
+            self.fading.set(false);
+        }
+
+        !self.fading.get()
+    }
+
+    /// Start listening for the fade end interrupt
+    fn listen(&mut self) {
+        self.enable_interrupt_hw(true);
+    }
+
+    /// Stop listening for the fade end interrupt
+    fn unlisten(&mut self) {
+        self.enable_interrupt_hw(false);
+    }
+
+    /// Clear the fade end interrupt
+    fn clear_interrupt(&mut self) {
+        // remember the end of the fade, which is no longer visible after clearing
+        self.is_fade_done();
+        self.clear_interrupt_hw();
+    }
+
+    /// Check if the fade end interrupt is pending (only while listening)
+    fn is_interrupt_set(&self) -> bool {
+        self.is_interrupt_set_hw()
+    }
+}
+
+impl<'a, S: TimerSpeed, O: OutputPin> Channel<'a, S, O>
+where
+    Channel<'a, S, O>: ChannelHW<O>,
+{
+    /// Return the duty value corresponding to 100%
+    fn get_duty_range(&self) -> Result<u32, Error> {
+        let timer = self.timer.ok_or(Error::Channel)?;
+
+        timer.get_max_duty().ok_or(Error::Timer)
+    }
+
+    /// Return the duty value of a fade target, which unlike [`ChannelIFace::set_duty`] can be 0
+    fn get_fade_target(&self, duty_pct: f32) -> Result<u32, Error> {
+        if !(0.0..=1.0).contains(&duty_pct) {
+            return Err(Error::Duty);
+        }
+
+        Ok((self.get_duty_range()? as f32 * duty_pct) as u32)
+    }
+
+    /// Start a fade ending exactly at `target`
+    ///
+    /// The start is adjusted by the remainder of the duty difference divided by `scale`.
+    fn start_fade_to(&self, current: u32, target: u32, steps: u32, cycles: u32, scale: u32) {
+        let increase = target > current;
+        let start = if increase {
+            target - steps * scale
+        } else {
+            target + steps * scale
+        };
+
+        self.fading.set(true);
+        self.start_fade_hw(start, increase, steps, cycles, scale);
+    }
 }
 
 /// Macro to configure channel parameters in hw
 macro_rules! set_channel {
-    ( $self: ident, $speed: ident, $num: literal, $channel_number: ident ) => {
+    ( $self: ident, $speed: ident, $num: literal, $timer_number: ident ) => {
         paste! {
             $self.ledc.[<$speed sch $num _hpoint>]
                 .write(|w| unsafe { w.[<hpoint_ $speed sch $num>]().bits(0x0) });
@@ -138,7 +383,7 @@ macro_rules! set_channel {
                 w.[<sig_out_en_ $speed sch $num>]()
                     .set_bit()
                     .[<timer_sel_ $speed sch $num>]()
-                    .bits($channel_number)
+                    .bits($timer_number)
             });
             $self.ledc.[<$speed sch $num _conf1>].write(|w| unsafe {
                 w.[<duty_start_ $speed sch $num>]()
@@ -162,7 +407,86 @@ macro_rules! set_duty {
         paste! {
             $self.ledc
                 .[<$speed sch $num _duty>]
-                .write(|w| unsafe { w.[<duty_ $speed sch $num>]().bits($duty << 4) })
+                .write(|w| unsafe { w.[<duty_ $speed sch $num>]().bits($duty << 4) });
+            // Reset the parameters of a previous fade
+            $self.ledc.[<$speed sch $num _conf1>].write(|w| unsafe {
+                w.[<duty_start_ $speed sch $num>]()
+                    .set_bit()
+                    .[<duty_inc_ $speed sch $num>]()
+                    .set_bit()
+                    .[<duty_num_ $speed sch $num>]()
+                    .bits(0x1)
+                    .[<duty_cycle_ $speed sch $num>]()
+                    .bits(0x1)
+                    .[<duty_scale_ $speed sch $num>]()
+                    .bits(0x0)
+                });
+        }
+    };
+}
+
+/// Macro to read the current duty from hw
+macro_rules! get_duty {
+    ( $self: ident, $speed: ident, $num: literal ) => {
+        paste! {
+            $self.ledc
+                .[<$speed sch $num _duty_r>]
+                .read()
+                .[<duty_ $speed sch $num _r>]()
+                .bits() >> 4
+        }
+    };
+}
+
+/// Macro to start a fade in hw
+macro_rules! start_fade {
+    ( $self: ident, $speed: ident, $num: literal, $start: ident, $increase: ident, $steps: ident,
+      $cycles: ident, $scale: ident ) => {
+        paste! {
+            $self.ledc
+                .int_clr
+                .write(|w| w.[<duty_chng_end_ $speed sch $num _int_clr>]().set_bit());
+            $self.ledc
+                .[<$speed sch $num _duty>]
+                .write(|w| unsafe { w.[<duty_ $speed sch $num>]().bits($start << 4) });
+            $self.ledc.[<$speed sch $num _conf1>].write(|w| unsafe {
+                w.[<duty_start_ $speed sch $num>]()
+                    .set_bit()
+                    .[<duty_inc_ $speed sch $num>]()
+                    .bit($increase)
+                    .[<duty_num_ $speed sch $num>]()
+                    .bits($steps as u16)
+                    .[<duty_cycle_ $speed sch $num>]()
+                    .bits($cycles as u16)
+                    .[<duty_scale_ $speed sch $num>]()
+                    .bits($scale as u16)
+                });
+        }
+    };
+}
+
+/// Macro to check if the fade has finished in hw
+macro_rules! is_fade_done {
+    ( $self: ident, $speed: ident, $num: literal ) => {
+        paste! {
+            $self.ledc
+                .int_raw
+                .read()
+                .[<duty_chng_end_ $speed sch $num _int_raw>]()
+                .bit_is_set()
+        }
+    };
+}
+
+/// Macro to check if the fade end interrupt is pending in hw
+macro_rules! is_interrupt_set {
+    ( $self: ident, $speed: ident, $num: literal ) => {
+        paste! {
+            $self.ledc
+                .int_st
+                .read()
+                .[<duty_chng_end_ $speed sch $num _int_st>]()
+                .bit_is_set()
         }
     };
 }
@@ -178,65 +502,112 @@ macro_rules! update_channel {
     };
 }
 
+/// Macro to set the hpoint in hw
+macro_rules! set_hpoint {
+    ( $self: ident, $speed: ident, $num: literal, $hpoint: ident ) => {
+        paste! {
+            $self.ledc
+                .[<$speed sch $num _hpoint>]
+                .write(|w| unsafe { w.[<hpoint_ $speed sch $num>]().bits($hpoint) })
+        }
+    };
+}
+
+/// Macro to enable/disable the output in hw
+macro_rules! enable_output {
+    ( $self: ident, $speed: ident, $num: literal, $enable: ident ) => {
+        paste! {
+            $self.ledc
+                .[<$speed sch $num _conf0>]
+                .modify(|_, w| w.[<sig_out_en_ $speed sch $num>]().bit($enable))
+        }
+    };
+}
+
+/// Macro to enable/disable the fade end interrupt in hw
+macro_rules! enable_interrupt {
+    ( $self: ident, $speed: ident, $num: literal, $enable: ident ) => {
+        paste! {
+            // shared register without set/clear functionality, so needs lock
+            (&INTERRUPT_LOCK).lock(|_| {
+                $self.ledc
+                    .int_ena
+                    .modify(|_, w| w.[<duty_chng_end_ $speed sch $num _int_ena>]().bit($enable))
+            })
+        }
+    };
+}
+
+/// Macro to clear the fade end interrupt in hw
+macro_rules! clear_interrupt {
+    ( $self: ident, $speed: ident, $num: literal ) => {
+        paste! {
+            $self.ledc
+                .int_clr
+                .write(|w| w.[<duty_chng_end_ $speed sch $num _int_clr>]().set_bit())
+        }
+    };
+}
+
 /// Channel HW interface for HighSpeed channels
 impl<'a, O: OutputPin> ChannelHW<O> for Channel<'a, HighSpeed, O> {
     /// Configure Channel HW except for the duty which is set via [`Self::set_duty_hw`].
     fn configure_hw(&mut self) -> Result<(), Error> {
-        if let Some(timer) = self.timer {
-            if !timer.is_configured() {
-                return Err(Error::Timer);
-            }
-
-            self.output_pin.set_to_push_pull_output();
-
-            let channel_number = timer.get_number() as u8;
-            match self.number {
-                Number::Channel0 => {
-                    set_channel!(self, h, 0, channel_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_0);
-                }
-                Number::Channel1 => {
-                    set_channel!(self, h, 1, channel_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_1);
-                }
-                Number::Channel2 => {
-                    set_channel!(self, h, 2, channel_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_2);
-                }
-                Number::Channel3 => {
-                    set_channel!(self, h, 3, channel_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_3);
-                }
-                Number::Channel4 => {
-                    set_channel!(self, h, 4, channel_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_4);
-                }
-                Number::Channel5 => {
-                    set_channel!(self, h, 5, channel_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_5);
-                }
-                Number::Channel6 => {
-                    set_channel!(self, h, 6, channel_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_6);
-                }
-                Number::Channel7 => {
-                    set_channel!(self, h, 7, channel_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_7);
-                }
+        match self.timer {
+            Some(timer) if timer.is_configured() => {
+                self.connect_hw(timer.get_number() as u8);
+                Ok(())
             }
-        } else {
-            return Err(Error::Timer);
+            _ => Err(Error::Timer),
         }
+    }
 
-        Ok(())
+    /// Connect the channel to a timer and route the output pin in HW
+    fn connect_hw(&mut self, timer_number: u8) {
+        self.output_pin.set_to_push_pull_output();
+
+        match self.number {
+            Number::Channel0 => {
+                set_channel!(self, h, 0, timer_number);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_0);
+            }
+            Number::Channel1 => {
+                set_channel!(self, h, 1, timer_number);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_1);
+            }
+            Number::Channel2 => {
+                set_channel!(self, h, 2, timer_number);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_2);
+            }
+            Number::Channel3 => {
+                set_channel!(self, h, 3, timer_number);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_3);
+            }
+            Number::Channel4 => {
+                set_channel!(self, h, 4, timer_number);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_4);
+            }
+            Number::Channel5 => {
+                set_channel!(self, h, 5, timer_number);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_5);
+            }
+            Number::Channel6 => {
+                set_channel!(self, h, 6, timer_number);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_6);
+            }
+            Number::Channel7 => {
+                set_channel!(self, h, 7, timer_number);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG_7);
+            }
+        }
     }
 
     /// Set duty in channel HW
@@ -252,88 +623,439 @@ impl<'a, O: OutputPin> ChannelHW<O> for Channel<'a, HighSpeed, O> {
             Number::Channel7 => set_duty!(self, h, 7, duty),
         };
     }
+
+    /// Return the current duty from HW
+    fn get_duty_hw(&self) -> u32 {
+        match self.number {
+            Number::Channel0 => get_duty!(self, h, 0),
+            Number::Channel1 => get_duty!(self, h, 1),
+            Number::Channel2 => get_duty!(self, h, 2),
+            Number::Channel3 => get_duty!(self, h, 3),
+            Number::Channel4 => get_duty!(self, h, 4),
+            Number::Channel5 => get_duty!(self, h, 5),
+            Number::Channel6 => get_duty!(self, h, 6),
+            Number::Channel7 => get_duty!(self, h, 7),
+        }
+    }
+
+    /// Start a fade in HW
+    fn start_fade_hw(&self, start: u32, increase: bool, steps: u32, cycles: u32, scale: u32) {
+        match self.number {
+            Number::Channel0 => start_fade!(self, h, 0, start, increase, steps, cycles, scale),
+            Number::Channel1 => start_fade!(self, h, 1, start, increase, steps, cycles, scale),
+            Number::Channel2 => start_fade!(self, h, 2, start, increase, steps, cycles, scale),
+            Number::Channel3 => start_fade!(self, h, 3, start, increase, steps, cycles, scale),
+            Number::Channel4 => start_fade!(self, h, 4, start, increase, steps, cycles, scale),
+            Number::Channel5 => start_fade!(self, h, 5, start, increase, steps, cycles, scale),
+            Number::Channel6 => start_fade!(self, h, 6, start, increase, steps, cycles, scale),
+            Number::Channel7 => start_fade!(self, h, 7, start, increase, steps, cycles, scale),
+        };
+    }
+
+    /// Check if the fade has finished in HW
+    fn is_fade_done_hw(&self) -> bool {
+        match self.number {
+            Number::Channel0 => is_fade_done!(self, h, 0),
+            Number::Channel1 => is_fade_done!(self, h, 1),
+            Number::Channel2 => is_fade_done!(self, h, 2),
+            Number::Channel3 => is_fade_done!(self, h, 3),
+            Number::Channel4 => is_fade_done!(self, h, 4),
+            Number::Channel5 => is_fade_done!(self, h, 5),
+            Number::Channel6 => is_fade_done!(self, h, 6),
+            Number::Channel7 => is_fade_done!(self, h, 7),
+        }
+    }
+
+    /// Set the hpoint in HW
+    fn set_hpoint_hw(&self, hpoint: u32) {
+        match self.number {
+            Number::Channel0 => set_hpoint!(self, h, 0, hpoint),
+            Number::Channel1 => set_hpoint!(self, h, 1, hpoint),
+            Number::Channel2 => set_hpoint!(self, h, 2, hpoint),
+            Number::Channel3 => set_hpoint!(self, h, 3, hpoint),
+            Number::Channel4 => set_hpoint!(self, h, 4, hpoint),
+            Number::Channel5 => set_hpoint!(self, h, 5, hpoint),
+            Number::Channel6 => set_hpoint!(self, h, 6, hpoint),
+            Number::Channel7 => set_hpoint!(self, h, 7, hpoint),
+        };
+    }
+
+    /// Enable/disable the output in HW
+    fn enable_output_hw(&self, enable: bool) {
+        match self.number {
+            Number::Channel0 => enable_output!(self, h, 0, enable),
+            Number::Channel1 => enable_output!(self, h, 1, enable),
+            Number::Channel2 => enable_output!(self, h, 2, enable),
+            Number::Channel3 => enable_output!(self, h, 3, enable),
+            Number::Channel4 => enable_output!(self, h, 4, enable),
+            Number::Channel5 => enable_output!(self, h, 5, enable),
+            Number::Channel6 => enable_output!(self, h, 6, enable),
+            Number::Channel7 => enable_output!(self, h, 7, enable),
+        };
+    }
+
+    /// Enable/disable the fade end interrupt in HW
+    fn enable_interrupt_hw(&self, enable: bool) {
+        match self.number {
+            Number::Channel0 => enable_interrupt!(self, h, 0, enable),
+            Number::Channel1 => enable_interrupt!(self, h, 1, enable),
+            Number::Channel2 => enable_interrupt!(self, h, 2, enable),
+            Number::Channel3 => enable_interrupt!(self, h, 3, enable),
+            Number::Channel4 => enable_interrupt!(self, h, 4, enable),
+            Number::Channel5 => enable_interrupt!(self, h, 5, enable),
+            Number::Channel6 => enable_interrupt!(self, h, 6, enable),
+            Number::Channel7 => enable_interrupt!(self, h, 7, enable),
+        };
+    }
+
+    /// Clear the fade end interrupt in HW
+    fn clear_interrupt_hw(&self) {
+        match self.number {
+            Number::Channel0 => clear_interrupt!(self, h, 0),
+            Number::Channel1 => clear_interrupt!(self, h, 1),
+            Number::Channel2 => clear_interrupt!(self, h, 2),
+            Number::Channel3 => clear_interrupt!(self, h, 3),
+            Number::Channel4 => clear_interrupt!(self, h, 4),
+            Number::Channel5 => clear_interrupt!(self, h, 5),
+            Number::Channel6 => clear_interrupt!(self, h, 6),
+            Number::Channel7 => clear_interrupt!(self, h, 7),
+        };
+    }
+
+    /// Check if the fade end interrupt is pending in HW
+    fn is_interrupt_set_hw(&self) -> bool {
+        match self.number {
+            Number::Channel0 => is_interrupt_set!(self, h, 0),
+            Number::Channel1 => is_interrupt_set!(self, h, 1),
+            Number::Channel2 => is_interrupt_set!(self, h, 2),
+            Number::Channel3 => is_interrupt_set!(self, h, 3),
+            Number::Channel4 => is_interrupt_set!(self, h, 4),
+            Number::Channel5 => is_interrupt_set!(self, h, 5),
+            Number::Channel6 => is_interrupt_set!(self, h, 6),
+            Number::Channel7 => is_interrupt_set!(self, h, 7),
+        }
+    }
 }
 
 /// Channel HW interface for LowSpeed channels
 impl<'a, O: OutputPin> ChannelHW<O> for Channel<'a, LowSpeed, O> {
-    /// Configure Channel HW
+    /// Configure Channel HW except for the duty which is set via [`Self::set_duty_hw`].
     fn configure_hw(&mut self) -> Result<(), Error> {
-        if let Some(timer) = self.timer {
-            if !timer.is_configured() {
-                return Err(Error::Timer);
-            }
-
-            self.output_pin.set_to_push_pull_output();
-
-            let channel_number = timer.get_number() as u8;
-            match self.number {
-                Number::Channel0 => {
-                    set_channel!(self, l, 0, channel_number);
-                    update_channel!(self, 0);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_0);
-                }
-                Number::Channel1 => {
-                    set_channel!(self, l, 1, channel_number);
-                    update_channel!(self, 1);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_1);
-                }
-                Number::Channel2 => {
-                    set_channel!(self, l, 2, channel_number);
-                    update_channel!(self, 2);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_2);
-                }
-                Number::Channel3 => {
-                    set_channel!(self, l, 3, channel_number);
-                    update_channel!(self, 3);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_3);
-                }
-                Number::Channel4 => {
-                    set_channel!(self, l, 4, channel_number);
-                    update_channel!(self, 4);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_4);
-                }
-                Number::Channel5 => {
-                    set_channel!(self, l, 5, channel_number);
-                    update_channel!(self, 5);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_5);
-                }
-                Number::Channel6 => {
-                    set_channel!(self, l, 6, channel_number);
-                    update_channel!(self, 6);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_6);
-                }
-                Number::Channel7 => {
-                    set_channel!(self, l, 7, channel_number);
-                    update_channel!(self, 7);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_7);
-                }
+        match self.timer {
+            Some(timer) if timer.is_configured() => {
+                self.connect_hw(timer.get_number() as u8);
+                Ok(())
             }
-        } else {
-            return Err(Error::Timer);
+            _ => Err(Error::Timer),
         }
+    }
 
-        Ok(())
+    /// Connect the channel to a timer and route the output pin in HW
+    fn connect_hw(&mut self, timer_number: u8) {
+        self.output_pin.set_to_push_pull_output();
+
+        match self.number {
+            Number::Channel0 => {
+                set_channel!(self, l, 0, timer_number);
+                update_channel!(self, 0);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_0);
+            }
+            Number::Channel1 => {
+                set_channel!(self, l, 1, timer_number);
+                update_channel!(self, 1);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_1);
+            }
+            Number::Channel2 => {
+                set_channel!(self, l, 2, timer_number);
+                update_channel!(self, 2);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_2);
+            }
+            Number::Channel3 => {
+                set_channel!(self, l, 3, timer_number);
+                update_channel!(self, 3);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_3);
+            }
+            Number::Channel4 => {
+                set_channel!(self, l, 4, timer_number);
+                update_channel!(self, 4);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_4);
+            }
+            Number::Channel5 => {
+                set_channel!(self, l, 5, timer_number);
+                update_channel!(self, 5);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_5);
+            }
+            Number::Channel6 => {
+                set_channel!(self, l, 6, timer_number);
+                update_channel!(self, 6);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_6);
+            }
+            Number::Channel7 => {
+                set_channel!(self, l, 7, timer_number);
+                update_channel!(self, 7);
+                self.output_pin
+                    .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG_7);
+            }
+        }
     }
 
     /// Set duty in channel HW
     fn set_duty_hw(&self, duty: u32) {
         match self.number {
-            Number::Channel0 => set_duty!(self, l, 0, duty),
-            Number::Channel1 => set_duty!(self, l, 1, duty),
-            Number::Channel2 => set_duty!(self, l, 2, duty),
-            Number::Channel3 => set_duty!(self, l, 3, duty),
-            Number::Channel4 => set_duty!(self, l, 4, duty),
-            Number::Channel5 => set_duty!(self, l, 5, duty),
-            Number::Channel6 => set_duty!(self, l, 6, duty),
-            Number::Channel7 => set_duty!(self, l, 7, duty),
+            Number::Channel0 => {
+                set_duty!(self, l, 0, duty);
+                update_channel!(self, 0);
+            }
+            Number::Channel1 => {
+                set_duty!(self, l, 1, duty);
+                update_channel!(self, 1);
+            }
+            Number::Channel2 => {
+                set_duty!(self, l, 2, duty);
+                update_channel!(self, 2);
+            }
+            Number::Channel3 => {
+                set_duty!(self, l, 3, duty);
+                update_channel!(self, 3);
+            }
+            Number::Channel4 => {
+                set_duty!(self, l, 4, duty);
+                update_channel!(self, 4);
+            }
+            Number::Channel5 => {
+                set_duty!(self, l, 5, duty);
+                update_channel!(self, 5);
+            }
+            Number::Channel6 => {
+                set_duty!(self, l, 6, duty);
+                update_channel!(self, 6);
+            }
+            Number::Channel7 => {
+                set_duty!(self, l, 7, duty);
+                update_channel!(self, 7);
+            }
+        };
+    }
+
+    /// Return the current duty from HW
+    fn get_duty_hw(&self) -> u32 {
+        match self.number {
+            Number::Channel0 => get_duty!(self, l, 0),
+            Number::Channel1 => get_duty!(self, l, 1),
+            Number::Channel2 => get_duty!(self, l, 2),
+            Number::Channel3 => get_duty!(self, l, 3),
+            Number::Channel4 => get_duty!(self, l, 4),
+            Number::Channel5 => get_duty!(self, l, 5),
+            Number::Channel6 => get_duty!(self, l, 6),
+            Number::Channel7 => get_duty!(self, l, 7),
+        }
+    }
+
+    /// Start a fade in HW
+    fn start_fade_hw(&self, start: u32, increase: bool, steps: u32, cycles: u32, scale: u32) {
+        match self.number {
+            Number::Channel0 => {
+                start_fade!(self, l, 0, start, increase, steps, cycles, scale);
+                update_channel!(self, 0);
+            }
+            Number::Channel1 => {
+                start_fade!(self, l, 1, start, increase, steps, cycles, scale);
+                update_channel!(self, 1);
+            }
+            Number::Channel2 => {
+                start_fade!(self, l, 2, start, increase, steps, cycles, scale);
+                update_channel!(self, 2);
+            }
+            Number::Channel3 => {
+                start_fade!(self, l, 3, start, increase, steps, cycles, scale);
+                update_channel!(self, 3);
+            }
+            Number::Channel4 => {
+                start_fade!(self, l, 4, start, increase, steps, cycles, scale);
+                update_channel!(self, 4);
+            }
+            Number::Channel5 => {
+                start_fade!(self, l, 5, start, increase, steps, cycles, scale);
+                update_channel!(self, 5);
+            }
+            Number::Channel6 => {
+                start_fade!(self, l, 6, start, increase, steps, cycles, scale);
+                update_channel!(self, 6);
+            }
+            Number::Channel7 => {
+                start_fade!(self, l, 7, start, increase, steps, cycles, scale);
+                update_channel!(self, 7);
+            }
         };
     }
+
+    /// Check if the fade has finished in HW
+    fn is_fade_done_hw(&self) -> bool {
+        match self.number {
+            Number::Channel0 => is_fade_done!(self, l, 0),
+            Number::Channel1 => is_fade_done!(self, l, 1),
+            Number::Channel2 => is_fade_done!(self, l, 2),
+            Number::Channel3 => is_fade_done!(self, l, 3),
+            Number::Channel4 => is_fade_done!(self, l, 4),
+            Number::Channel5 => is_fade_done!(self, l, 5),
+            Number::Channel6 => is_fade_done!(self, l, 6),
+            Number::Channel7 => is_fade_done!(self, l, 7),
+        }
+    }
+
+    /// Set the hpoint in HW
+    fn set_hpoint_hw(&self, hpoint: u32) {
+        match self.number {
+            Number::Channel0 => {
+                set_hpoint!(self, l, 0, hpoint);
+                update_channel!(self, 0);
+            }
+            Number::Channel1 => {
+                set_hpoint!(self, l, 1, hpoint);
+                update_channel!(self, 1);
+            }
+            Number::Channel2 => {
+                set_hpoint!(self, l, 2, hpoint);
+                update_channel!(self, 2);
+            }
+            Number::Channel3 => {
+                set_hpoint!(self, l, 3, hpoint);
+                update_channel!(self, 3);
+            }
+            Number::Channel4 => {
+                set_hpoint!(self, l, 4, hpoint);
+                update_channel!(self, 4);
+            }
+            Number::Channel5 => {
+                set_hpoint!(self, l, 5, hpoint);
+                update_channel!(self, 5);
+            }
+            Number::Channel6 => {
+                set_hpoint!(self, l, 6, hpoint);
+                update_channel!(self, 6);
+            }
+            Number::Channel7 => {
+                set_hpoint!(self, l, 7, hpoint);
+                update_channel!(self, 7);
+            }
+        };
+    }
+
+    /// Enable/disable the output in HW
+    fn enable_output_hw(&self, enable: bool) {
+        match self.number {
+            Number::Channel0 => {
+                enable_output!(self, l, 0, enable);
+                update_channel!(self, 0);
+            }
+            Number::Channel1 => {
+                enable_output!(self, l, 1, enable);
+                update_channel!(self, 1);
+            }
+            Number::Channel2 => {
+                enable_output!(self, l, 2, enable);
+                update_channel!(self, 2);
+            }
+            Number::Channel3 => {
+                enable_output!(self, l, 3, enable);
+                update_channel!(self, 3);
+            }
+            Number::Channel4 => {
+                enable_output!(self, l, 4, enable);
+                update_channel!(self, 4);
+            }
+            Number::Channel5 => {
+                enable_output!(self, l, 5, enable);
+                update_channel!(self, 5);
+            }
+            Number::Channel6 => {
+                enable_output!(self, l, 6, enable);
+                update_channel!(self, 6);
+            }
+            Number::Channel7 => {
+                enable_output!(self, l, 7, enable);
+                update_channel!(self, 7);
+            }
+        };
+    }
+
+    /// Enable/disable the fade end interrupt in HW
+    fn enable_interrupt_hw(&self, enable: bool) {
+        match self.number {
+            Number::Channel0 => enable_interrupt!(self, l, 0, enable),
+            Number::Channel1 => enable_interrupt!(self, l, 1, enable),
+            Number::Channel2 => enable_interrupt!(self, l, 2, enable),
+            Number::Channel3 => enable_interrupt!(self, l, 3, enable),
+            Number::Channel4 => enable_interrupt!(self, l, 4, enable),
+            Number::Channel5 => enable_interrupt!(self, l, 5, enable),
+            Number::Channel6 => enable_interrupt!(self, l, 6, enable),
+            Number::Channel7 => enable_interrupt!(self, l, 7, enable),
+        };
+    }
+
+    /// Clear the fade end interrupt in HW
+    fn clear_interrupt_hw(&self) {
+        match self.number {
+            Number::Channel0 => clear_interrupt!(self, l, 0),
+            Number::Channel1 => clear_interrupt!(self, l, 1),
+            Number::Channel2 => clear_interrupt!(self, l, 2),
+            Number::Channel3 => clear_interrupt!(self, l, 3),
+            Number::Channel4 => clear_interrupt!(self, l, 4),
+            Number::Channel5 => clear_interrupt!(self, l, 5),
+            Number::Channel6 => clear_interrupt!(self, l, 6),
+            Number::Channel7 => clear_interrupt!(self, l, 7),
+        };
+    }
+
+    /// Check if the fade end interrupt is pending in HW
+    fn is_interrupt_set_hw(&self) -> bool {
+        match self.number {
+            Number::Channel0 => is_interrupt_set!(self, l, 0),
+            Number::Channel1 => is_interrupt_set!(self, l, 1),
+            Number::Channel2 => is_interrupt_set!(self, l, 2),
+            Number::Channel3 => is_interrupt_set!(self, l, 3),
+            Number::Channel4 => is_interrupt_set!(self, l, 4),
+            Number::Channel5 => is_interrupt_set!(self, l, 5),
+            Number::Channel6 => is_interrupt_set!(self, l, 6),
+            Number::Channel7 => is_interrupt_set!(self, l, 7),
+        }
+    }
+}
+
+/// embedded-hal [`PwmPin`](embedded_hal::PwmPin) implementation
+///
+/// The duty is in timer ticks (0 to [`TimerIFace::get_max_duty`]), larger values are clamped.
+impl<'a, S: TimerSpeed, O: OutputPin> embedded_hal::PwmPin for Channel<'a, S, O>
+where
+    Channel<'a, S, O>: ChannelHW<O>,
+{
+    type Duty = u32;
+
+    fn disable(&mut self) {
+        self.enable_output_hw(false);
+    }
+
+    fn enable(&mut self) {
+        self.enable_output_hw(true);
+    }
+
+    fn get_duty(&self) -> Self::Duty {
+        self.get_duty_hw()
+    }
+
+    fn get_max_duty(&self) -> Self::Duty {
+        self.get_duty_range().unwrap_or(0)
+    }
+
+    fn set_duty(&mut self, duty: Self::Duty) {
+        let duty = core::cmp::min(duty, self.get_duty_range().unwrap_or(0));
+        self.set_duty_hw(duty);
+    }
 }
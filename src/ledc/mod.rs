@@ -1,7 +1,7 @@
 //! LEDC (LED PWM Controller) peripheral control
 //!
-//! Currently only supports fixed frequency output. Hardware fade support and interrupts are not currently
-//! implemented. High Speed and Low Speed channels are available.
+//! Supports fixed frequency output with hardware fades of the duty and interrupts. High Speed and
+//! Low Speed channels are available.
 //!
 //! # Example:
 //! The following will configure the Low Speed channel 0 to 24Mhz output with 50% duty using the ABP Clock
@@ -26,23 +26,75 @@
 //!         })
 //!     .unwrap();
 //! ```
-//! # TODO
-//! - Hardware fade support
-//! - Interrupts
+//!
+//! The duty can be faded by the hardware, e.g. for LED breathing effects:
+//! ```
+//!     channel0.start_fade(1.0, 1000.ms()).unwrap();
+//!     channel0.wait_for_fade();
+//!     channel0.start_fade(0.0, 1000.ms()).unwrap();
+//! ```
+//!
+//! The duty can also be set in timer ticks with [`channel::ChannelIFace::set_duty_raw`] and the
+//! output of channels sharing a timer can be staggered with
+//! [`channel::ChannelIFace::set_hpoint`]. For generic drivers the channels implement the
+//! embedded-hal `PwmPin` trait and [`pwm::Pwm`], which owns a timer and the channels it drives,
+//! implements the `Pwm` trait.
+//!
+//! Timers can interrupt on overflow (the start of each PWM period) and channels at the end of a
+//! fade. All events share the LEDC interrupt, which needs to be enabled with
+//! `interrupt::enable(Interrupt::LEDC)`. The handler checks and clears the events:
+//! ```
+//!     channel0.listen();
+//!     interrupt::enable(Interrupt::LEDC).unwrap();
+//!
+//!     #[interrupt]
+//!     fn LEDC() {
+//!         if channel0.is_interrupt_set() {
+//!             channel0.clear_interrupt();
+//!             // start the next fade
+//!         }
+//!     }
+//! ```
+//!
+//! # Clock sources and DFS
+//! Timers clocked by the APB clock (or by REF_TICK when it is not stable) are registered with
+//! [`ClockControlConfig::add_callback`] and their divisors are recalculated on every DFS change,
+//! so the configured frequency is kept (with a glitch in the PWM period at the change). For
+//! output which is not affected by DFS at all, use REF_TICK with a stable reference clock or,
+//! for Low Speed timers, the 8MHz oscillator as global slow clock.
+//!
+//! The 8MHz oscillator needs to be enabled in the clock control:
+//! ```
+//!     ledc.set_global_slow_clock(LSGlobalClkSource::EightMHz);
+//!     let mut lstimer0 = ledc.get_timer::<LowSpeed>(timer::Number::Timer0);
+//!     lstimer0
+//!         .configure(timer::config::Config {
+//!             duty: timer::config::Duty::Duty10Bit,
+//!             clock_source: timer::LSClockSource::SlowClk,
+//!             frequency: 1.kHz().into(),
+//!         })
+//!     .unwrap();
+//! ```
 
-use crate::{clock_control::ClockControlConfig, dport, gpio::OutputPin};
+use crate::{clock_control::ClockControlConfig, dport, gpio::OutputPin, prelude::*};
 use channel::Channel;
 use timer::Timer;
 
 use self::timer::TimerSpeed;
 
 pub mod channel;
+pub mod pwm;
 pub mod timer;
 
+/// Lock for the interrupt enable register shared by all timers and channels
+static INTERRUPT_LOCK: CriticalSectionSpinLockMutex<()> = CriticalSectionSpinLockMutex::new(());
+
 /// Global slow clock source
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum LSGlobalClkSource {
+    /// 8MHz oscillator, independent of DFS
     EightMHz,
+    /// APB clock, changes with DFS
     ABPClk,
 }
 
@@ -76,6 +128,9 @@ impl<'a> LEDC<'a> {
     }
 
     /// Set global slow clock source
+    ///
+    /// Needs to be set before configuring the Low Speed timers using
+    /// [`timer::LSClockSource::SlowClk`], as their divisors are based on it.
     pub fn set_global_slow_clock(&mut self, clock_source: LSGlobalClkSource) {
         match clock_source {
             LSGlobalClkSource::EightMHz => self.ledc.conf.write(|w| w.apb_clk_sel().clear_bit()),
@@ -0,0 +1,144 @@
+//! embedded-hal `Pwm` implementation driving several channels from one timer
+//!
+//! [Pwm] owns the timer and the channels it drives, so the period can be changed through the
+//! embedded-hal [`Pwm`](embedded_hal::Pwm) trait while the channels are in use. The period is
+//! expressed as frequency and changing it keeps the duty of the channels in timer ticks.
+//!
+//! As all channels share one pin type, pins of different types need to be converted into an
+//! [AnyPin](crate::gpio::AnyPin) first:
+//! ```
+//!     let mut pwm = Pwm::new(lstimer0).unwrap();
+//!     pwm.add_channel(channel::Number::Channel0, pins.gpio4.degrade())
+//!         .unwrap();
+//!     pwm.add_channel(channel::Number::Channel1, pins.gpio5.degrade())
+//!         .unwrap();
+//!
+//!     let max_duty = pwm.get_max_duty();
+//!     pwm.set_duty(channel::Number::Channel0, max_duty / 4);
+//!     pwm.enable(channel::Number::Channel0);
+//! ```
+
+use super::channel::{self, Channel, ChannelHW, Error};
+use super::timer::{Timer, TimerHW, TimerIFace, TimerSpeed};
+use crate::gpio::OutputPin;
+use crate::units::*;
+
+/// Timer with the channels it drives
+pub struct Pwm<'a, S: TimerSpeed, O: OutputPin> {
+    timer: Timer<'a, S>,
+    channels: [Option<Channel<'a, S, O>>; 8],
+}
+
+impl<'a, S: TimerSpeed, O: OutputPin> Pwm<'a, S, O>
+where
+    Timer<'a, S>: TimerHW<S>,
+    Channel<'a, S, O>: ChannelHW<O>,
+{
+    /// Create a new PWM from a configured timer
+    pub fn new(timer: Timer<'a, S>) -> Result<Self, Error> {
+        if !timer.is_configured() {
+            return Err(Error::Timer);
+        }
+
+        Ok(Pwm {
+            timer,
+            channels: [None, None, None, None, None, None, None, None],
+        })
+    }
+
+    /// Connect a channel to the timer and route it to the pin
+    ///
+    /// The channel starts with a duty of 0 and can be used with the embedded-hal
+    /// [`Pwm`](embedded_hal::Pwm) functions afterwards.
+    pub fn add_channel(&mut self, number: channel::Number, output_pin: O) -> Result<(), Error> {
+        let slot = &mut self.channels[number as usize];
+        if slot.is_some() {
+            return Err(Error::Channel);
+        }
+
+        let mut channel = Channel::new(number, output_pin);
+        channel.set_duty_hw(0);
+        channel.connect_hw(self.timer.get_number() as u8);
+        *slot = Some(channel);
+
+        Ok(())
+    }
+
+    /// Disable the output of a channel and return its pin
+    pub fn remove_channel(&mut self, number: channel::Number) -> Option<O> {
+        self.channels[number as usize].take().map(|channel| {
+            channel.enable_output_hw(false);
+            channel.into_pin()
+        })
+    }
+
+    /// Disable the outputs of all channels and return the timer and the pins
+    pub fn release(mut self) -> (Timer<'a, S>, [Option<O>; 8]) {
+        let mut pins = [None, None, None, None, None, None, None, None];
+        for (pin, channel) in pins.iter_mut().zip(self.channels.iter_mut()) {
+            if let Some(channel) = channel.take() {
+                channel.enable_output_hw(false);
+                *pin = Some(channel.into_pin());
+            }
+        }
+
+        (self.timer, pins)
+    }
+}
+
+/// embedded-hal [`Pwm`](embedded_hal::Pwm) implementation
+///
+/// The duty is in timer ticks (0 to [`TimerIFace::get_max_duty`]), larger values are clamped.
+/// Channels which have not been added with [`Pwm::add_channel`] are ignored.
+impl<'a, S: TimerSpeed, O: OutputPin> embedded_hal::Pwm for Pwm<'a, S, O>
+where
+    Timer<'a, S>: TimerHW<S>,
+    Channel<'a, S, O>: ChannelHW<O>,
+{
+    type Channel = channel::Number;
+    type Time = Hertz;
+    type Duty = u32;
+
+    fn disable(&mut self, channel: Self::Channel) {
+        if let Some(channel) = &self.channels[channel as usize] {
+            channel.enable_output_hw(false);
+        }
+    }
+
+    fn enable(&mut self, channel: Self::Channel) {
+        if let Some(channel) = &self.channels[channel as usize] {
+            channel.enable_output_hw(true);
+        }
+    }
+
+    fn get_period(&self) -> Self::Time {
+        self.timer.get_pwm_freq().unwrap_or(Hertz(0))
+    }
+
+    fn get_duty(&self, channel: Self::Channel) -> Self::Duty {
+        self.channels[channel as usize]
+            .as_ref()
+            .map_or(0, |channel| channel.get_duty_hw())
+    }
+
+    fn get_max_duty(&self) -> Self::Duty {
+        self.timer.get_max_duty().unwrap_or(0)
+    }
+
+    fn set_duty(&mut self, channel: Self::Channel, duty: Self::Duty) {
+        let duty = core::cmp::min(duty, self.get_max_duty());
+        if let Some(channel) = &self.channels[channel as usize] {
+            channel.set_duty_hw(duty);
+        }
+    }
+
+    /// Set the frequency of the timer
+    ///
+    /// The frequency is kept if the new one can not be reached with the duty resolution.
+    fn set_period<P>(&mut self, period: P)
+    where
+        P: Into<Self::Time>,
+    {
+        let _ = self.timer.set_frequency(period.into());
+    }
+}
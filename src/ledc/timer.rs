@@ -1,12 +1,21 @@
-use super::{HighSpeed, LowSpeed, Speed};
-use crate::{clock_control::ClockControlConfig, units::*};
+use super::{HighSpeed, LowSpeed, Speed, INTERRUPT_LOCK};
+use crate::{
+    clock_control::{CPUSource, ClockControlConfig},
+    prelude::*,
+    units::*,
+};
 use esp32::ledc;
+use paste::paste;
 
 /// Timer errors
 #[derive(Debug)]
 pub enum Error {
     /// Invalid Divisor
     Divisor,
+    /// The clock source is disabled (e.g. RTC8M is not enabled in the clock control)
+    ClockSource,
+    /// The callback to follow DFS changes could not be registered
+    Callback,
 }
 
 /// Clock source for HS Timers
@@ -32,6 +41,14 @@ pub enum Number {
     Timer3,
 }
 
+/// All timer numbers, used to iterate over the timers
+const NUMBERS: [Number; 4] = [
+    Number::Timer0,
+    Number::Timer1,
+    Number::Timer2,
+    Number::Timer3,
+];
+
 /// Timer configuration
 pub mod config {
     use crate::units::*;
@@ -70,19 +87,95 @@ pub mod config {
     }
 }
 
+/// Clock source of a timer which changes its frequency with DFS
+#[derive(Copy, Clone)]
+enum DfsSource {
+    Apb,
+    RefTick,
+}
+
+/// Settings needed to recalculate the divisor of a timer after a DFS change
+#[derive(Copy, Clone)]
+struct DfsTimer {
+    clock_control_config: ClockControlConfig,
+    source: DfsSource,
+    frequency: Hertz,
+    duty: config::Duty,
+}
+
+/// Timers with a clock source which changes with DFS
+struct DfsTimers {
+    low_speed: [Option<DfsTimer>; 4],
+    high_speed: [Option<DfsTimer>; 4],
+    callback_registered: bool,
+}
+
+static DFS_TIMERS: CriticalSectionSpinLockMutex<DfsTimers> =
+    CriticalSectionSpinLockMutex::new(DfsTimers {
+        low_speed: [None; 4],
+        high_speed: [None; 4],
+        callback_registered: false,
+    });
+
+/// Callback recalculating the divisors of the registered timers after a DFS change
+fn dfs_callback(_: CPUSource, _: Hertz, _: Hertz, _: CPUSource, _: Hertz, apb_frequency: Hertz) {
+    let (low_speed, high_speed) =
+        (&DFS_TIMERS).lock(|timers| (timers.low_speed, timers.high_speed));
+    let ledc = unsafe { &*esp32::LEDC::ptr() };
+
+    for (number, timer) in NUMBERS.iter().zip(low_speed.iter()) {
+        if let Some(timer) = timer {
+            Timer::<LowSpeed>::new(ledc, timer.clock_control_config, *number)
+                .update_divisor(timer, apb_frequency);
+        }
+    }
+
+    for (number, timer) in NUMBERS.iter().zip(high_speed.iter()) {
+        if let Some(timer) = timer {
+            Timer::<HighSpeed>::new(ledc, timer.clock_control_config, *number)
+                .update_divisor(timer, apb_frequency);
+        }
+    }
+}
+
+/// Calculate the divisor (with 8 fractional bits) for the frequency
+fn calculate_divisor(src_freq: Hertz, frequency: Hertz, duty: config::Duty) -> Result<u32, Error> {
+    let src_freq: u32 = src_freq.into();
+    if src_freq == 0 {
+        return Err(Error::ClockSource);
+    }
+
+    let precision = 2_u64.pow(duty as u32);
+    let frequency: u32 = frequency.into();
+
+    let divisor = (((src_freq as u64) << 8) + ((frequency as u64 * precision) / 2))
+        / (frequency as u64 * precision);
+
+    if divisor >= 0x10_0000 || divisor == 0 {
+        return Err(Error::Divisor);
+    }
+
+    Ok(divisor as u32)
+}
+
 /// Trait defining the type of timer source
 pub trait TimerSpeed: Speed {
     type ClockSourceType;
+
+    /// High Speed timer
+    const HIGH_SPEED: bool;
 }
 
 /// Timer source type for LowSpeed timers
 impl TimerSpeed for LowSpeed {
     type ClockSourceType = LSClockSource;
+    const HIGH_SPEED: bool = false;
 }
 
 /// Timer source type for HighSpeed timers
 impl TimerSpeed for HighSpeed {
     type ClockSourceType = HSClockSource;
+    const HIGH_SPEED: bool = true;
 }
 
 /// Interface for Timers
@@ -91,16 +184,53 @@ pub trait TimerIFace<S: TimerSpeed> {
     fn get_freq(&self) -> Option<Hertz>;
 
     /// Configure the timer
+    ///
+    /// If the clock source changes with DFS (APB clock, or REF_TICK when it is not stable), the
+    /// divisor is recalculated automatically on every DFS change, so the frequency is kept.
     fn configure(&mut self, config: config::Config<S::ClockSourceType>) -> Result<(), Error>;
 
+    /// Change the frequency of a configured timer, keeping the clock source and duty resolution
+    ///
+    /// The duty of the channels in timer ticks is kept, so their duty cycle does not change.
+    fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error>;
+
     /// Check if the timer has been configured
     fn is_configured(&self) -> bool;
 
     /// Return the duty resolution of the timer
     fn get_duty(&self) -> Option<config::Duty>;
 
+    /// Return the configured PWM frequency of the timer
+    fn get_pwm_freq(&self) -> Option<Hertz>;
+
+    /// Return the duty value corresponding to 100% (2^duty resolution)
+    fn get_max_duty(&self) -> Option<u32>;
+
+    /// Pause the timer, the outputs of the channels keep their current level
+    fn pause(&self);
+
+    /// Resume the timer after [`Self::pause`]
+    fn resume(&self);
+
+    /// Reset the counter of the timer
+    fn reset(&self);
+
     /// Return the timer number
     fn get_number(&self) -> Number;
+
+    /// Start listening for the overflow interrupt
+    ///
+    /// Takes `&self`, so it can be used while channels borrow the timer.
+    fn listen(&self);
+
+    /// Stop listening for the overflow interrupt
+    fn unlisten(&self);
+
+    /// Clear the overflow interrupt
+    fn clear_interrupt(&self);
+
+    /// Check if the timer has overflowed since the interrupt was cleared
+    fn is_interrupt_set(&self) -> bool;
 }
 
 /// Interface for HW configuration of timer
@@ -111,8 +241,101 @@ pub trait TimerHW<S: TimerSpeed> {
     /// Configure the HW for the timer
     fn configure_hw(&self, divisor: u32);
 
+    /// Set the divisor of the timer in HW
+    fn set_divisor_hw(&self, divisor: u32);
+
+    /// Register/unregister the timer for recalculation of the divisor on DFS changes, depending
+    /// on the clock source
+    fn register_dfs_hw(&self, frequency: Hertz) -> Result<(), Error>;
+
     /// Update the timer in HW
     fn update_hw(&self);
+
+    /// Pause/resume the timer in HW
+    fn set_pause_hw(&self, pause: bool);
+
+    /// Hold the counter of the timer in reset in HW
+    fn set_reset_hw(&self, reset: bool);
+
+    /// Enable/disable the overflow interrupt in HW
+    fn enable_interrupt_hw(&self, enable: bool);
+
+    /// Clear the overflow interrupt in HW
+    fn clear_interrupt_hw(&self);
+
+    /// Check if the overflow interrupt is set in HW
+    fn is_interrupt_set_hw(&self) -> bool;
+}
+
+/// Macro to set the divisor of the timer in hw
+macro_rules! set_divisor {
+    ( $self: ident, $speed: ident, $num: literal, $divisor: ident ) => {
+        paste! {
+            $self.ledc
+                .[<$speed stimer $num _conf>]
+                .modify(|_, w| unsafe { w.[<div_num_ $speed stimer $num>]().bits($divisor) })
+        }
+    };
+}
+
+/// Macro to pause/resume the timer in hw
+macro_rules! set_pause {
+    ( $self: ident, $speed: ident, $num: literal, $pause: ident ) => {
+        paste! {
+            $self.ledc
+                .[<$speed stimer $num _conf>]
+                .modify(|_, w| w.[<$speed stimer $num _pause>]().bit($pause))
+        }
+    };
+}
+
+/// Macro to hold the timer in reset in hw
+macro_rules! set_reset {
+    ( $self: ident, $speed: ident, $num: literal, $reset: ident ) => {
+        paste! {
+            $self.ledc
+                .[<$speed stimer $num _conf>]
+                .modify(|_, w| w.[<$speed stimer $num _rst>]().bit($reset))
+        }
+    };
+}
+
+/// Macro to enable/disable the overflow interrupt in hw
+macro_rules! enable_interrupt {
+    ( $self: ident, $speed: ident, $num: literal, $enable: ident ) => {
+        paste! {
+            // shared register without set/clear functionality, so needs lock
+            (&INTERRUPT_LOCK).lock(|_| {
+                $self.ledc
+                    .int_ena
+                    .modify(|_, w| w.[<$speed timer $num _ovf_int_ena>]().bit($enable))
+            })
+        }
+    };
+}
+
+/// Macro to clear the overflow interrupt in hw
+macro_rules! clear_interrupt {
+    ( $self: ident, $speed: ident, $num: literal ) => {
+        paste! {
+            $self.ledc
+                .int_clr
+                .write(|w| w.[<$speed timer $num _ovf_int_clr>]().set_bit())
+        }
+    };
+}
+
+/// Macro to check the overflow interrupt in hw
+macro_rules! is_interrupt_set {
+    ( $self: ident, $speed: ident, $num: literal ) => {
+        paste! {
+            $self.ledc
+                .int_raw
+                .read()
+                .[<$speed timer $num _ovf_int_raw>]()
+                .bit_is_set()
+        }
+    };
 }
 
 /// Timer struct
@@ -121,6 +344,7 @@ pub struct Timer<'a, S: TimerSpeed> {
     clock_control_config: ClockControlConfig,
     number: Number,
     duty: Option<config::Duty>,
+    frequency: Option<Hertz>,
     configured: bool,
     clock_source: Option<S::ClockSourceType>,
 }
@@ -139,21 +363,32 @@ where
         self.duty = Some(config.duty);
         self.clock_source = Some(config.clock_source);
 
-        let src_freq: u32 = self.get_freq().unwrap().into();
-        let precision = 2_u64.pow(config.duty as u32);
-        let frequency: u32 = config.frequency.into();
+        let divisor = self.get_divisor(config.frequency, config.duty)?;
+
+        // register before writing the divisor, so a DFS change in between is not missed
+        self.register_dfs_hw(config.frequency)?;
+        self.configure_hw(divisor);
+        self.update_hw();
 
-        let divisor = (((src_freq as u64) << 8) + ((frequency as u64 * precision) / 2))
-            / (frequency as u64 * precision);
+        self.frequency = Some(config.frequency);
+        self.configured = true;
 
-        if divisor >= 0x10_0000 || divisor == 0 {
-            return Err(Error::Divisor);
-        }
+        Ok(())
+    }
 
-        self.configure_hw(divisor as u32);
+    /// Change the frequency of a configured timer, keeping the clock source and duty resolution
+    fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+        let duty = self
+            .duty
+            .filter(|_| self.configured)
+            .ok_or(Error::Divisor)?;
+        let divisor = self.get_divisor(frequency, duty)?;
+
+        self.register_dfs_hw(frequency)?;
+        self.set_divisor_hw(divisor);
         self.update_hw();
 
-        self.configured = true;
+        self.frequency = Some(frequency);
 
         Ok(())
     }
@@ -168,10 +403,59 @@ where
         self.duty
     }
 
+    /// Return the configured PWM frequency of the timer
+    fn get_pwm_freq(&self) -> Option<Hertz> {
+        self.frequency
+    }
+
+    /// Return the duty value corresponding to 100% (2^duty resolution)
+    fn get_max_duty(&self) -> Option<u32> {
+        self.duty.map(|duty| 2_u32.pow(duty as u32))
+    }
+
+    /// Pause the timer, the outputs of the channels keep their current level
+    fn pause(&self) {
+        self.set_pause_hw(true);
+        self.update_hw();
+    }
+
+    /// Resume the timer after [`Self::pause`]
+    fn resume(&self) {
+        self.set_pause_hw(false);
+        self.update_hw();
+    }
+
+    /// Reset the counter of the timer
+    fn reset(&self) {
+        self.set_reset_hw(true);
+        self.set_reset_hw(false);
+        self.update_hw();
+    }
+
     /// Return the timer number
     fn get_number(&self) -> Number {
         self.number
     }
+
+    /// Start listening for the overflow interrupt
+    fn listen(&self) {
+        self.enable_interrupt_hw(true);
+    }
+
+    /// Stop listening for the overflow interrupt
+    fn unlisten(&self) {
+        self.enable_interrupt_hw(false);
+    }
+
+    /// Clear the overflow interrupt
+    fn clear_interrupt(&self) {
+        self.clear_interrupt_hw();
+    }
+
+    /// Check if the timer has overflowed since the interrupt was cleared
+    fn is_interrupt_set(&self) -> bool {
+        self.is_interrupt_set_hw()
+    }
 }
 
 impl<'a, S: TimerSpeed> Timer<'a, S> {
@@ -186,19 +470,105 @@ impl<'a, S: TimerSpeed> Timer<'a, S> {
             clock_control_config,
             number,
             duty: None,
+            frequency: None,
             configured: false,
             clock_source: None,
         }
     }
 
+    /// Helper function that calculates the divisor (with 8 fractional bits) for the frequency
+    fn get_divisor(&self, frequency: Hertz, duty: config::Duty) -> Result<u32, Error>
+    where
+        Timer<'a, S>: TimerHW<S>,
+    {
+        calculate_divisor(self.get_freq_hw().ok_or(Error::Divisor)?, frequency, duty)
+    }
+
     /// Helper function that return the current frequency of the LowSpeed global source
+    ///
+    /// Returns 0Hz if the global source is the 8MHz oscillator and it is not enabled.
     fn get_slow_clock_freq(&self) -> Hertz {
         if self.ledc.conf.read().apb_clk_sel().bit_is_clear() {
-            8.MHz().into()
+            self.clock_control_config.rtc8m_frequency()
         } else {
             self.clock_control_config.apb_frequency()
         }
     }
+
+    /// Helper function that stores the settings of the timer if the clock source changes with
+    /// DFS and registers the DFS callback on first use
+    fn register_dfs(&self, source: Option<DfsSource>, frequency: Hertz) -> Result<(), Error> {
+        let timer = match (source, self.duty) {
+            (Some(source), Some(duty)) => Some(DfsTimer {
+                clock_control_config: self.clock_control_config,
+                source,
+                frequency,
+                duty,
+            }),
+            _ => None,
+        };
+        let number = self.number as usize;
+
+        let register_callback = (&DFS_TIMERS).lock(|timers| {
+            if S::HIGH_SPEED {
+                timers.high_speed[number] = timer;
+            } else {
+                timers.low_speed[number] = timer;
+            }
+
+            let register = timer.is_some() && !timers.callback_registered;
+            timers.callback_registered |= register;
+            register
+        });
+
+        // register outside of the lock, the callbacks are called with the DFS lock taken
+        if register_callback
+            && self
+                .clock_control_config
+                .add_callback(&dfs_callback)
+                .is_err()
+        {
+            (&DFS_TIMERS).lock(|timers| timers.callback_registered = false);
+            return Err(Error::Callback);
+        }
+
+        Ok(())
+    }
+
+    /// Helper function that recalculates the divisor after a DFS change
+    fn update_divisor(&self, timer: &DfsTimer, apb_frequency: Hertz)
+    where
+        Timer<'a, S>: TimerHW<S>,
+    {
+        let src_freq = match timer.source {
+            DfsSource::Apb => apb_frequency,
+            DfsSource::RefTick => timer.clock_control_config.ref_frequency(),
+        };
+
+        // keep the old divisor if the frequency can not be reached anymore
+        if let Ok(divisor) = calculate_divisor(src_freq, timer.frequency, timer.duty) {
+            self.set_divisor_hw(divisor);
+            self.update_hw();
+        }
+    }
+}
+
+/// Stop recalculating the divisor of a configured timer on DFS changes
+impl<'a, S: TimerSpeed> Drop for Timer<'a, S> {
+    fn drop(&mut self) {
+        if !self.configured {
+            return;
+        }
+
+        let number = self.number as usize;
+        (&DFS_TIMERS).lock(|timers| {
+            if S::HIGH_SPEED {
+                timers.high_speed[number] = None;
+            } else {
+                timers.low_speed[number] = None;
+            }
+        });
+    }
 }
 
 /// Timer HW implementation for LowSpeed timers
@@ -267,6 +637,30 @@ impl<'a> TimerHW<LowSpeed> for Timer<'a, LowSpeed> {
         };
     }
 
+    /// Set the divisor of the timer in HW
+    fn set_divisor_hw(&self, divisor: u32) {
+        match self.number {
+            Number::Timer0 => set_divisor!(self, l, 0, divisor),
+            Number::Timer1 => set_divisor!(self, l, 1, divisor),
+            Number::Timer2 => set_divisor!(self, l, 2, divisor),
+            Number::Timer3 => set_divisor!(self, l, 3, divisor),
+        };
+    }
+
+    /// Register/unregister the timer for recalculation of the divisor on DFS changes
+    fn register_dfs_hw(&self, frequency: Hertz) -> Result<(), Error> {
+        let source = match self.clock_source {
+            Some(LSClockSource::RefTick) if !self.clock_control_config.is_ref_clock_stable() => {
+                Some(DfsSource::RefTick)
+            }
+            Some(LSClockSource::SlowClk) if self.ledc.conf.read().apb_clk_sel().bit_is_set() => {
+                Some(DfsSource::Apb)
+            }
+            _ => None,
+        };
+        self.register_dfs(source, frequency)
+    }
+
     /// Update the timer in HW
     fn update_hw(&self) {
         match self.number {
@@ -288,6 +682,56 @@ impl<'a> TimerHW<LowSpeed> for Timer<'a, LowSpeed> {
                 .modify(|_, w| w.lstimer3_para_up().set_bit()),
         };
     }
+
+    /// Pause/resume the timer in HW
+    fn set_pause_hw(&self, pause: bool) {
+        match self.number {
+            Number::Timer0 => set_pause!(self, l, 0, pause),
+            Number::Timer1 => set_pause!(self, l, 1, pause),
+            Number::Timer2 => set_pause!(self, l, 2, pause),
+            Number::Timer3 => set_pause!(self, l, 3, pause),
+        };
+    }
+
+    /// Hold the counter of the timer in reset in HW
+    fn set_reset_hw(&self, reset: bool) {
+        match self.number {
+            Number::Timer0 => set_reset!(self, l, 0, reset),
+            Number::Timer1 => set_reset!(self, l, 1, reset),
+            Number::Timer2 => set_reset!(self, l, 2, reset),
+            Number::Timer3 => set_reset!(self, l, 3, reset),
+        };
+    }
+
+    /// Enable/disable the overflow interrupt in HW
+    fn enable_interrupt_hw(&self, enable: bool) {
+        match self.number {
+            Number::Timer0 => enable_interrupt!(self, ls, 0, enable),
+            Number::Timer1 => enable_interrupt!(self, ls, 1, enable),
+            Number::Timer2 => enable_interrupt!(self, ls, 2, enable),
+            Number::Timer3 => enable_interrupt!(self, ls, 3, enable),
+        };
+    }
+
+    /// Clear the overflow interrupt in HW
+    fn clear_interrupt_hw(&self) {
+        match self.number {
+            Number::Timer0 => clear_interrupt!(self, ls, 0),
+            Number::Timer1 => clear_interrupt!(self, ls, 1),
+            Number::Timer2 => clear_interrupt!(self, ls, 2),
+            Number::Timer3 => clear_interrupt!(self, ls, 3),
+        };
+    }
+
+    /// Check if the overflow interrupt is set in HW
+    fn is_interrupt_set_hw(&self) -> bool {
+        match self.number {
+            Number::Timer0 => is_interrupt_set!(self, ls, 0),
+            Number::Timer1 => is_interrupt_set!(self, ls, 1),
+            Number::Timer2 => is_interrupt_set!(self, ls, 2),
+            Number::Timer3 => is_interrupt_set!(self, ls, 3),
+        }
+    }
 }
 
 /// Timer HW implementation for HighSpeed timers
@@ -356,8 +800,80 @@ impl<'a> TimerHW<HighSpeed> for Timer<'a, HighSpeed> {
         };
     }
 
+    /// Set the divisor of the timer in HW
+    fn set_divisor_hw(&self, divisor: u32) {
+        match self.number {
+            Number::Timer0 => set_divisor!(self, h, 0, divisor),
+            Number::Timer1 => set_divisor!(self, h, 1, divisor),
+            Number::Timer2 => set_divisor!(self, h, 2, divisor),
+            Number::Timer3 => set_divisor!(self, h, 3, divisor),
+        };
+    }
+
+    /// Register/unregister the timer for recalculation of the divisor on DFS changes
+    fn register_dfs_hw(&self, frequency: Hertz) -> Result<(), Error> {
+        let source = match self.clock_source {
+            Some(HSClockSource::RefTick) if !self.clock_control_config.is_ref_clock_stable() => {
+                Some(DfsSource::RefTick)
+            }
+            Some(HSClockSource::APBClk) => Some(DfsSource::Apb),
+            _ => None,
+        };
+        self.register_dfs(source, frequency)
+    }
+
     /// Update the timer in HW
     fn update_hw(&self) {
         // Nothing to do for HS timers
     }
+
+    /// Pause/resume the timer in HW
+    fn set_pause_hw(&self, pause: bool) {
+        match self.number {
+            Number::Timer0 => set_pause!(self, h, 0, pause),
+            Number::Timer1 => set_pause!(self, h, 1, pause),
+            Number::Timer2 => set_pause!(self, h, 2, pause),
+            Number::Timer3 => set_pause!(self, h, 3, pause),
+        };
+    }
+
+    /// Hold the counter of the timer in reset in HW
+    fn set_reset_hw(&self, reset: bool) {
+        match self.number {
+            Number::Timer0 => set_reset!(self, h, 0, reset),
+            Number::Timer1 => set_reset!(self, h, 1, reset),
+            Number::Timer2 => set_reset!(self, h, 2, reset),
+            Number::Timer3 => set_reset!(self, h, 3, reset),
+        };
+    }
+
+    /// Enable/disable the overflow interrupt in HW
+    fn enable_interrupt_hw(&self, enable: bool) {
+        match self.number {
+            Number::Timer0 => enable_interrupt!(self, hs, 0, enable),
+            Number::Timer1 => enable_interrupt!(self, hs, 1, enable),
+            Number::Timer2 => enable_interrupt!(self, hs, 2, enable),
+            Number::Timer3 => enable_interrupt!(self, hs, 3, enable),
+        };
+    }
+
+    /// Clear the overflow interrupt in HW
+    fn clear_interrupt_hw(&self) {
+        match self.number {
+            Number::Timer0 => clear_interrupt!(self, hs, 0),
+            Number::Timer1 => clear_interrupt!(self, hs, 1),
+            Number::Timer2 => clear_interrupt!(self, hs, 2),
+            Number::Timer3 => clear_interrupt!(self, hs, 3),
+        };
+    }
+
+    /// Check if the overflow interrupt is set in HW
+    fn is_interrupt_set_hw(&self) -> bool {
+        match self.number {
+            Number::Timer0 => is_interrupt_set!(self, hs, 0),
+            Number::Timer1 => is_interrupt_set!(self, hs, 1),
+            Number::Timer2 => is_interrupt_set!(self, hs, 2),
+            Number::Timer3 => is_interrupt_set!(self, hs, 3),
+        }
+    }
 }
@@ -13,6 +13,8 @@
 //! - `mem`
 //!     - Include customized memcpy, memset, etc. which use word (4-byte) sized and aligned
 //!         instructions to support IRAM usage and as optimization
+//! - `smart-leds`
+//!     - Enables the RMT based WS2812/SK6812 driver implementing `smart_leds::SmartLedsWrite`
 
 #![no_std]
 #![cfg_attr(feature = "alloc", feature(allocator_api))]
@@ -36,11 +38,16 @@ pub mod efuse;
 pub mod external_ram;
 pub mod gpio;
 pub mod i2c;
+pub mod i2s;
 #[cfg(feature = "rt")]
 pub mod interrupt;
 pub mod ledc;
+pub mod mcpwm;
+pub mod pcnt;
 pub mod prelude;
+pub mod rmt;
 pub mod serial;
+pub mod sigma_delta;
 pub mod spi;
 pub mod timer;
 pub mod units;
@@ -0,0 +1,456 @@
+//! MCPWM capture channels
+//!
+//! Each unit has three capture channels, which store the value of a free running 32 bit
+//! capture timer on edges of their input. The capture timer runs from the APB clock, so the
+//! timestamps have a resolution of 12.5ns. To keep the APB clock (and the meaning of the
+//! timestamps) constant, a [Capture] holds a [LockAPB].
+//!
+//! Timestamps can be polled with [Capture::read] or collected in an interrupt handler with
+//! [Capture::handle_interrupt], which stores them in a small queue read by [Capture::read].
+//! [PulseMeter] calculates period, pulse width, frequency and duty from the timestamps of both
+//! edges:
+//! ```no_run
+//! let mut capture = mcpwm.capture0.connect(
+//!     pins.gpio18,
+//!     mcpwm::capture::CaptureConfig::default().edge(mcpwm::capture::CaptureEdge::Both),
+//! );
+//! let mut meter = PulseMeter::new(capture.apb_frequency());
+//!
+//! loop {
+//!     let timestamp = nb::block!(capture.read()).unwrap();
+//!     if meter.update(timestamp) {
+//!         let echo = meter.pulse_width().unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! The capture interrupts share the MCPWM interrupt of the unit, which needs to be enabled with
+//! `interrupt::enable(Interrupt::PWM0_INTR)`.
+
+use super::private::{CaptureNumber, Instance};
+use super::UNIT_LOCK;
+use crate::clock_control::{dfs::LockAPB, ClockControlConfig};
+use crate::gpio::InputPin;
+use crate::prelude::*;
+use core::marker::PhantomData;
+
+/// Size of the timestamp queue filled by [Capture::handle_interrupt]
+pub const QUEUE_SIZE: usize = 16;
+
+/// Bit of the capture 0 interrupt in the interrupt registers
+const CAP0_INT: u32 = 1 << 27;
+
+/// Capture errors
+#[derive(Debug)]
+pub enum Error {
+    /// Timestamps were lost because the queue was full
+    Overrun,
+}
+
+/// Edges to capture
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum CaptureEdge {
+    Falling = 1,
+    Rising = 2,
+    Both = 3,
+}
+
+/// Edge of a timestamp
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// Captured value of the capture timer
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Timestamp {
+    /// Capture timer value in APB clock ticks
+    pub value: u32,
+    pub edge: Edge,
+}
+
+/// Capture channel configuration
+#[derive(Copy, Clone, Debug)]
+pub struct CaptureConfig {
+    pub edge: CaptureEdge,
+    /// Capture only every (prescale + 1)th edge
+    pub prescale: u8,
+    /// Invert the input signal
+    pub invert: bool,
+}
+
+impl CaptureConfig {
+    pub fn edge(mut self, edge: CaptureEdge) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    pub fn prescale(mut self, prescale: u8) -> Self {
+        self.prescale = prescale;
+        self
+    }
+
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+}
+
+impl Default for CaptureConfig {
+    fn default() -> CaptureConfig {
+        CaptureConfig {
+            edge: CaptureEdge::Rising,
+            prescale: 0,
+            invert: false,
+        }
+    }
+}
+
+/// Capture channel 0 of a unit
+pub struct Capture0;
+/// Capture channel 1 of a unit
+pub struct Capture1;
+/// Capture channel 2 of a unit
+pub struct Capture2;
+
+/// Capture channel which is not yet connected to a pin
+pub struct CaptureChannel<UNIT: Instance, CH: CaptureNumber> {
+    clock_control_config: ClockControlConfig,
+    _unit: PhantomData<UNIT>,
+    _channel: PhantomData<CH>,
+}
+
+impl<UNIT: Instance, CH: CaptureNumber> CaptureChannel<UNIT, CH> {
+    pub(super) fn new(clock_control_config: ClockControlConfig) -> Self {
+        CaptureChannel {
+            clock_control_config,
+            _unit: PhantomData,
+            _channel: PhantomData,
+        }
+    }
+
+    /// Connect the channel to a pin and start capturing
+    pub fn connect<PIN: InputPin>(
+        self,
+        mut pin: PIN,
+        config: CaptureConfig,
+    ) -> Capture<UNIT, CH, PIN> {
+        let lock = self.clock_control_config.lock_apb_frequency();
+        let apb_frequency = self.clock_control_config.apb_frequency();
+        let mcpwm = UNIT::register_block();
+
+        pin.set_to_input()
+            .connect_input_to_peripheral(UNIT::CAPTURE_SIGNALS[CH::NUMBER as usize]);
+
+        // the capture timer is shared by the channels, so needs lock
+        (&UNIT_LOCK).lock(|_| {
+            mcpwm
+                .cap_timer_cfg
+                .modify(|_, w| w.cap_timer_en().set_bit())
+        });
+
+        CH::configure(
+            mcpwm,
+            true,
+            config.edge as u8,
+            config.prescale,
+            config.invert,
+        );
+
+        Capture {
+            channel: self,
+            pin,
+            _lock: lock,
+            apb_frequency,
+            queue: [Timestamp {
+                value: 0,
+                edge: Edge::Rising,
+            }; QUEUE_SIZE],
+            head: 0,
+            len: 0,
+            overrun: false,
+        }
+    }
+}
+
+/// Capture channel connected to a pin
+pub struct Capture<UNIT: Instance, CH: CaptureNumber, PIN: InputPin> {
+    channel: CaptureChannel<UNIT, CH>,
+    pin: PIN,
+    _lock: LockAPB,
+    apb_frequency: Hertz,
+    queue: [Timestamp; QUEUE_SIZE],
+    head: usize,
+    len: usize,
+    overrun: bool,
+}
+
+impl<UNIT: Instance, CH: CaptureNumber, PIN: InputPin> Capture<UNIT, CH, PIN> {
+    /// Frequency of the capture timer
+    pub fn apb_frequency(&self) -> Hertz {
+        self.apb_frequency
+    }
+
+    /// Read the next timestamp
+    ///
+    /// Returns queued timestamps first, then polls the channel. Reports [Error::Overrun] once
+    /// if timestamps were lost.
+    pub fn read(&mut self) -> nb::Result<Timestamp, Error> {
+        if self.overrun {
+            self.overrun = false;
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+
+        if self.len > 0 {
+            let timestamp = self.queue[self.head];
+            self.head = (self.head + 1) % QUEUE_SIZE;
+            self.len -= 1;
+            return Ok(timestamp);
+        }
+
+        if self.is_interrupt_set() {
+            let timestamp = self.timestamp();
+            self.clear_interrupt();
+            Ok(timestamp)
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Store a new timestamp in the queue, to be called from the MCPWM interrupt handler
+    ///
+    /// Returns true if a timestamp was captured.
+    pub fn handle_interrupt(&mut self) -> bool {
+        if !self.is_interrupt_set() {
+            return false;
+        }
+
+        let timestamp = self.timestamp();
+        self.clear_interrupt();
+
+        if self.len == QUEUE_SIZE {
+            self.overrun = true;
+        } else {
+            self.queue[(self.head + self.len) % QUEUE_SIZE] = timestamp;
+            self.len += 1;
+        }
+        true
+    }
+
+    /// Last captured timestamp
+    pub fn timestamp(&self) -> Timestamp {
+        let mcpwm = UNIT::register_block();
+        Timestamp {
+            value: CH::value(mcpwm),
+            edge: if CH::is_falling_edge(mcpwm) {
+                Edge::Falling
+            } else {
+                Edge::Rising
+            },
+        }
+    }
+
+    /// Capture the current value of the capture timer by software
+    ///
+    /// The value can be read with [Self::timestamp] and is also delivered by [Self::read].
+    pub fn software_capture(&mut self) {
+        CH::software_capture(UNIT::register_block());
+    }
+
+    /// Convert capture timer ticks to nanoseconds
+    pub fn ticks_to_ns(&self, ticks: u32) -> NanoSecondsU64 {
+        ticks_to_ns(ticks, self.apb_frequency)
+    }
+
+    /// Start listening for capture interrupts
+    pub fn listen(&mut self) {
+        enable_interrupt::<UNIT, CH>(true);
+    }
+
+    /// Stop listening for capture interrupts
+    pub fn unlisten(&mut self) {
+        enable_interrupt::<UNIT, CH>(false);
+    }
+
+    /// Check if a capture happened since the interrupt was cleared
+    pub fn is_interrupt_set(&self) -> bool {
+        UNIT::register_block().int_raw_mcpwm.read().bits() & (CAP0_INT << CH::NUMBER) != 0
+    }
+
+    /// Clear the capture interrupt
+    pub fn clear_interrupt(&mut self) {
+        UNIT::register_block()
+            .int_clr_mcpwm
+            .write(|w| unsafe { w.bits(CAP0_INT << CH::NUMBER) });
+    }
+
+    /// Stop capturing and release the channel and pin
+    pub fn release(self) -> (CaptureChannel<UNIT, CH>, PIN) {
+        enable_interrupt::<UNIT, CH>(false);
+        CH::configure(UNIT::register_block(), false, 0, 0, false);
+        (self.channel, self.pin)
+    }
+}
+
+/// Measures period and pulse width from the timestamps of both edges of a signal
+///
+/// The capture channel must capture both edges without prescaler.
+pub struct PulseMeter {
+    apb_frequency: Hertz,
+    last_rising: Option<u32>,
+    period: Option<u32>,
+    pulse_width: Option<u32>,
+}
+
+impl PulseMeter {
+    /// Create a pulse meter for timestamps of a capture timer running at `apb_frequency`
+    pub fn new(apb_frequency: Hertz) -> Self {
+        PulseMeter {
+            apb_frequency,
+            last_rising: None,
+            period: None,
+            pulse_width: None,
+        }
+    }
+
+    /// Add a timestamp, returns true if a new period has been measured (at each rising edge)
+    pub fn update(&mut self, timestamp: Timestamp) -> bool {
+        match timestamp.edge {
+            Edge::Rising => {
+                let previous = self.last_rising.replace(timestamp.value);
+                self.period = previous.map(|previous| timestamp.value.wrapping_sub(previous));
+                self.period.is_some() && self.pulse_width.is_some()
+            }
+            Edge::Falling => {
+                self.pulse_width = self
+                    .last_rising
+                    .map(|rising| timestamp.value.wrapping_sub(rising));
+                false
+            }
+        }
+    }
+
+    /// Period in capture timer ticks
+    pub fn period_ticks(&self) -> Option<u32> {
+        self.period
+    }
+
+    /// High time in capture timer ticks
+    pub fn pulse_width_ticks(&self) -> Option<u32> {
+        self.pulse_width
+    }
+
+    /// Period of the signal
+    pub fn period(&self) -> Option<NanoSecondsU64> {
+        self.period
+            .map(|ticks| ticks_to_ns(ticks, self.apb_frequency))
+    }
+
+    /// High time of the signal (e.g. the echo time of an ultrasonic sensor)
+    pub fn pulse_width(&self) -> Option<NanoSecondsU64> {
+        self.pulse_width
+            .map(|ticks| ticks_to_ns(ticks, self.apb_frequency))
+    }
+
+    /// Frequency of the signal
+    pub fn frequency(&self) -> Option<Hertz> {
+        let apb_frequency: u32 = self.apb_frequency.into();
+        match self.period {
+            Some(period) if period > 0 => Some(Hertz((apb_frequency + period / 2) / period)),
+            _ => None,
+        }
+    }
+
+    /// Duty of the signal (0.0 to 1.0)
+    pub fn duty(&self) -> Option<f32> {
+        match (self.period, self.pulse_width) {
+            (Some(period), Some(pulse_width)) if period > 0 && pulse_width <= period => {
+                Some(pulse_width as f32 / period as f32)
+            }
+            _ => None,
+        }
+    }
+
+    /// Forget all timestamps, e.g. after an overrun
+    pub fn reset(&mut self) {
+        self.last_rising = None;
+        self.period = None;
+        self.pulse_width = None;
+    }
+}
+
+/// Convert capture timer ticks to nanoseconds
+fn ticks_to_ns(ticks: u32, apb_frequency: Hertz) -> NanoSecondsU64 {
+    let apb_frequency: u32 = apb_frequency.into();
+    NanoSecondsU64(ticks as u64 * 1_000_000_000 / apb_frequency as u64)
+}
+
+/// Enable/disable the interrupt of a capture channel
+fn enable_interrupt<UNIT: Instance, CH: CaptureNumber>(enable: bool) {
+    // shared register without set/clear functionality, so needs lock
+    (&UNIT_LOCK).lock(|_| {
+        UNIT::register_block().int_ena_mcpwm.modify(|r, w| unsafe {
+            w.bits(if enable {
+                r.bits() | CAP0_INT << CH::NUMBER
+            } else {
+                r.bits() & !(CAP0_INT << CH::NUMBER)
+            })
+        })
+    });
+}
+
+macro_rules! captures {
+    ($($channel:ident: $num:literal,)+) => {
+        $(
+            impl CaptureNumber for $channel {
+                const NUMBER: u8 = $num;
+
+                fn configure(
+                    mcpwm: &super::mcpwm0::RegisterBlock,
+                    enable: bool,
+                    mode: u8,
+                    prescale: u8,
+                    invert: bool,
+                ) {
+                    paste::paste! {
+                        mcpwm.[<cap_ch $num _cfg>].write(|w| unsafe {
+                            w.[<cap $num _en>]()
+                                .bit(enable)
+                                .[<cap $num _mode>]()
+                                .bits(mode)
+                                .[<cap $num _prescale>]()
+                                .bits(prescale)
+                                .[<cap $num _in_invert>]()
+                                .bit(invert)
+                        });
+                    }
+                }
+
+                fn value(mcpwm: &super::mcpwm0::RegisterBlock) -> u32 {
+                    paste::paste! {
+                        mcpwm.[<cap_ch $num>].read().[<cap $num _value>]().bits()
+                    }
+                }
+
+                fn is_falling_edge(mcpwm: &super::mcpwm0::RegisterBlock) -> bool {
+                    paste::paste! {
+                        mcpwm.cap_status.read().[<cap $num _edge>]().bit_is_set()
+                    }
+                }
+
+                fn software_capture(mcpwm: &super::mcpwm0::RegisterBlock) {
+                    paste::paste! {
+                        mcpwm.[<cap_ch $num _cfg>].modify(|_, w| w.[<cap $num _sw>]().set_bit());
+                    }
+                }
+            }
+        )+
+    };
+}
+
+captures! {
+    Capture0: 0,
+    Capture1: 1,
+    Capture2: 2,
+}
@@ -0,0 +1,1315 @@
+//! MCPWM (Motor Control PWM) peripheral control
+//!
+//! The ESP32 has two MCPWM units, each with three 16 bit timers and three operators. Each
+//! operator is connected to one of the timers and drives a pair of outputs (A and B) via two
+//! generators, which switch their output on timer events (zero, period) and on the compare
+//! values A and B.
+//!
+//! On top of the generators an operator provides:
+//! - dead-time insertion to generate complementary outputs for half bridges
+//! - fault handling: fault inputs force the outputs to a safe state, either until cleared by
+//!   software (one-shot) or until the next period (cycle-by-cycle)
+//! - update of the compare values synchronous to the timer (at zero and/or period), so the
+//!   outputs never see a partial update
+//!
+//! The timers can be synchronized to each other or to external sync inputs with a phase offset.
+//!
+//! The unit is clocked by the 160MHz PLL clock divided by `prescale + 1`, which is kept enabled
+//! while the [Control] of the unit exists.
+//!
+//! # Example:
+//! Complementary 20kHz PWM with 1µs dead time and a fault input shutting down both outputs:
+//! ```no_run
+//! let mut mcpwm = dp.MCPWM0.split(clock_control_config, mcpwm::config::Config::default());
+//!
+//! mcpwm.timer0.set_frequency(20.kHz().into()).unwrap();
+//! mcpwm.timer0.start();
+//!
+//! mcpwm.control.enable_fault(mcpwm::Fault::F0, pins.gpio5, false);
+//!
+//! let mut output = mcpwm.operator0.connect(
+//!     &mcpwm.timer0,
+//!     pins.gpio25.into_push_pull_output(),
+//!     pins.gpio26.into_push_pull_output(),
+//!     mcpwm::config::OperatorConfig::default()
+//!         .dead_time(mcpwm::config::DeadTimeConfig::complementary(10, 10))
+//!         .fault(mcpwm::Fault::F0, mcpwm::config::FaultMode::OneShot),
+//! );
+//!
+//! output.set_duty(mcpwm::Comparator::A, 0.25);
+//! ```
+//!
+//! The capture channels of a unit are described in [capture].
+
+use crate::clock_control::{dfs::LockPllD2, ClockControlConfig};
+use crate::gpio::{InputPin, InputSignal, OutputPin, OutputSignal};
+use crate::prelude::*;
+use crate::target::{mcpwm0, MCPWM0, MCPWM1};
+use core::marker::PhantomData;
+
+use capture::{Capture0, Capture1, Capture2, CaptureChannel};
+use private::{CaptureNumber, Instance, OperatorNumber, TimerNumber};
+
+pub mod capture;
+
+/// Frequency of the clock feeding the MCPWM units (PLL / 2)
+const SOURCE_CLK_FREQ: u32 = 160_000_000;
+
+/// MCPWM errors
+#[derive(Debug)]
+pub enum Error {
+    /// The frequency can not be reached with the 16 bit period and 8 bit prescaler
+    Frequency,
+}
+
+/// MCPWM configuration
+pub mod config {
+    use super::{Action, Comparator, Fault};
+
+    /// Unit configuration
+    #[derive(Copy, Clone, Debug)]
+    pub struct Config {
+        /// Unit clock prescaler: 160MHz / (prescale + 1)
+        pub prescale: u8,
+    }
+
+    impl Config {
+        pub fn prescale(mut self, prescale: u8) -> Self {
+            self.prescale = prescale;
+            self
+        }
+    }
+
+    impl Default for Config {
+        /// 10MHz unit clock
+        fn default() -> Config {
+            Config { prescale: 15 }
+        }
+    }
+
+    /// Counting mode of a timer
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum CountMode {
+        /// Count from 0 to period, then restart at 0
+        Up = 1,
+        /// Count from period to 0, then restart at period
+        Down = 2,
+        /// Count from 0 to period and back to 0 (symmetric PWM)
+        UpDown = 3,
+    }
+
+    /// Timer events at which new values are taken over from the shadow registers
+    ///
+    /// Multiple events can be combined, an empty update takes the values over immediately.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct Update {
+        pub on_zero: bool,
+        pub on_period: bool,
+        pub on_sync: bool,
+    }
+
+    impl Update {
+        /// Take new values over immediately
+        pub const IMMEDIATE: Update = Update {
+            on_zero: false,
+            on_period: false,
+            on_sync: false,
+        };
+
+        /// Take new values over when the timer reaches zero
+        pub const ON_ZERO: Update = Update {
+            on_zero: true,
+            on_period: false,
+            on_sync: false,
+        };
+
+        /// Bits of the update method fields
+        pub(super) fn bits(&self) -> u8 {
+            self.on_zero as u8 | (self.on_period as u8) << 1 | (self.on_sync as u8) << 2
+        }
+    }
+
+    /// Timer events at which a new period is taken over from the shadow register
+    ///
+    /// Unlike the compare values, the period can not be updated at the period event. An empty
+    /// update takes the period over immediately.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct PeriodUpdate {
+        pub on_zero: bool,
+        pub on_sync: bool,
+    }
+
+    impl PeriodUpdate {
+        /// Take a new period over immediately
+        pub const IMMEDIATE: PeriodUpdate = PeriodUpdate {
+            on_zero: false,
+            on_sync: false,
+        };
+
+        /// Take a new period over when the timer reaches zero
+        pub const ON_ZERO: PeriodUpdate = PeriodUpdate {
+            on_zero: true,
+            on_sync: false,
+        };
+
+        /// Bits of the period update method field
+        pub(super) fn bits(&self) -> u8 {
+            self.on_zero as u8 | (self.on_sync as u8) << 1
+        }
+    }
+
+    /// Timer configuration
+    #[derive(Copy, Clone, Debug)]
+    pub struct TimerConfig {
+        /// Timer clock prescaler: unit clock / (prescale + 1)
+        pub prescale: u8,
+        /// Period in timer ticks
+        ///
+        /// The timer counts from 0 to period, so a PWM period takes period + 1 ticks when counting
+        /// up or down and 2 * period ticks when counting up and down.
+        pub period: u16,
+        pub mode: CountMode,
+        /// When a new period is taken over
+        pub period_update: PeriodUpdate,
+    }
+
+    impl TimerConfig {
+        pub fn prescale(mut self, prescale: u8) -> Self {
+            self.prescale = prescale;
+            self
+        }
+
+        pub fn period(mut self, period: u16) -> Self {
+            self.period = period;
+            self
+        }
+
+        pub fn mode(mut self, mode: CountMode) -> Self {
+            self.mode = mode;
+            self
+        }
+
+        pub fn period_update(mut self, period_update: PeriodUpdate) -> Self {
+            self.period_update = period_update;
+            self
+        }
+    }
+
+    impl Default for TimerConfig {
+        fn default() -> TimerConfig {
+            TimerConfig {
+                prescale: 0,
+                period: 0xffff,
+                mode: CountMode::Up,
+                period_update: PeriodUpdate::ON_ZERO,
+            }
+        }
+    }
+
+    /// Actions of a generator on the timer events
+    ///
+    /// The compare events are split by counting direction, the zero and period events are used
+    /// for both directions.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct GeneratorConfig {
+        pub on_zero: Action,
+        pub on_period: Action,
+        pub on_compare_a_up: Action,
+        pub on_compare_a_down: Action,
+        pub on_compare_b_up: Action,
+        pub on_compare_b_down: Action,
+    }
+
+    impl GeneratorConfig {
+        /// Generator without any action
+        pub fn new() -> Self {
+            GeneratorConfig {
+                on_zero: Action::None,
+                on_period: Action::None,
+                on_compare_a_up: Action::None,
+                on_compare_a_down: Action::None,
+                on_compare_b_up: Action::None,
+                on_compare_b_down: Action::None,
+            }
+        }
+
+        /// Output high from zero until the compare value (up and up-down counting)
+        ///
+        /// The duty is compare value / period.
+        pub fn active_high(comparator: Comparator) -> Self {
+            let config = GeneratorConfig {
+                on_zero: Action::High,
+                ..Self::new()
+            };
+            match comparator {
+                Comparator::A => GeneratorConfig {
+                    on_compare_a_up: Action::Low,
+                    on_compare_a_down: Action::High,
+                    ..config
+                },
+                Comparator::B => GeneratorConfig {
+                    on_compare_b_up: Action::Low,
+                    on_compare_b_down: Action::High,
+                    ..config
+                },
+            }
+        }
+
+        /// Bits of the generator action register
+        pub(super) fn bits(&self) -> u32 {
+            let up = self.on_zero as u32
+                | (self.on_period as u32) << 2
+                | (self.on_compare_a_up as u32) << 4
+                | (self.on_compare_b_up as u32) << 6;
+            let down = self.on_zero as u32
+                | (self.on_period as u32) << 2
+                | (self.on_compare_a_down as u32) << 4
+                | (self.on_compare_b_down as u32) << 6;
+            up | down << 12
+        }
+    }
+
+    /// Dead-time output mode
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum DeadTimeMode {
+        /// No dead time, A and B are the outputs of the generators
+        Bypass,
+        /// A is generator A with delayed rising edge, B its inverse with delayed falling edge
+        ActiveHighComplementary,
+        /// As [DeadTimeMode::ActiveHighComplementary] with both outputs inverted
+        ActiveLowComplementary,
+        /// A and B both follow generator A with delayed rising and falling edge respectively
+        ActiveHigh,
+        /// As [DeadTimeMode::ActiveHigh] with both outputs inverted
+        ActiveLow,
+    }
+
+    /// Dead-time configuration, delays are in unit clock ticks
+    #[derive(Copy, Clone, Debug)]
+    pub struct DeadTimeConfig {
+        pub mode: DeadTimeMode,
+        pub rising_edge_delay: u16,
+        pub falling_edge_delay: u16,
+    }
+
+    impl DeadTimeConfig {
+        /// Active high complementary outputs for a half bridge
+        pub fn complementary(rising_edge_delay: u16, falling_edge_delay: u16) -> Self {
+            DeadTimeConfig {
+                mode: DeadTimeMode::ActiveHighComplementary,
+                rising_edge_delay,
+                falling_edge_delay,
+            }
+        }
+    }
+
+    impl Default for DeadTimeConfig {
+        fn default() -> DeadTimeConfig {
+            DeadTimeConfig {
+                mode: DeadTimeMode::Bypass,
+                rising_edge_delay: 0,
+                falling_edge_delay: 0,
+            }
+        }
+    }
+
+    /// Reaction of an operator on a fault input
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum FaultMode {
+        /// The fault is ignored
+        Disabled,
+        /// The outputs are forced until the fault is cleared by software
+        OneShot,
+        /// The outputs are forced until the next period after the fault has gone
+        CycleByCycle,
+    }
+
+    /// Operator configuration
+    #[derive(Copy, Clone, Debug)]
+    pub struct OperatorConfig {
+        pub generator_a: GeneratorConfig,
+        pub generator_b: GeneratorConfig,
+        /// When new compare values are taken over
+        pub compare_update: Update,
+        pub dead_time: DeadTimeConfig,
+        /// Reaction on the fault inputs F0, F1 and F2
+        pub faults: [FaultMode; 3],
+        /// Level of output A while a fault is active
+        pub fault_action_a: Action,
+        /// Level of output B while a fault is active
+        pub fault_action_b: Action,
+    }
+
+    impl OperatorConfig {
+        pub fn generator_a(mut self, generator_a: GeneratorConfig) -> Self {
+            self.generator_a = generator_a;
+            self
+        }
+
+        pub fn generator_b(mut self, generator_b: GeneratorConfig) -> Self {
+            self.generator_b = generator_b;
+            self
+        }
+
+        pub fn compare_update(mut self, compare_update: Update) -> Self {
+            self.compare_update = compare_update;
+            self
+        }
+
+        pub fn dead_time(mut self, dead_time: DeadTimeConfig) -> Self {
+            self.dead_time = dead_time;
+            self
+        }
+
+        pub fn fault(mut self, fault: Fault, mode: FaultMode) -> Self {
+            self.faults[fault as usize] = mode;
+            self
+        }
+
+        pub fn fault_actions(mut self, action_a: Action, action_b: Action) -> Self {
+            self.fault_action_a = action_a;
+            self.fault_action_b = action_b;
+            self
+        }
+    }
+
+    impl Default for OperatorConfig {
+        /// Active high outputs driven by compare A and B, updated at zero, faults disabled
+        fn default() -> OperatorConfig {
+            OperatorConfig {
+                generator_a: GeneratorConfig::active_high(Comparator::A),
+                generator_b: GeneratorConfig::active_high(Comparator::B),
+                compare_update: Update::ON_ZERO,
+                dead_time: DeadTimeConfig::default(),
+                faults: [FaultMode::Disabled; 3],
+                fault_action_a: Action::Low,
+                fault_action_b: Action::Low,
+            }
+        }
+    }
+}
+
+/// Action of a generator or fault handler on its output
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Action {
+    None = 0,
+    Low = 1,
+    High = 2,
+    Toggle = 3,
+}
+
+/// Compare value of an operator
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Comparator {
+    A,
+    B,
+}
+
+/// Output of an operator
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Generator {
+    A,
+    B,
+}
+
+/// Fault input of a unit
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Fault {
+    F0 = 0,
+    F1 = 1,
+    F2 = 2,
+}
+
+/// Sync input of a unit (GPIO)
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SyncPin {
+    Sync0 = 0,
+    Sync1 = 1,
+    Sync2 = 2,
+}
+
+/// Sync input source of a timer
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SyncInput {
+    None = 0,
+    Timer0 = 1,
+    Timer1 = 2,
+    Timer2 = 3,
+    Pin0 = 4,
+    Pin1 = 5,
+    Pin2 = 6,
+}
+
+/// Sync output of a timer, can be used as sync input by the other timers
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SyncOutput {
+    /// Forward the sync input
+    SyncInput = 0,
+    /// Sync at zero
+    Zero = 1,
+    /// Sync at period
+    Period = 2,
+    /// Sync only by [Timer::software_sync]
+    Software = 3,
+}
+
+/// Extension trait to split a MCPWM unit into its timers, operators and capture channels
+pub trait McpwmExt: Instance + Sized {
+    fn split(self, clock_control_config: ClockControlConfig, config: config::Config)
+        -> Parts<Self>;
+}
+
+impl<UNIT: Instance> McpwmExt for UNIT {
+    fn split(
+        self,
+        clock_control_config: ClockControlConfig,
+        config: config::Config,
+    ) -> Parts<Self> {
+        let lock = clock_control_config.lock_plld2();
+
+        crate::dport::enable_peripheral(UNIT::PERIPHERAL);
+        crate::dport::reset_peripheral(UNIT::PERIPHERAL);
+
+        UNIT::register_block()
+            .clk_cfg
+            .write(|w| unsafe { w.clk_prescale().bits(config.prescale) });
+
+        Parts {
+            control: Control {
+                _lock: lock,
+                prescale: config.prescale,
+                _unit: self,
+            },
+            timer0: Timer::new(),
+            timer1: Timer::new(),
+            timer2: Timer::new(),
+            operator0: Operator::new(),
+            operator1: Operator::new(),
+            operator2: Operator::new(),
+            capture0: CaptureChannel::new(clock_control_config),
+            capture1: CaptureChannel::new(clock_control_config),
+            capture2: CaptureChannel::new(clock_control_config),
+        }
+    }
+}
+
+/// Timers, operators and capture channels of a MCPWM unit
+pub struct Parts<UNIT: Instance> {
+    pub control: Control<UNIT>,
+    pub timer0: Timer<UNIT, Timer0>,
+    pub timer1: Timer<UNIT, Timer1>,
+    pub timer2: Timer<UNIT, Timer2>,
+    pub operator0: Operator<UNIT, Operator0>,
+    pub operator1: Operator<UNIT, Operator1>,
+    pub operator2: Operator<UNIT, Operator2>,
+    pub capture0: CaptureChannel<UNIT, Capture0>,
+    pub capture1: CaptureChannel<UNIT, Capture1>,
+    pub capture2: CaptureChannel<UNIT, Capture2>,
+}
+
+/// Unit wide settings: fault and sync inputs
+///
+/// Keeps the PLL clock of the unit enabled, so must be kept alive while the unit is used.
+pub struct Control<UNIT: Instance> {
+    _lock: LockPllD2,
+    prescale: u8,
+    _unit: UNIT,
+}
+
+impl<UNIT: Instance> Control<UNIT> {
+    /// Frequency of the unit clock (also the clock of the dead-time delays)
+    pub fn frequency(&self) -> Hertz {
+        Hertz(SOURCE_CLK_FREQ / (self.prescale as u32 + 1))
+    }
+
+    /// Connect a pin to a fault input and enable fault detection
+    ///
+    /// If `active_high` is false, the fault is active while the pin is low.
+    pub fn enable_fault<PIN: InputPin>(&mut self, fault: Fault, mut pin: PIN, active_high: bool) {
+        pin.set_to_input()
+            .connect_input_to_peripheral(UNIT::FAULT_SIGNALS[fault as usize]);
+
+        let mcpwm = UNIT::register_block();
+        let (enable, pole) = (1 << fault as u32, 1 << (fault as u32 + 3));
+        mcpwm.fault_detect.modify(|r, w| unsafe {
+            w.bits(if active_high {
+                r.bits() | enable | pole
+            } else {
+                (r.bits() | enable) & !pole
+            })
+        });
+    }
+
+    /// Disable detection of a fault input
+    pub fn disable_fault(&mut self, fault: Fault) {
+        UNIT::register_block()
+            .fault_detect
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << fault as u32)) });
+    }
+
+    /// Check if a fault input is currently active
+    pub fn is_fault_present(&self, fault: Fault) -> bool {
+        UNIT::register_block().fault_detect.read().bits() & (1 << (fault as u32 + 6)) != 0
+    }
+
+    /// Connect a pin to a sync input, which can then be selected with [SyncInput::Pin0] etc.
+    pub fn connect_sync<PIN: InputPin>(&mut self, sync: SyncPin, mut pin: PIN) {
+        pin.set_to_input()
+            .connect_input_to_peripheral(UNIT::SYNC_SIGNALS[sync as usize]);
+    }
+}
+
+/// Timer 0 of a unit
+pub struct Timer0;
+/// Timer 1 of a unit
+pub struct Timer1;
+/// Timer 2 of a unit
+pub struct Timer2;
+
+/// MCPWM timer
+pub struct Timer<UNIT: Instance, T: TimerNumber> {
+    _unit: PhantomData<UNIT>,
+    _timer: PhantomData<T>,
+}
+
+impl<UNIT: Instance, T: TimerNumber> Timer<UNIT, T> {
+    fn new() -> Self {
+        Timer {
+            _unit: PhantomData,
+            _timer: PhantomData,
+        }
+    }
+
+    /// Configure the timer, the timer is stopped
+    pub fn configure(&mut self, config: config::TimerConfig) {
+        let mcpwm = UNIT::register_block();
+        T::set_mode(mcpwm, 0, 0);
+        T::configure(
+            mcpwm,
+            config.prescale,
+            config.period,
+            config.period_update.bits(),
+        );
+        T::set_mode(mcpwm, 0, config.mode as u8);
+    }
+
+    /// Set the PWM frequency, keeping the counting mode
+    ///
+    /// The prescaler is chosen as small as possible for the best duty resolution. A frozen
+    /// timer (e.g. after `split`) is set to count up.
+    pub fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+        let mcpwm = UNIT::register_block();
+        let unit_frequency =
+            SOURCE_CLK_FREQ / (mcpwm.clk_cfg.read().clk_prescale().bits() as u32 + 1);
+        let frequency: u32 = frequency.into();
+        // a period in up-down mode covers twice the period value, otherwise period + 1 ticks
+        let (frequency, offset) = match self.mode() {
+            Some(config::CountMode::UpDown) => (frequency * 2, 0),
+            _ => (frequency, 1),
+        };
+        if frequency == 0 || unit_frequency / frequency < 2 {
+            return Err(Error::Frequency);
+        }
+
+        let ticks = unit_frequency / frequency;
+        // smallest prescaler for which the period value fits in 16 bits
+        let prescale = (ticks - 1) / (0xffff + offset);
+        if prescale > 0xff {
+            return Err(Error::Frequency);
+        }
+        let period = ticks / (prescale + 1) - offset;
+
+        T::configure(
+            mcpwm,
+            prescale as u8,
+            period as u16,
+            T::period_update(mcpwm),
+        );
+        if self.mode().is_none() {
+            T::set_mode(mcpwm, 0, config::CountMode::Up as u8);
+        }
+        Ok(())
+    }
+
+    /// Start the timer (free running), a frozen timer counts up
+    pub fn start(&mut self) {
+        let mcpwm = UNIT::register_block();
+        let mode = self.mode().unwrap_or(config::CountMode::Up);
+        T::set_mode(mcpwm, 2, mode as u8);
+    }
+
+    /// Stop the timer at the next zero
+    pub fn stop(&mut self) {
+        let mcpwm = UNIT::register_block();
+        T::set_mode(mcpwm, 0, T::mode(mcpwm));
+    }
+
+    /// Period in timer ticks
+    pub fn period(&self) -> u16 {
+        T::period(UNIT::register_block())
+    }
+
+    /// Current counter value
+    pub fn value(&self) -> u16 {
+        T::value(UNIT::register_block())
+    }
+
+    /// Counting mode, `None` if the timer is frozen
+    pub fn mode(&self) -> Option<config::CountMode> {
+        match T::mode(UNIT::register_block()) {
+            1 => Some(config::CountMode::Up),
+            2 => Some(config::CountMode::Down),
+            3 => Some(config::CountMode::UpDown),
+            _ => None,
+        }
+    }
+
+    /// Synchronize the timer to a sync input
+    ///
+    /// On a sync event the counter is loaded with `phase` and continues counting up or down
+    /// (only relevant for up-down counting).
+    pub fn set_sync_input(&mut self, input: SyncInput, phase: u16, count_up: bool) {
+        let mcpwm = UNIT::register_block();
+        T::set_sync_input(mcpwm, input as u8);
+        T::set_sync(mcpwm, input != SyncInput::None, phase, !count_up);
+    }
+
+    /// Select the sync output of the timer
+    pub fn set_sync_output(&mut self, output: SyncOutput) {
+        T::set_sync_output(UNIT::register_block(), output as u8);
+    }
+
+    /// Trigger a sync event by software, synchronizing the timer and the timers following it
+    pub fn software_sync(&mut self) {
+        T::software_sync(UNIT::register_block());
+    }
+}
+
+/// Operator 0 of a unit
+pub struct Operator0;
+/// Operator 1 of a unit
+pub struct Operator1;
+/// Operator 2 of a unit
+pub struct Operator2;
+
+/// MCPWM operator which is not yet connected to a timer and pins
+pub struct Operator<UNIT: Instance, OP: OperatorNumber> {
+    _unit: PhantomData<UNIT>,
+    _operator: PhantomData<OP>,
+}
+
+impl<UNIT: Instance, OP: OperatorNumber> Operator<UNIT, OP> {
+    fn new() -> Self {
+        Operator {
+            _unit: PhantomData,
+            _operator: PhantomData,
+        }
+    }
+
+    /// Connect the operator to a timer and route its outputs to the pins
+    pub fn connect<T: TimerNumber, PA: OutputPin, PB: OutputPin>(
+        self,
+        _timer: &Timer<UNIT, T>,
+        mut pin_a: PA,
+        mut pin_b: PB,
+        config: config::OperatorConfig,
+    ) -> PwmOutput<UNIT, OP, PA, PB> {
+        let mcpwm = UNIT::register_block();
+
+        OP::select_timer(mcpwm, T::NUMBER);
+        OP::set_compare_update(mcpwm, config.compare_update.bits());
+        OP::set_generators(mcpwm, config.generator_a.bits(), config.generator_b.bits());
+        OP::set_dead_time(mcpwm, &config.dead_time);
+        OP::set_faults(
+            mcpwm,
+            &config.faults,
+            config.fault_action_a,
+            config.fault_action_b,
+        );
+
+        pin_a
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(UNIT::OUTPUT_SIGNALS[OP::NUMBER as usize][0]);
+        pin_b
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(UNIT::OUTPUT_SIGNALS[OP::NUMBER as usize][1]);
+
+        PwmOutput {
+            operator: self,
+            pin_a,
+            pin_b,
+        }
+    }
+}
+
+/// MCPWM operator driving a pair of pins
+pub struct PwmOutput<UNIT: Instance, OP: OperatorNumber, PA: OutputPin, PB: OutputPin> {
+    operator: Operator<UNIT, OP>,
+    pin_a: PA,
+    pin_b: PB,
+}
+
+impl<UNIT: Instance, OP: OperatorNumber, PA: OutputPin, PB: OutputPin> PwmOutput<UNIT, OP, PA, PB> {
+    /// Set a compare value in timer ticks
+    ///
+    /// The value is taken over at the events selected by the compare update of the
+    /// configuration.
+    pub fn set_compare(&mut self, comparator: Comparator, value: u16) {
+        OP::set_compare(UNIT::register_block(), comparator == Comparator::B, value);
+    }
+
+    /// Get a compare value in timer ticks
+    pub fn compare(&self, comparator: Comparator) -> u16 {
+        OP::compare(UNIT::register_block(), comparator == Comparator::B)
+    }
+
+    /// Set a compare value as fraction of the period of the connected timer (0.0 to 1.0)
+    pub fn set_duty(&mut self, comparator: Comparator, duty: f32) {
+        let mcpwm = UNIT::register_block();
+        let period = OP::timer_period(mcpwm) as f32;
+        let duty = if duty < 0.0 {
+            0.0
+        } else if duty > 1.0 {
+            1.0
+        } else {
+            duty
+        };
+        OP::set_compare(mcpwm, comparator == Comparator::B, (period * duty) as u16);
+    }
+
+    /// Change the actions of a generator
+    pub fn set_generator(&mut self, generator: Generator, config: config::GeneratorConfig) {
+        OP::set_generator(
+            UNIT::register_block(),
+            generator == Generator::B,
+            config.bits(),
+        );
+    }
+
+    /// Change the dead-time configuration
+    pub fn set_dead_time(&mut self, config: config::DeadTimeConfig) {
+        OP::set_dead_time(UNIT::register_block(), &config);
+    }
+
+    /// Force an output to a level (`None` to release the force)
+    ///
+    /// The force is applied before the dead-time insertion.
+    pub fn force(&mut self, generator: Generator, level: Option<bool>) {
+        let mode = match level {
+            None => 0,
+            Some(false) => 1,
+            Some(true) => 2,
+        };
+        OP::force(UNIT::register_block(), generator == Generator::B, mode);
+    }
+
+    /// Check if a one-shot fault is active (outputs forced until [Self::clear_fault])
+    pub fn is_one_shot_fault_active(&self) -> bool {
+        OP::fault_status(UNIT::register_block()).0
+    }
+
+    /// Check if a cycle-by-cycle fault is active
+    pub fn is_cycle_by_cycle_fault_active(&self) -> bool {
+        OP::fault_status(UNIT::register_block()).1
+    }
+
+    /// Trigger a one-shot fault by software, e.g. for an emergency stop
+    pub fn trigger_fault(&mut self) {
+        OP::trigger_one_shot_fault(UNIT::register_block());
+    }
+
+    /// Clear a one-shot fault, the outputs are driven by the generators again
+    ///
+    /// The fault is triggered again immediately if the fault input is still active.
+    pub fn clear_fault(&mut self) {
+        OP::clear_one_shot_fault(UNIT::register_block());
+    }
+
+    /// Release the operator and pins
+    ///
+    /// The pins are left connected to the operator.
+    pub fn release(self) -> (Operator<UNIT, OP>, PA, PB) {
+        (self.operator, self.pin_a, self.pin_b)
+    }
+}
+
+macro_rules! timers {
+    ($($timer:ident: $num:literal,)+) => {
+        $(
+            impl TimerNumber for $timer {
+                const NUMBER: u8 = $num;
+
+                fn configure(mcpwm: &mcpwm0::RegisterBlock, prescale: u8, period: u16, update: u8) {
+                    paste::paste! {
+                        mcpwm.[<timer $num _cfg0>].write(|w| unsafe {
+                            w.[<timer $num _prescale>]()
+                                .bits(prescale)
+                                .[<timer $num _period>]()
+                                .bits(period)
+                                .[<timer $num _period_upmethod>]()
+                                .bits(update)
+                        });
+                    }
+                }
+
+                fn period(mcpwm: &mcpwm0::RegisterBlock) -> u16 {
+                    paste::paste! {
+                        mcpwm.[<timer $num _cfg0>].read().[<timer $num _period>]().bits()
+                    }
+                }
+
+                fn period_update(mcpwm: &mcpwm0::RegisterBlock) -> u8 {
+                    paste::paste! {
+                        mcpwm.[<timer $num _cfg0>].read().[<timer $num _period_upmethod>]().bits()
+                    }
+                }
+
+                fn set_mode(mcpwm: &mcpwm0::RegisterBlock, start: u8, mode: u8) {
+                    paste::paste! {
+                        mcpwm.[<timer $num _cfg1>].write(|w| unsafe {
+                            w.[<timer $num _start>]()
+                                .bits(start)
+                                .[<timer $num _mod>]()
+                                .bits(mode)
+                        });
+                    }
+                }
+
+                fn mode(mcpwm: &mcpwm0::RegisterBlock) -> u8 {
+                    paste::paste! {
+                        mcpwm.[<timer $num _cfg1>].read().[<timer $num _mod>]().bits()
+                    }
+                }
+
+                fn value(mcpwm: &mcpwm0::RegisterBlock) -> u16 {
+                    paste::paste! {
+                        mcpwm.[<timer $num _status>].read().[<timer $num _value>]().bits()
+                    }
+                }
+
+                fn set_sync(mcpwm: &mcpwm0::RegisterBlock, enable: bool, phase: u16, down: bool) {
+                    paste::paste! {
+                        mcpwm.[<timer $num _sync>].modify(|_, w| unsafe {
+                            w.[<timer $num _synci_en>]()
+                                .bit(enable)
+                                .[<timer $num _phase>]()
+                                .bits(phase)
+                                .[<timer $num _phase_direction>]()
+                                .bit(down)
+                        });
+                    }
+                }
+
+                fn set_sync_input(mcpwm: &mcpwm0::RegisterBlock, input: u8) {
+                    paste::paste! {
+                        // shared register without set/clear functionality, so needs lock
+                        (&UNIT_LOCK).lock(|_| {
+                            mcpwm
+                                .timer_synci_cfg
+                                .modify(|_, w| unsafe { w.[<timer $num _syncisel>]().bits(input) })
+                        });
+                    }
+                }
+
+                fn set_sync_output(mcpwm: &mcpwm0::RegisterBlock, output: u8) {
+                    paste::paste! {
+                        mcpwm
+                            .[<timer $num _sync>]
+                            .modify(|_, w| unsafe { w.[<timer $num _synco_sel>]().bits(output) });
+                    }
+                }
+
+                fn software_sync(mcpwm: &mcpwm0::RegisterBlock) {
+                    paste::paste! {
+                        // a toggle of the bit triggers the sync
+                        mcpwm.[<timer $num _sync>].modify(|r, w| {
+                            w.[<timer $num _sync_sw>]()
+                                .bit(!r.[<timer $num _sync_sw>]().bit())
+                        });
+                    }
+                }
+            }
+        )+
+    };
+}
+
+timers! {
+    Timer0: 0,
+    Timer1: 1,
+    Timer2: 2,
+}
+
+macro_rules! operators {
+    ($($operator:ident: $num:literal,)+) => {
+        $(
+            impl OperatorNumber for $operator {
+                const NUMBER: u8 = $num;
+
+                fn select_timer(mcpwm: &mcpwm0::RegisterBlock, timer: u8) {
+                    paste::paste! {
+                        // shared register without set/clear functionality, so needs lock
+                        (&UNIT_LOCK).lock(|_| {
+                            mcpwm
+                                .operator_timersel
+                                .modify(|_, w| unsafe { w.[<operator $num _timersel>]().bits(timer) })
+                        });
+                    }
+                }
+
+                fn timer_period(mcpwm: &mcpwm0::RegisterBlock) -> u16 {
+                    let timer = paste::paste! {
+                        mcpwm.operator_timersel.read().[<operator $num _timersel>]().bits()
+                    };
+                    match timer {
+                        0 => Timer0::period(mcpwm),
+                        1 => Timer1::period(mcpwm),
+                        _ => Timer2::period(mcpwm),
+                    }
+                }
+
+                fn set_compare_update(mcpwm: &mcpwm0::RegisterBlock, update: u8) {
+                    paste::paste! {
+                        mcpwm.[<gen $num _stmp_cfg>].write(|w| unsafe {
+                            w.[<gen $num _a_upmethod>]()
+                                .bits(update)
+                                .[<gen $num _b_upmethod>]()
+                                .bits(update)
+                        });
+                    }
+                }
+
+                fn set_compare(mcpwm: &mcpwm0::RegisterBlock, b: bool, value: u16) {
+                    paste::paste! {
+                        if b {
+                            mcpwm
+                                .[<gen $num _tstmp_b>]
+                                .write(|w| unsafe { w.[<gen $num _b>]().bits(value) });
+                        } else {
+                            mcpwm
+                                .[<gen $num _tstmp_a>]
+                                .write(|w| unsafe { w.[<gen $num _a>]().bits(value) });
+                        }
+                    }
+                }
+
+                fn compare(mcpwm: &mcpwm0::RegisterBlock, b: bool) -> u16 {
+                    paste::paste! {
+                        if b {
+                            mcpwm.[<gen $num _tstmp_b>].read().[<gen $num _b>]().bits()
+                        } else {
+                            mcpwm.[<gen $num _tstmp_a>].read().[<gen $num _a>]().bits()
+                        }
+                    }
+                }
+
+                fn set_generator(mcpwm: &mcpwm0::RegisterBlock, b: bool, bits: u32) {
+                    paste::paste! {
+                        if b {
+                            mcpwm.[<gen $num _b>].write(|w| unsafe { w.bits(bits) });
+                        } else {
+                            mcpwm.[<gen $num _a>].write(|w| unsafe { w.bits(bits) });
+                        }
+                    }
+                }
+
+                fn set_dead_time(mcpwm: &mcpwm0::RegisterBlock, config: &config::DeadTimeConfig) {
+                    use config::DeadTimeMode::*;
+
+                    let bypass = config.mode == Bypass;
+                    let (red_invert, fed_invert) = match config.mode {
+                        Bypass | ActiveHigh => (false, false),
+                        ActiveHighComplementary => (false, true),
+                        ActiveLowComplementary => (true, false),
+                        ActiveLow => (true, true),
+                    };
+
+                    paste::paste! {
+                        mcpwm
+                            .[<dt $num _red_cfg>]
+                            .write(|w| unsafe { w.[<dt $num _red>]().bits(config.rising_edge_delay) });
+                        mcpwm
+                            .[<dt $num _fed_cfg>]
+                            .write(|w| unsafe { w.[<dt $num _fed>]().bits(config.falling_edge_delay) });
+                        // both delays use generator A as input, clocked by the unit clock
+                        mcpwm.[<dt $num _cfg>].write(|w| unsafe {
+                            w.[<dt $num _fed_upmethod>]()
+                                .bits(0)
+                                .[<dt $num _red_upmethod>]()
+                                .bits(0)
+                                .[<dt $num _red_insel>]()
+                                .clear_bit()
+                                .[<dt $num _fed_insel>]()
+                                .clear_bit()
+                                .[<dt $num _red_outinvert>]()
+                                .bit(red_invert)
+                                .[<dt $num _fed_outinvert>]()
+                                .bit(fed_invert)
+                                .[<dt $num _a_outbypass>]()
+                                .bit(bypass)
+                                .[<dt $num _b_outbypass>]()
+                                .bit(bypass)
+                                .[<dt $num _clk_sel>]()
+                                .clear_bit()
+                        });
+                    }
+                }
+
+                fn set_faults(
+                    mcpwm: &mcpwm0::RegisterBlock,
+                    faults: &[config::FaultMode; 3],
+                    action_a: Action,
+                    action_b: Action,
+                ) {
+                    use config::FaultMode;
+
+                    let one_shot = |fault: usize| faults[fault] == FaultMode::OneShot;
+                    let cycle_by_cycle = |fault: usize| faults[fault] == FaultMode::CycleByCycle;
+                    let (a, b) = (action_a as u8, action_b as u8);
+
+                    paste::paste! {
+                        mcpwm.[<fh $num _cfg0>].write(|w| unsafe {
+                            w.[<fh $num _f0_ost>]()
+                                .bit(one_shot(0))
+                                .[<fh $num _f1_ost>]()
+                                .bit(one_shot(1))
+                                .[<fh $num _f2_ost>]()
+                                .bit(one_shot(2))
+                                .[<fh $num _f0_cbc>]()
+                                .bit(cycle_by_cycle(0))
+                                .[<fh $num _f1_cbc>]()
+                                .bit(cycle_by_cycle(1))
+                                .[<fh $num _f2_cbc>]()
+                                .bit(cycle_by_cycle(2))
+                                // the software one-shot fault is always enabled
+                                .[<fh $num _sw_ost>]()
+                                .set_bit()
+                                .[<fh $num _a_ost_u>]()
+                                .bits(a)
+                                .[<fh $num _a_ost_d>]()
+                                .bits(a)
+                                .[<fh $num _a_cbc_u>]()
+                                .bits(a)
+                                .[<fh $num _a_cbc_d>]()
+                                .bits(a)
+                                .[<fh $num _b_ost_u>]()
+                                .bits(b)
+                                .[<fh $num _b_ost_d>]()
+                                .bits(b)
+                                .[<fh $num _b_cbc_u>]()
+                                .bits(b)
+                                .[<fh $num _b_cbc_d>]()
+                                .bits(b)
+                        });
+                        // cycle-by-cycle faults end at zero
+                        mcpwm
+                            .[<fh $num _cfg1>]
+                            .write(|w| unsafe { w.[<fh $num _cbcpulse>]().bits(0b01) });
+                    }
+                }
+
+                fn force(mcpwm: &mcpwm0::RegisterBlock, b: bool, mode: u8) {
+                    paste::paste! {
+                        // continuous force, applied immediately
+                        if b {
+                            mcpwm.[<gen $num _force>].modify(|_, w| unsafe {
+                                w.[<gen $num _cntuforce_upmethod>]()
+                                    .bits(0)
+                                    .[<gen $num _b_cntuforce_mode>]()
+                                    .bits(mode)
+                            });
+                        } else {
+                            mcpwm.[<gen $num _force>].modify(|_, w| unsafe {
+                                w.[<gen $num _cntuforce_upmethod>]()
+                                    .bits(0)
+                                    .[<gen $num _a_cntuforce_mode>]()
+                                    .bits(mode)
+                            });
+                        }
+                    }
+                }
+
+                fn fault_status(mcpwm: &mcpwm0::RegisterBlock) -> (bool, bool) {
+                    paste::paste! {
+                        let status = mcpwm.[<fh $num _status>].read();
+                        (
+                            status.[<fh $num _ost_on>]().bit_is_set(),
+                            status.[<fh $num _cbc_on>]().bit_is_set(),
+                        )
+                    }
+                }
+
+                fn trigger_one_shot_fault(mcpwm: &mcpwm0::RegisterBlock) {
+                    paste::paste! {
+                        // a toggle of the bit triggers the fault
+                        mcpwm.[<fh $num _cfg1>].modify(|r, w| {
+                            w.[<fh $num _force_ost>]()
+                                .bit(!r.[<fh $num _force_ost>]().bit())
+                        });
+                    }
+                }
+
+                fn clear_one_shot_fault(mcpwm: &mcpwm0::RegisterBlock) {
+                    paste::paste! {
+                        // a rising edge clears the fault
+                        mcpwm
+                            .[<fh $num _cfg1>]
+                            .modify(|_, w| w.[<fh $num _clr_ost>]().set_bit());
+                        mcpwm
+                            .[<fh $num _cfg1>]
+                            .modify(|_, w| w.[<fh $num _clr_ost>]().clear_bit());
+                    }
+                }
+            }
+        )+
+    };
+}
+
+operators! {
+    Operator0: 0,
+    Operator1: 1,
+    Operator2: 2,
+}
+
+/// Lock for the registers shared by the timers and operators of both units
+static UNIT_LOCK: CriticalSectionSpinLockMutex<()> = CriticalSectionSpinLockMutex::new(());
+
+impl Instance for MCPWM0 {
+    const PERIPHERAL: crate::dport::Peripheral = crate::dport::Peripheral::PWM0;
+    const OUTPUT_SIGNALS: [[OutputSignal; 2]; 3] = [
+        [OutputSignal::PWM0_0A, OutputSignal::PWM0_0B],
+        [OutputSignal::PWM0_1A, OutputSignal::PWM0_1B],
+        [OutputSignal::PWM0_2A, OutputSignal::PWM0_2B],
+    ];
+    const FAULT_SIGNALS: [InputSignal; 3] = [
+        InputSignal::PWM0_F0,
+        InputSignal::PWM0_F1,
+        InputSignal::PWM0_F2,
+    ];
+    const SYNC_SIGNALS: [InputSignal; 3] = [
+        InputSignal::PWM0_SYNC0,
+        InputSignal::PWM0_SYNC1,
+        InputSignal::PWM0_SYNC2,
+    ];
+    const CAPTURE_SIGNALS: [InputSignal; 3] = [
+        InputSignal::PWM0_CAP0,
+        InputSignal::PWM0_CAP1,
+        InputSignal::PWM0_CAP2,
+    ];
+
+    fn register_block() -> &'static mcpwm0::RegisterBlock {
+        unsafe { &*MCPWM0::ptr() }
+    }
+}
+
+impl Instance for MCPWM1 {
+    const PERIPHERAL: crate::dport::Peripheral = crate::dport::Peripheral::PWM1;
+    const OUTPUT_SIGNALS: [[OutputSignal; 2]; 3] = [
+        [OutputSignal::PWM1_0A, OutputSignal::PWM1_0B],
+        [OutputSignal::PWM1_1A, OutputSignal::PWM1_1B],
+        [OutputSignal::PWM1_2A, OutputSignal::PWM1_2B],
+    ];
+    const FAULT_SIGNALS: [InputSignal; 3] = [
+        InputSignal::PWM1_F0,
+        InputSignal::PWM1_F1,
+        InputSignal::PWM1_F2,
+    ];
+    const SYNC_SIGNALS: [InputSignal; 3] = [
+        InputSignal::PWM1_SYNC0,
+        InputSignal::PWM1_SYNC1,
+        InputSignal::PWM1_SYNC2,
+    ];
+    const CAPTURE_SIGNALS: [InputSignal; 3] = [
+        InputSignal::PWM1_CAP0,
+        InputSignal::PWM1_CAP1,
+        InputSignal::PWM1_CAP2,
+    ];
+
+    fn register_block() -> &'static mcpwm0::RegisterBlock {
+        unsafe { &*MCPWM1::ptr() }
+    }
+}
+
+mod private {
+    use super::{config, Action};
+    use crate::gpio::{InputSignal, OutputSignal};
+    use crate::target::mcpwm0;
+
+    pub trait Instance {
+        /// Peripheral for clock enable and reset
+        const PERIPHERAL: crate::dport::Peripheral;
+        /// Output matrix signals of the operators (A, B)
+        const OUTPUT_SIGNALS: [[OutputSignal; 2]; 3];
+        /// Input matrix signals of the fault inputs
+        const FAULT_SIGNALS: [InputSignal; 3];
+        /// Input matrix signals of the sync inputs
+        const SYNC_SIGNALS: [InputSignal; 3];
+        /// Input matrix signals of the capture channels
+        const CAPTURE_SIGNALS: [InputSignal; 3];
+
+        fn register_block() -> &'static mcpwm0::RegisterBlock;
+    }
+
+    pub trait TimerNumber {
+        const NUMBER: u8;
+
+        /// Write prescale, period and period update method
+        fn configure(mcpwm: &mcpwm0::RegisterBlock, prescale: u8, period: u16, update: u8);
+        fn period(mcpwm: &mcpwm0::RegisterBlock) -> u16;
+        fn period_update(mcpwm: &mcpwm0::RegisterBlock) -> u8;
+        /// Write start/stop mode and counting mode
+        fn set_mode(mcpwm: &mcpwm0::RegisterBlock, start: u8, mode: u8);
+        fn mode(mcpwm: &mcpwm0::RegisterBlock) -> u8;
+        fn value(mcpwm: &mcpwm0::RegisterBlock) -> u16;
+        fn set_sync(mcpwm: &mcpwm0::RegisterBlock, enable: bool, phase: u16, down: bool);
+        fn set_sync_input(mcpwm: &mcpwm0::RegisterBlock, input: u8);
+        fn set_sync_output(mcpwm: &mcpwm0::RegisterBlock, output: u8);
+        fn software_sync(mcpwm: &mcpwm0::RegisterBlock);
+    }
+
+    pub trait OperatorNumber {
+        const NUMBER: u8;
+
+        fn select_timer(mcpwm: &mcpwm0::RegisterBlock, timer: u8);
+        /// Period of the selected timer
+        fn timer_period(mcpwm: &mcpwm0::RegisterBlock) -> u16;
+        fn set_compare_update(mcpwm: &mcpwm0::RegisterBlock, update: u8);
+        fn set_compare(mcpwm: &mcpwm0::RegisterBlock, b: bool, value: u16);
+        fn compare(mcpwm: &mcpwm0::RegisterBlock, b: bool) -> u16;
+        fn set_generator(mcpwm: &mcpwm0::RegisterBlock, b: bool, bits: u32);
+
+        fn set_generators(mcpwm: &mcpwm0::RegisterBlock, bits_a: u32, bits_b: u32) {
+            Self::set_generator(mcpwm, false, bits_a);
+            Self::set_generator(mcpwm, true, bits_b);
+        }
+
+        fn set_dead_time(mcpwm: &mcpwm0::RegisterBlock, config: &config::DeadTimeConfig);
+        fn set_faults(
+            mcpwm: &mcpwm0::RegisterBlock,
+            faults: &[config::FaultMode; 3],
+            action_a: Action,
+            action_b: Action,
+        );
+        /// Continuous software force: 0 disabled, 1 low, 2 high
+        fn force(mcpwm: &mcpwm0::RegisterBlock, b: bool, mode: u8);
+        /// One-shot and cycle-by-cycle fault status
+        fn fault_status(mcpwm: &mcpwm0::RegisterBlock) -> (bool, bool);
+        fn trigger_one_shot_fault(mcpwm: &mcpwm0::RegisterBlock);
+        fn clear_one_shot_fault(mcpwm: &mcpwm0::RegisterBlock);
+    }
+
+    pub trait CaptureNumber {
+        const NUMBER: u8;
+
+        /// Write enable, edge mode, prescale and input inversion
+        fn configure(
+            mcpwm: &mcpwm0::RegisterBlock,
+            enable: bool,
+            mode: u8,
+            prescale: u8,
+            invert: bool,
+        );
+        fn value(mcpwm: &mcpwm0::RegisterBlock) -> u32;
+        fn is_falling_edge(mcpwm: &mcpwm0::RegisterBlock) -> bool;
+        fn software_capture(mcpwm: &mcpwm0::RegisterBlock);
+    }
+}
@@ -0,0 +1,162 @@
+//! Quadrature encoder
+//!
+//! Decodes the A and B signals of a rotary encoder with a pulse counter unit. The count is
+//! extended to 64 bit by accumulating the overflows of the 16 bit counter at the limits, which
+//! requires [QuadratureEncoder::update] to be called from the PCNT interrupt handler (or at
+//! least once per [LIMIT] counts):
+//! ```no_run
+//! let mut encoder = QuadratureEncoder::new(
+//!     units.unit0,
+//!     &mut pins.gpio18,
+//!     &mut pins.gpio19,
+//!     Decoding::X4,
+//!     Some(100),
+//! );
+//! interrupt::enable(Interrupt::PCNT_INTR).unwrap();
+//!
+//! #[interrupt]
+//! fn PCNT_INTR() {
+//!     encoder.update();
+//! }
+//!
+//! let position = encoder.value();
+//! ```
+//!
+//! The count increases when A leads B.
+
+use super::config::{ChannelConfig, Config, ControlMode, EdgeMode};
+use super::private::Instance;
+use super::{Channel, Event, PulseCounter};
+use crate::gpio::InputPin;
+
+/// Limit of the hardware counter, at which the count is moved to the extended count
+pub const LIMIT: i16 = i16::MAX;
+
+/// Edges counted per encoder cycle
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Decoding {
+    /// Rising edges of A
+    X1,
+    /// Both edges of A
+    X2,
+    /// Both edges of A and B
+    X4,
+}
+
+/// Quadrature encoder with 64 bit count
+pub struct QuadratureEncoder<UNIT: Instance> {
+    counter: PulseCounter<UNIT>,
+    decoding: Decoding,
+    overflow: i64,
+}
+
+impl<UNIT: Instance> QuadratureEncoder<UNIT> {
+    /// Configure the unit for quadrature decoding and start counting
+    ///
+    /// `filter` ignores pulses shorter than the given number of APB clock ticks.
+    pub fn new<A: InputPin, B: InputPin>(
+        unit: UNIT,
+        pin_a: &mut A,
+        pin_b: &mut B,
+        decoding: Decoding,
+        filter: Option<u16>,
+    ) -> Self {
+        // channel 0 counts the edges of A, reversed while B is high
+        let channel0 = ChannelConfig {
+            rising_edge: EdgeMode::Increment,
+            falling_edge: match decoding {
+                Decoding::X1 => EdgeMode::Hold,
+                Decoding::X2 | Decoding::X4 => EdgeMode::Decrement,
+            },
+            control_high: ControlMode::Reverse,
+            control_low: ControlMode::Keep,
+        };
+        // channel 1 counts the edges of B, reversed while A is high
+        let channel1 = match decoding {
+            Decoding::X4 => ChannelConfig {
+                rising_edge: EdgeMode::Decrement,
+                falling_edge: EdgeMode::Increment,
+                control_high: ControlMode::Reverse,
+                control_low: ControlMode::Keep,
+            },
+            Decoding::X1 | Decoding::X2 => ChannelConfig::disabled(),
+        };
+
+        let mut counter = PulseCounter::new(
+            unit,
+            Config::default()
+                .channel0(channel0)
+                .channel1(channel1)
+                .filter(filter)
+                .limits(-LIMIT, LIMIT),
+        );
+
+        counter.connect_pulse(Channel::Channel0, pin_a);
+        counter.connect_control(Channel::Channel0, pin_b);
+        counter.connect_pulse(Channel::Channel1, pin_b);
+        counter.connect_control(Channel::Channel1, pin_a);
+
+        counter.listen(Event::HighLimit);
+        counter.listen(Event::LowLimit);
+        counter.resume();
+
+        QuadratureEncoder {
+            counter,
+            decoding,
+            overflow: 0,
+        }
+    }
+
+    /// Move the overflow of the hardware counter to the extended count
+    ///
+    /// To be called from the PCNT interrupt handler. Returns true if an overflow was handled.
+    pub fn update(&mut self) -> bool {
+        if !self.counter.is_interrupt_set() {
+            return false;
+        }
+
+        let events = self.counter.events();
+        self.counter.clear_interrupt();
+
+        if events.contains(Event::HighLimit) {
+            self.overflow += LIMIT as i64;
+        } else if events.contains(Event::LowLimit) {
+            self.overflow -= LIMIT as i64;
+        }
+        true
+    }
+
+    /// Count since creation or the last [Self::reset]
+    pub fn value(&mut self) -> i64 {
+        loop {
+            self.update();
+            let count = self.counter.value();
+            // the counter is reset at a limit, so retry if this happened while reading
+            if !self.counter.is_interrupt_set() {
+                return self.overflow + count as i64;
+            }
+        }
+    }
+
+    /// Count truncated to 32 bit (wrapping)
+    pub fn value_i32(&mut self) -> i32 {
+        self.value() as i32
+    }
+
+    /// Set the count to 0
+    pub fn reset(&mut self) {
+        self.counter.clear();
+        self.counter.clear_interrupt();
+        self.overflow = 0;
+    }
+
+    /// Decoding mode
+    pub fn decoding(&self) -> Decoding {
+        self.decoding
+    }
+
+    /// Stop counting and release the unit
+    pub fn release(self) -> UNIT {
+        self.counter.release()
+    }
+}
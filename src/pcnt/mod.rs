@@ -0,0 +1,541 @@
+//! Pulse counter (PCNT)
+//!
+//! The ESP32 has 8 pulse counter units, each with a signed 16 bit counter and two channels.
+//! Each channel has a pulse input, whose rising and falling edges increment, decrement or hold
+//! the counter, and a control input, whose level can reverse or disable the counting of the
+//! channel. Inputs can be connected to any input pin via the GPIO matrix, unconnected inputs
+//! are held low.
+//!
+//! A unit generates events when the counter reaches zero, one of two thresholds or the high
+//! and low limit. At the limits the counter is reset to 0. Events can trigger the PCNT
+//! interrupt, which needs to be enabled with `interrupt::enable(Interrupt::PCNT_INTR)`.
+//!
+//! For rotary encoders [encoder::QuadratureEncoder] provides ready-made quadrature decoding
+//! with a 64 bit count.
+//!
+//! # Example:
+//! Count rising edges of a flow meter and interrupt every 1000 pulses:
+//! ```no_run
+//! let units = dp.PCNT.split();
+//!
+//! let mut counter = PulseCounter::new(
+//!     units.unit0,
+//!     pcnt::config::Config::default()
+//!         .channel0(pcnt::config::ChannelConfig::count_rising_edges())
+//!         .filter(Some(100))
+//!         .limits(-1, 1000),
+//! );
+//! counter.connect_pulse(pcnt::Channel::Channel0, &mut pins.gpio4);
+//! counter.listen(pcnt::Event::HighLimit);
+//! counter.resume();
+//! ```
+
+use crate::gpio::{self, InputPin, InputSignal};
+use crate::prelude::*;
+use crate::target::{pcnt, PCNT};
+use core::marker::PhantomData;
+
+use private::Instance;
+
+pub mod encoder;
+
+/// Maximum value of the glitch filter in APB clock ticks
+pub const MAX_FILTER: u16 = 1023;
+
+/// Lock for the control and interrupt enable registers shared by all units
+static LOCK: CriticalSectionSpinLockMutex<()> = CriticalSectionSpinLockMutex::new(());
+
+/// Pulse counter configuration
+pub mod config {
+    use super::MAX_FILTER;
+
+    /// Counter action on an edge of the pulse input
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum EdgeMode {
+        Hold = 0,
+        Increment = 1,
+        Decrement = 2,
+    }
+
+    /// Modification of the edge mode by a level of the control input
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum ControlMode {
+        /// Count as configured by the edge mode
+        Keep = 0,
+        /// Increment instead of decrement and vice versa
+        Reverse = 1,
+        /// Do not count
+        Disable = 2,
+    }
+
+    /// Channel configuration
+    #[derive(Copy, Clone, Debug)]
+    pub struct ChannelConfig {
+        pub rising_edge: EdgeMode,
+        pub falling_edge: EdgeMode,
+        /// Mode while the control input is high
+        pub control_high: ControlMode,
+        /// Mode while the control input is low
+        pub control_low: ControlMode,
+    }
+
+    impl ChannelConfig {
+        /// Channel which does not count
+        pub fn disabled() -> Self {
+            ChannelConfig {
+                rising_edge: EdgeMode::Hold,
+                falling_edge: EdgeMode::Hold,
+                control_high: ControlMode::Keep,
+                control_low: ControlMode::Keep,
+            }
+        }
+
+        /// Increment on rising edges, independent of the control input
+        pub fn count_rising_edges() -> Self {
+            ChannelConfig {
+                rising_edge: EdgeMode::Increment,
+                ..Self::disabled()
+            }
+        }
+
+        /// Bits of the channel in the `conf0` register
+        pub(super) fn bits(&self) -> u32 {
+            self.falling_edge as u32
+                | (self.rising_edge as u32) << 2
+                | (self.control_high as u32) << 4
+                | (self.control_low as u32) << 6
+        }
+    }
+
+    impl Default for ChannelConfig {
+        fn default() -> ChannelConfig {
+            Self::disabled()
+        }
+    }
+
+    /// Pulse counter unit configuration
+    #[derive(Copy, Clone, Debug)]
+    pub struct Config {
+        pub channel0: ChannelConfig,
+        pub channel1: ChannelConfig,
+        /// Ignore pulses shorter than the given number of APB clock ticks (max. [MAX_FILTER])
+        pub filter: Option<u16>,
+        /// Low limit, the counter is reset to 0 when reaching it
+        pub low_limit: i16,
+        /// High limit, the counter is reset to 0 when reaching it
+        pub high_limit: i16,
+        pub threshold0: i16,
+        pub threshold1: i16,
+    }
+
+    impl Config {
+        pub fn channel0(mut self, channel0: ChannelConfig) -> Self {
+            self.channel0 = channel0;
+            self
+        }
+
+        pub fn channel1(mut self, channel1: ChannelConfig) -> Self {
+            self.channel1 = channel1;
+            self
+        }
+
+        pub fn filter(mut self, filter: Option<u16>) -> Self {
+            self.filter = filter.map(|filter| core::cmp::min(filter, MAX_FILTER));
+            self
+        }
+
+        pub fn limits(mut self, low_limit: i16, high_limit: i16) -> Self {
+            self.low_limit = low_limit;
+            self.high_limit = high_limit;
+            self
+        }
+
+        pub fn thresholds(mut self, threshold0: i16, threshold1: i16) -> Self {
+            self.threshold0 = threshold0;
+            self.threshold1 = threshold1;
+            self
+        }
+    }
+
+    impl Default for Config {
+        fn default() -> Config {
+            Config {
+                channel0: ChannelConfig::disabled(),
+                channel1: ChannelConfig::disabled(),
+                filter: None,
+                low_limit: i16::MIN,
+                high_limit: i16::MAX,
+                threshold0: 0,
+                threshold1: 0,
+            }
+        }
+    }
+}
+
+/// Channel of a pulse counter unit
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Channel {
+    Channel0,
+    Channel1,
+}
+
+/// Events of a pulse counter unit
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Event {
+    /// The counter reached 0
+    Zero,
+    /// The counter reached the high limit (and was reset to 0)
+    HighLimit,
+    /// The counter reached the low limit (and was reset to 0)
+    LowLimit,
+    Threshold0,
+    Threshold1,
+}
+
+impl Event {
+    /// Bit enabling the event in the `conf0` register
+    fn enable_bit(&self) -> u32 {
+        match self {
+            Event::Zero => 1 << 11,
+            Event::HighLimit => 1 << 12,
+            Event::LowLimit => 1 << 13,
+            Event::Threshold0 => 1 << 14,
+            Event::Threshold1 => 1 << 15,
+        }
+    }
+
+    /// Bit of the event in the status register
+    fn status_bit(&self) -> u32 {
+        match self {
+            Event::Threshold1 => 1 << 2,
+            Event::Threshold0 => 1 << 3,
+            Event::LowLimit => 1 << 4,
+            Event::HighLimit => 1 << 5,
+            Event::Zero => 1 << 6,
+        }
+    }
+}
+
+/// Events which occurred at the last interrupt of a unit
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Events {
+    bits: u32,
+}
+
+impl Events {
+    /// Check if an event occurred
+    pub fn contains(&self, event: Event) -> bool {
+        self.bits & event.status_bit() != 0
+    }
+}
+
+/// Extension trait to split the PCNT peripheral into independent units
+pub trait PcntExt {
+    fn split(self) -> Units;
+}
+
+/// Pulse counter units
+pub struct Units {
+    pub unit0: Unit0,
+    pub unit1: Unit1,
+    pub unit2: Unit2,
+    pub unit3: Unit3,
+    pub unit4: Unit4,
+    pub unit5: Unit5,
+    pub unit6: Unit6,
+    pub unit7: Unit7,
+}
+
+impl PcntExt for PCNT {
+    fn split(self) -> Units {
+        dport::enable_peripheral(Peripheral::PCNT);
+        dport::reset_peripheral(Peripheral::PCNT);
+
+        Units {
+            unit0: Unit0 {
+                _private: PhantomData,
+            },
+            unit1: Unit1 {
+                _private: PhantomData,
+            },
+            unit2: Unit2 {
+                _private: PhantomData,
+            },
+            unit3: Unit3 {
+                _private: PhantomData,
+            },
+            unit4: Unit4 {
+                _private: PhantomData,
+            },
+            unit5: Unit5 {
+                _private: PhantomData,
+            },
+            unit6: Unit6 {
+                _private: PhantomData,
+            },
+            unit7: Unit7 {
+                _private: PhantomData,
+            },
+        }
+    }
+}
+
+/// Pulse counter unit
+///
+/// The counter is paused after creation and needs to be started with [PulseCounter::resume].
+pub struct PulseCounter<UNIT: Instance> {
+    unit: UNIT,
+}
+
+impl<UNIT: Instance> PulseCounter<UNIT> {
+    /// Configure the unit, the counter is cleared and paused
+    pub fn new(unit: UNIT, config: config::Config) -> Self {
+        let mut counter = PulseCounter { unit };
+        counter.pause();
+        counter.configure(config);
+        counter.clear();
+        counter
+    }
+
+    /// Change the configuration, the enabled events are kept
+    pub fn configure(&mut self, config: config::Config) {
+        let events = UNIT::conf0() & 0xf800;
+        let (filter_enable, filter) = match config.filter {
+            Some(filter) => (1 << 10, core::cmp::min(filter, MAX_FILTER) as u32),
+            None => (0, 0),
+        };
+
+        UNIT::set_conf0(
+            filter
+                | filter_enable
+                | events
+                | config.channel0.bits() << 16
+                | config.channel1.bits() << 24,
+        );
+        UNIT::set_conf1(config.threshold0 as u16 as u32 | (config.threshold1 as u16 as u32) << 16);
+        UNIT::set_conf2(config.high_limit as u16 as u32 | (config.low_limit as u16 as u32) << 16);
+    }
+
+    /// Connect a pin to the pulse input of a channel
+    pub fn connect_pulse<PIN: InputPin>(&mut self, channel: Channel, pin: &mut PIN) {
+        pin.set_to_input()
+            .connect_input_to_peripheral(UNIT::SIGNALS[channel as usize][0]);
+    }
+
+    /// Connect a pin to the control input of a channel
+    pub fn connect_control<PIN: InputPin>(&mut self, channel: Channel, pin: &mut PIN) {
+        pin.set_to_input()
+            .connect_input_to_peripheral(UNIT::SIGNALS[channel as usize][1]);
+    }
+
+    /// Disconnect the pulse input of a channel, the input is held low
+    pub fn disconnect_pulse(&mut self, channel: Channel) {
+        gpio::connect_low_to_peripheral(UNIT::SIGNALS[channel as usize][0]);
+    }
+
+    /// Disconnect the control input of a channel, the input is held low
+    pub fn disconnect_control(&mut self, channel: Channel) {
+        gpio::connect_low_to_peripheral(UNIT::SIGNALS[channel as usize][1]);
+    }
+
+    /// Current counter value
+    pub fn value(&self) -> i16 {
+        UNIT::count()
+    }
+
+    /// Reset the counter to 0
+    pub fn clear(&mut self) {
+        let reset = 1 << (2 * UNIT::NUMBER);
+        (&LOCK).lock(|_| {
+            let pcnt = unsafe { &*PCNT::ptr() };
+            pcnt.ctrl.modify(|r, w| unsafe { w.bits(r.bits() | reset) });
+            pcnt.ctrl
+                .modify(|r, w| unsafe { w.bits(r.bits() & !reset) });
+        });
+    }
+
+    /// Stop counting, the value is kept
+    pub fn pause(&mut self) {
+        self.set_pause(true);
+    }
+
+    /// Start counting
+    pub fn resume(&mut self) {
+        self.set_pause(false);
+    }
+
+    /// Set the glitch filter in APB clock ticks (max. [MAX_FILTER]), `None` to disable it
+    pub fn set_filter(&mut self, filter: Option<u16>) {
+        let conf0 = UNIT::conf0() & !0x7ff;
+        UNIT::set_conf0(match filter {
+            Some(filter) => conf0 | 1 << 10 | core::cmp::min(filter, MAX_FILTER) as u32,
+            None => conf0,
+        });
+    }
+
+    /// Set the low and high limit, at which the counter is reset to 0
+    pub fn set_limits(&mut self, low_limit: i16, high_limit: i16) {
+        UNIT::set_conf2(high_limit as u16 as u32 | (low_limit as u16 as u32) << 16);
+    }
+
+    /// Set the thresholds generating the [Event::Threshold0] and [Event::Threshold1] events
+    pub fn set_thresholds(&mut self, threshold0: i16, threshold1: i16) {
+        UNIT::set_conf1(threshold0 as u16 as u32 | (threshold1 as u16 as u32) << 16);
+    }
+
+    /// Start listening for an [Event]
+    pub fn listen(&mut self, event: Event) {
+        UNIT::set_conf0(UNIT::conf0() | event.enable_bit());
+        self.enable_interrupt(true);
+    }
+
+    /// Stop listening for an [Event]
+    ///
+    /// The interrupt of the unit is disabled when no event is left.
+    pub fn unlisten(&mut self, event: Event) {
+        let conf0 = UNIT::conf0() & !event.enable_bit();
+        UNIT::set_conf0(conf0);
+        if conf0 & 0xf800 == 0 {
+            self.enable_interrupt(false);
+        }
+    }
+
+    /// Check if an event of the unit is pending
+    pub fn is_interrupt_set(&self) -> bool {
+        unsafe { &*PCNT::ptr() }.int_raw.read().bits() & (1 << UNIT::NUMBER) != 0
+    }
+
+    /// Clear the interrupt of the unit
+    pub fn clear_interrupt(&mut self) {
+        unsafe { &*PCNT::ptr() }
+            .int_clr
+            .write(|w| unsafe { w.bits(1 << UNIT::NUMBER) });
+    }
+
+    /// Events which triggered the last interrupt
+    pub fn events(&self) -> Events {
+        Events {
+            bits: UNIT::status(),
+        }
+    }
+
+    /// Pause the counter and release the unit
+    pub fn release(mut self) -> UNIT {
+        self.pause();
+        self.enable_interrupt(false);
+        self.unit
+    }
+
+    fn set_pause(&mut self, pause: bool) {
+        let bit = 1 << (2 * UNIT::NUMBER + 1);
+        // shared register without set/clear functionality, so needs lock
+        (&LOCK).lock(|_| {
+            unsafe { &*PCNT::ptr() }.ctrl.modify(|r, w| unsafe {
+                w.bits(if pause {
+                    r.bits() | bit
+                } else {
+                    r.bits() & !bit
+                })
+            })
+        });
+    }
+
+    fn enable_interrupt(&mut self, enable: bool) {
+        let bit = 1 << UNIT::NUMBER;
+        // shared register without set/clear functionality, so needs lock
+        (&LOCK).lock(|_| {
+            unsafe { &*PCNT::ptr() }.int_ena.modify(|r, w| unsafe {
+                w.bits(if enable {
+                    r.bits() | bit
+                } else {
+                    r.bits() & !bit
+                })
+            })
+        });
+    }
+}
+
+macro_rules! units {
+    ($($unit:ident: ($num:literal, $sig0:ident, $ctrl0:ident, $sig1:ident, $ctrl1:ident),)+) => {
+        $(
+            /// Pulse counter unit
+            pub struct $unit {
+                _private: PhantomData<()>,
+            }
+
+            impl Instance for $unit {
+                const NUMBER: u8 = $num;
+                const SIGNALS: [[InputSignal; 2]; 2] = [
+                    [InputSignal::$sig0, InputSignal::$ctrl0],
+                    [InputSignal::$sig1, InputSignal::$ctrl1],
+                ];
+
+                fn register_block() -> &'static pcnt::RegisterBlock {
+                    unsafe { &*PCNT::ptr() }
+                }
+
+                fn conf0() -> u32 {
+                    paste::paste! { Self::register_block().[<u $num _conf0>].read().bits() }
+                }
+
+                fn set_conf0(bits: u32) {
+                    paste::paste! {
+                        Self::register_block().[<u $num _conf0>].write(|w| unsafe { w.bits(bits) })
+                    }
+                }
+
+                fn set_conf1(bits: u32) {
+                    paste::paste! {
+                        Self::register_block().[<u $num _conf1>].write(|w| unsafe { w.bits(bits) })
+                    }
+                }
+
+                fn set_conf2(bits: u32) {
+                    paste::paste! {
+                        Self::register_block().[<u $num _conf2>].write(|w| unsafe { w.bits(bits) })
+                    }
+                }
+
+                fn count() -> i16 {
+                    paste::paste! { Self::register_block().[<u $num _cnt>].read().bits() as u16 as i16 }
+                }
+
+                fn status() -> u32 {
+                    paste::paste! { Self::register_block().[<u $num _status>].read().bits() }
+                }
+            }
+        )+
+    };
+}
+
+units! {
+    Unit0: (0, PCNT_SIG_CH0_0, PCNT_CTRL_CH0_0, PCNT_SIG_CH1_0, PCNT_CTRL_CH1_0),
+    Unit1: (1, PCNT_SIG_CH0_1, PCNT_CTRL_CH0_1, PCNT_SIG_CH1_1, PCNT_CTRL_CH1_1),
+    Unit2: (2, PCNT_SIG_CH0_2, PCNT_CTRL_CH0_2, PCNT_SIG_CH1_2, PCNT_CTRL_CH1_2),
+    Unit3: (3, PCNT_SIG_CH0_3, PCNT_CTRL_CH0_3, PCNT_SIG_CH1_3, PCNT_CTRL_CH1_3),
+    Unit4: (4, PCNT_SIG_CH0_4, PCNT_CTRL_CH0_4, PCNT_SIG_CH1_4, PCNT_CTRL_CH1_4),
+    Unit5: (5, PCNT_SIG_CH0_5, PCNT_CTRL_CH0_5, PCNT_SIG_CH1_5, PCNT_CTRL_CH1_5),
+    Unit6: (6, PCNT_SIG_CH0_6, PCNT_CTRL_CH0_6, PCNT_SIG_CH1_6, PCNT_CTRL_CH1_6),
+    Unit7: (7, PCNT_SIG_CH0_7, PCNT_CTRL_CH0_7, PCNT_SIG_CH1_7, PCNT_CTRL_CH1_7),
+}
+
+mod private {
+    use crate::gpio::InputSignal;
+    use crate::target::pcnt;
+
+    pub trait Instance {
+        const NUMBER: u8;
+        /// Input matrix signals of the channels (pulse, control)
+        const SIGNALS: [[InputSignal; 2]; 2];
+
+        fn register_block() -> &'static pcnt::RegisterBlock;
+        fn conf0() -> u32;
+        fn set_conf0(bits: u32);
+        /// Write thresholds 0 (bits 0-15) and 1 (bits 16-31)
+        fn set_conf1(bits: u32);
+        /// Write high limit (bits 0-15) and low limit (bits 16-31)
+        fn set_conf2(bits: u32);
+        fn count() -> i16;
+        fn status() -> u32;
+    }
+}
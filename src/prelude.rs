@@ -12,6 +12,10 @@ pub use xtensa_lx_rt::{entry, exception};
 pub use crate::analog::SensExt;
 pub use crate::dport::{self, Peripheral};
 pub use crate::gpio::GpioExt;
+pub use crate::mcpwm::McpwmExt;
+pub use crate::pcnt::PcntExt;
+pub use crate::rmt::RmtExt;
+pub use crate::sigma_delta::SigmaDeltaExt;
 pub use crate::interrupt;
 pub use crate::proc_macros::*;
 pub use crate::units::*;
@@ -0,0 +1,613 @@
+//! RMT (Remote Control) peripheral
+//!
+//! The RMT has 8 channels, which can transmit or receive sequences of pulses with a precise
+//! duration. The pulses are stored as [Item]s, each containing two level/duration pairs, in
+//! 8 RAM blocks of 64 items. Channel n uses block n by default and can use the blocks of the
+//! following channels for longer sequences, which then can not be used.
+//!
+//! - [tx::Tx] transmits items with optional carrier modulation, once or in a loop. Sequences
+//!   longer than the RAM are refilled from an iterator while transmitting.
+//! - [rx::Rx] receives items until the input is idle for a configurable time, with a glitch
+//!   filter.
+//! - [ws2812::Ws2812] drives WS2812/SK6812 smart LED strips (with the `smart-leds` feature).
+//!
+//! The channel clock is the APB clock or REF_TICK divided by a divider derived from the
+//! requested tick frequency. When using the APB clock, the channel holds a
+//! [LockAPB][crate::clock_control::dfs::LockAPB] to keep the frequency constant.
+//!
+//! # Example:
+//! Send a NEC IR frame with 38kHz carrier:
+//! ```no_run
+//! let channels = dp.RMT.split();
+//!
+//! let mut tx = Tx::new(
+//!     channels.channel0,
+//!     pins.gpio4,
+//!     clock_control_config,
+//!     rmt::config::TxConfig::default()
+//!         .frequency(1.MHz().into())
+//!         .carrier(Some(rmt::config::CarrierConfig::new(38.kHz().into()))),
+//! )
+//! .unwrap();
+//!
+//! tx.write(&[
+//!     Item::new(true, 9000, false, 4500),
+//!     // ...
+//! ])
+//! .unwrap();
+//! ```
+
+use crate::clock_control::{dfs::LockAPB, ClockControlConfig};
+use crate::gpio::{InputSignal, OutputSignal};
+use crate::prelude::*;
+use crate::target::{rmt, RMT};
+use core::marker::PhantomData;
+
+use private::Instance;
+
+pub mod rx;
+pub mod tx;
+#[cfg(feature = "smart-leds")]
+pub mod ws2812;
+
+/// Number of items in a RAM block
+pub const BLOCK_SIZE: usize = 64;
+
+/// Maximum duration of an item part in channel clock ticks
+pub const MAX_DURATION: u16 = 0x7fff;
+
+/// Start address of the RMT RAM
+const RAM_BASE: usize = 0x3ff5_6800;
+
+/// Lock for the registers shared by all channels
+static LOCK: CriticalSectionSpinLockMutex<()> = CriticalSectionSpinLockMutex::new(());
+
+/// RAM blocks in use, bit n for block n
+static MEMORY: CriticalSectionSpinLockMutex<u8> = CriticalSectionSpinLockMutex::new(0);
+
+/// RMT errors
+#[derive(Debug)]
+pub enum Error {
+    /// The tick frequency can not be reached with the 8 bit divider
+    Divider,
+    /// The number of RAM blocks is 0 or exceeds the last block
+    MemoryBlocks,
+    /// A RAM block is used by another channel
+    MemoryInUse,
+    /// The carrier frequency or duty can not be reached
+    Carrier,
+    /// A duration is 0 or exceeds [MAX_DURATION] channel clock ticks
+    Duration,
+    /// The sequence does not fit in the RAM (for looped transmission)
+    TooLong,
+    /// More items were received than fit in the RAM
+    Overflow,
+    /// The buffer is too small for the received items
+    BufferTooSmall,
+}
+
+/// RMT configuration
+pub mod config {
+    use crate::units::*;
+
+    /// Source of the channel clock
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum ClockSource {
+        /// APB clock, the APB frequency is locked while the channel is used
+        APBClk,
+        /// REF_TICK, which is only independent of DFS when the reference clock is stable
+        RefTick,
+    }
+
+    /// Carrier modulation of the high (or low) level of the transmitted items
+    #[derive(Copy, Clone, Debug)]
+    pub struct CarrierConfig {
+        pub frequency: Hertz,
+        /// Duty of the carrier (0.0 to 1.0)
+        pub duty: f32,
+        /// Modulate the high level (true) or the low level (false)
+        pub level: bool,
+    }
+
+    impl CarrierConfig {
+        /// Carrier with 50% duty on the high level
+        pub fn new(frequency: Hertz) -> Self {
+            CarrierConfig {
+                frequency,
+                duty: 0.5,
+                level: true,
+            }
+        }
+
+        pub fn duty(mut self, duty: f32) -> Self {
+            self.duty = duty;
+            self
+        }
+
+        pub fn level(mut self, level: bool) -> Self {
+            self.level = level;
+            self
+        }
+    }
+
+    /// Transmit channel configuration
+    #[derive(Copy, Clone, Debug)]
+    pub struct TxConfig {
+        pub clock_source: ClockSource,
+        /// Frequency of the channel clock, the unit of the item durations
+        pub frequency: Hertz,
+        /// Number of RAM blocks of 64 items
+        pub memory_blocks: u8,
+        pub carrier: Option<CarrierConfig>,
+        /// Output level while idle, `None` to output the level of the last item
+        pub idle_level: Option<bool>,
+    }
+
+    impl TxConfig {
+        pub fn clock_source(mut self, clock_source: ClockSource) -> Self {
+            self.clock_source = clock_source;
+            self
+        }
+
+        pub fn frequency(mut self, frequency: Hertz) -> Self {
+            self.frequency = frequency;
+            self
+        }
+
+        pub fn memory_blocks(mut self, memory_blocks: u8) -> Self {
+            self.memory_blocks = memory_blocks;
+            self
+        }
+
+        pub fn carrier(mut self, carrier: Option<CarrierConfig>) -> Self {
+            self.carrier = carrier;
+            self
+        }
+
+        pub fn idle_level(mut self, idle_level: Option<bool>) -> Self {
+            self.idle_level = idle_level;
+            self
+        }
+    }
+
+    impl Default for TxConfig {
+        /// 1MHz channel clock from the APB clock, one RAM block, idle low
+        fn default() -> TxConfig {
+            TxConfig {
+                clock_source: ClockSource::APBClk,
+                frequency: Hertz(1_000_000),
+                memory_blocks: 1,
+                carrier: None,
+                idle_level: Some(false),
+            }
+        }
+    }
+
+    /// Receive channel configuration
+    #[derive(Copy, Clone, Debug)]
+    pub struct RxConfig {
+        pub clock_source: ClockSource,
+        /// Frequency of the channel clock, the unit of the item durations
+        pub frequency: Hertz,
+        /// Number of RAM blocks of 64 items
+        pub memory_blocks: u8,
+        /// Reception ends when the input does not change for this number of channel ticks
+        pub idle_threshold: u16,
+        /// Ignore pulses shorter than the given number of APB clock ticks
+        pub filter: Option<u8>,
+    }
+
+    impl RxConfig {
+        pub fn clock_source(mut self, clock_source: ClockSource) -> Self {
+            self.clock_source = clock_source;
+            self
+        }
+
+        pub fn frequency(mut self, frequency: Hertz) -> Self {
+            self.frequency = frequency;
+            self
+        }
+
+        pub fn memory_blocks(mut self, memory_blocks: u8) -> Self {
+            self.memory_blocks = memory_blocks;
+            self
+        }
+
+        pub fn idle_threshold(mut self, idle_threshold: u16) -> Self {
+            self.idle_threshold = idle_threshold;
+            self
+        }
+
+        pub fn filter(mut self, filter: Option<u8>) -> Self {
+            self.filter = filter;
+            self
+        }
+    }
+
+    impl Default for RxConfig {
+        /// 1MHz channel clock from the APB clock, one RAM block, 10ms idle threshold
+        fn default() -> RxConfig {
+            RxConfig {
+                clock_source: ClockSource::APBClk,
+                frequency: Hertz(1_000_000),
+                memory_blocks: 1,
+                idle_threshold: 10_000,
+                filter: Some(100),
+            }
+        }
+    }
+}
+
+/// Pair of pulses as stored in the RMT RAM
+///
+/// An item with a duration of 0 marks the end of a sequence.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Item(u32);
+
+impl Item {
+    /// End of a sequence
+    pub const END: Item = Item(0);
+
+    /// Create an item from two level/duration pairs (durations in channel clock ticks, max.
+    /// [MAX_DURATION])
+    pub fn new(level0: bool, duration0: u16, level1: bool, duration1: u16) -> Self {
+        Item(
+            (duration0 & MAX_DURATION) as u32
+                | (level0 as u32) << 15
+                | ((duration1 & MAX_DURATION) as u32) << 16
+                | (level1 as u32) << 31,
+        )
+    }
+
+    /// Create an item from the raw RAM value
+    pub fn from_bits(bits: u32) -> Self {
+        Item(bits)
+    }
+
+    /// Raw RAM value
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn level0(&self) -> bool {
+        self.0 & (1 << 15) != 0
+    }
+
+    pub fn duration0(&self) -> u16 {
+        self.0 as u16 & MAX_DURATION
+    }
+
+    pub fn level1(&self) -> bool {
+        self.0 & (1 << 31) != 0
+    }
+
+    pub fn duration1(&self) -> u16 {
+        (self.0 >> 16) as u16 & MAX_DURATION
+    }
+
+    /// Check if the item ends a sequence
+    pub fn is_end(&self) -> bool {
+        self.duration0() == 0 || self.duration1() == 0
+    }
+}
+
+/// Extension trait to split the RMT peripheral into independent channels
+pub trait RmtExt {
+    fn split(self) -> Channels;
+}
+
+/// RMT channels
+pub struct Channels {
+    pub channel0: Channel0,
+    pub channel1: Channel1,
+    pub channel2: Channel2,
+    pub channel3: Channel3,
+    pub channel4: Channel4,
+    pub channel5: Channel5,
+    pub channel6: Channel6,
+    pub channel7: Channel7,
+}
+
+impl RmtExt for RMT {
+    fn split(self) -> Channels {
+        dport::enable_peripheral(Peripheral::RMT);
+        dport::reset_peripheral(Peripheral::RMT);
+
+        // direct RAM access instead of FIFO and wrap around at the end of the RAM of a channel
+        self.apb_conf.write(|w| unsafe { w.bits(0b11) });
+
+        Channels {
+            channel0: Channel0 {
+                _private: PhantomData,
+            },
+            channel1: Channel1 {
+                _private: PhantomData,
+            },
+            channel2: Channel2 {
+                _private: PhantomData,
+            },
+            channel3: Channel3 {
+                _private: PhantomData,
+            },
+            channel4: Channel4 {
+                _private: PhantomData,
+            },
+            channel5: Channel5 {
+                _private: PhantomData,
+            },
+            channel6: Channel6 {
+                _private: PhantomData,
+            },
+            channel7: Channel7 {
+                _private: PhantomData,
+            },
+        }
+    }
+}
+
+/// conf0: clock divider (bits 0-7)
+const CONF0_DIV_CNT: u32 = 0xff;
+/// conf0: idle threshold (bits 8-23)
+const CONF0_IDLE_THRES_SHIFT: u32 = 8;
+/// conf0: number of RAM blocks (bits 24-27)
+const CONF0_MEM_SIZE_SHIFT: u32 = 24;
+/// conf0: carrier enable
+const CONF0_CARRIER_EN: u32 = 1 << 28;
+/// conf0: level modulated by the carrier
+const CONF0_CARRIER_OUT_LV: u32 = 1 << 29;
+/// conf0: clock enable
+const CONF0_CLK_EN: u32 = 1 << 31;
+
+/// conf1: start transmission
+const CONF1_TX_START: u32 = 1 << 0;
+/// conf1: enable reception
+const CONF1_RX_EN: u32 = 1 << 1;
+/// conf1: reset the write (receive) pointer
+const CONF1_MEM_WR_RST: u32 = 1 << 2;
+/// conf1: reset the read (transmit) pointer
+const CONF1_MEM_RD_RST: u32 = 1 << 3;
+/// conf1: RAM owned by the receiver
+const CONF1_MEM_OWNER: u32 = 1 << 5;
+/// conf1: repeat the transmission
+const CONF1_TX_CONTI_MODE: u32 = 1 << 6;
+/// conf1: enable the receive filter
+const CONF1_RX_FILTER_EN: u32 = 1 << 7;
+/// conf1: receive filter threshold (bits 8-15)
+const CONF1_RX_FILTER_THRES_SHIFT: u32 = 8;
+/// conf1: use the APB clock instead of REF_TICK
+const CONF1_REF_ALWAYS_ON: u32 = 1 << 17;
+/// conf1: idle output level
+const CONF1_IDLE_OUT_LV: u32 = 1 << 18;
+/// conf1: enable the idle output level
+const CONF1_IDLE_OUT_EN: u32 = 1 << 19;
+
+/// Interrupts of a channel
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+enum Interrupt {
+    TxEnd,
+    RxEnd,
+    Error,
+    TxThreshold,
+}
+
+impl Interrupt {
+    /// Bit of the interrupt of channel `number` in the interrupt registers
+    fn bit(&self, number: u8) -> u32 {
+        match self {
+            Interrupt::TxEnd => 1 << (3 * number),
+            Interrupt::RxEnd => 1 << (3 * number + 1),
+            Interrupt::Error => 1 << (3 * number + 2),
+            Interrupt::TxThreshold => 1 << (24 + number),
+        }
+    }
+}
+
+/// Clock and RAM settings of a configured channel
+struct ChannelState {
+    _lock: Option<LockAPB>,
+    source_frequency: Hertz,
+    frequency: Hertz,
+    memory_blocks: u8,
+}
+
+impl ChannelState {
+    /// Allocate the RAM blocks and configure the clock of a channel
+    fn new<CH: Instance>(
+        clock_control_config: ClockControlConfig,
+        clock_source: config::ClockSource,
+        frequency: Hertz,
+        memory_blocks: u8,
+    ) -> Result<Self, Error> {
+        if memory_blocks == 0 || CH::NUMBER + memory_blocks > 8 {
+            return Err(Error::MemoryBlocks);
+        }
+
+        let (lock, source_frequency) = match clock_source {
+            config::ClockSource::APBClk => {
+                let lock = clock_control_config.lock_apb_frequency();
+                (Some(lock), clock_control_config.apb_frequency_apb_locked())
+            }
+            config::ClockSource::RefTick => (None, clock_control_config.ref_frequency()),
+        };
+
+        let source: u32 = source_frequency.into();
+        let requested: u32 = frequency.into();
+        if requested == 0 {
+            return Err(Error::Divider);
+        }
+        let divider = (source + requested / 2) / requested;
+        if divider == 0 || divider > 256 {
+            return Err(Error::Divider);
+        }
+
+        let mask = (((1u32 << memory_blocks) - 1) << CH::NUMBER) as u8;
+        (&MEMORY).lock(|memory| {
+            if *memory & mask != 0 {
+                return Err(Error::MemoryInUse);
+            }
+            *memory |= mask;
+            Ok(())
+        })?;
+
+        // a divider of 256 is written as 0
+        CH::modify_conf0(
+            CONF0_DIV_CNT | 0xf << CONF0_MEM_SIZE_SHIFT | CONF0_CLK_EN,
+            divider & 0xff | (memory_blocks as u32) << CONF0_MEM_SIZE_SHIFT | CONF0_CLK_EN,
+        );
+        CH::modify_conf1(
+            CONF1_REF_ALWAYS_ON,
+            if clock_source == config::ClockSource::APBClk {
+                CONF1_REF_ALWAYS_ON
+            } else {
+                0
+            },
+        );
+
+        Ok(ChannelState {
+            _lock: lock,
+            source_frequency,
+            frequency: Hertz(source / divider),
+            memory_blocks,
+        })
+    }
+
+    /// Number of items in the RAM of the channel
+    fn capacity(&self) -> usize {
+        self.memory_blocks as usize * BLOCK_SIZE
+    }
+
+    /// Free the RAM blocks of the channel
+    fn release<CH: Instance>(self) {
+        let mask = (((1u32 << self.memory_blocks) - 1) << CH::NUMBER) as u8;
+        (&MEMORY).lock(|memory| *memory &= !mask);
+    }
+}
+
+/// Write an item to the RAM of a channel
+fn write_item<CH: Instance>(index: usize, item: Item) {
+    unsafe { core::ptr::write_volatile(ram::<CH>().add(index), item.0) };
+}
+
+/// Read an item from the RAM of a channel
+fn read_item<CH: Instance>(index: usize) -> Item {
+    Item(unsafe { core::ptr::read_volatile(ram::<CH>().add(index)) })
+}
+
+/// Start of the RAM of a channel
+fn ram<CH: Instance>() -> *mut u32 {
+    (RAM_BASE + CH::NUMBER as usize * BLOCK_SIZE * 4) as *mut u32
+}
+
+/// Check if an interrupt of a channel is set
+fn is_interrupt_set<CH: Instance>(interrupt: Interrupt) -> bool {
+    unsafe { &*RMT::ptr() }.int_raw.read().bits() & interrupt.bit(CH::NUMBER) != 0
+}
+
+/// Clear an interrupt of a channel
+fn clear_interrupt<CH: Instance>(interrupt: Interrupt) {
+    unsafe { &*RMT::ptr() }
+        .int_clr
+        .write(|w| unsafe { w.bits(interrupt.bit(CH::NUMBER)) });
+}
+
+/// Enable/disable an interrupt of a channel
+fn enable_interrupt<CH: Instance>(interrupt: Interrupt, enable: bool) {
+    let bit = interrupt.bit(CH::NUMBER);
+    // shared register without set/clear functionality, so needs lock
+    (&LOCK).lock(|_| {
+        unsafe { &*RMT::ptr() }.int_ena.modify(|r, w| unsafe {
+            w.bits(if enable {
+                r.bits() | bit
+            } else {
+                r.bits() & !bit
+            })
+        })
+    });
+}
+
+macro_rules! channels {
+    ($($channel:ident: ($num:literal, $out:ident, $in:ident),)+) => {
+        $(
+            /// RMT channel
+            pub struct $channel {
+                _private: PhantomData<()>,
+            }
+
+            impl Instance for $channel {
+                const NUMBER: u8 = $num;
+                const OUTPUT_SIGNAL: OutputSignal = OutputSignal::$out;
+                const INPUT_SIGNAL: InputSignal = InputSignal::$in;
+
+                fn register_block() -> &'static rmt::RegisterBlock {
+                    unsafe { &*RMT::ptr() }
+                }
+
+                fn modify_conf0(mask: u32, bits: u32) {
+                    paste::paste! {
+                        Self::register_block()
+                            .[<ch $num conf0>]
+                            .modify(|r, w| unsafe { w.bits(r.bits() & !mask | bits & mask) })
+                    }
+                }
+
+                fn modify_conf1(mask: u32, bits: u32) {
+                    paste::paste! {
+                        Self::register_block()
+                            .[<ch $num conf1>]
+                            .modify(|r, w| unsafe { w.bits(r.bits() & !mask | bits & mask) })
+                    }
+                }
+
+                fn set_carrier_duty(high: u16, low: u16) {
+                    paste::paste! {
+                        Self::register_block()
+                            .[<ch $num carrier_duty>]
+                            .write(|w| unsafe { w.bits(low as u32 | (high as u32) << 16) })
+                    }
+                }
+
+                fn set_tx_limit(limit: u16) {
+                    paste::paste! {
+                        Self::register_block()
+                            .[<ch $num _tx_lim>]
+                            .write(|w| unsafe { w.bits(limit as u32) })
+                    }
+                }
+            }
+        )+
+    };
+}
+
+channels! {
+    Channel0: (0, RMT_SIG_0, RMT_SIG_0),
+    Channel1: (1, RMT_SIG_1, RMT_SIG_1),
+    Channel2: (2, RMT_SIG_2, RMT_SIG_2),
+    Channel3: (3, RMT_SIG_3, RMT_SIG_3),
+    Channel4: (4, RMT_SIG_4, RMT_SIG_4),
+    Channel5: (5, RMT_SIG_5, RMT_SIG_5),
+    Channel6: (6, RMT_SIG_6, RMT_SIG_6),
+    Channel7: (7, RMT_SIG_7, RMT_SIG_7),
+}
+
+mod private {
+    use crate::gpio::{InputSignal, OutputSignal};
+    use crate::target::rmt;
+
+    pub trait Instance {
+        const NUMBER: u8;
+        /// Output matrix signal of the channel
+        const OUTPUT_SIGNAL: OutputSignal;
+        /// Input matrix signal of the channel
+        const INPUT_SIGNAL: InputSignal;
+
+        fn register_block() -> &'static rmt::RegisterBlock;
+        /// Replace the bits in `mask` of the `conf0` register
+        fn modify_conf0(mask: u32, bits: u32);
+        /// Replace the bits in `mask` of the `conf1` register
+        fn modify_conf1(mask: u32, bits: u32);
+        /// Carrier high and low time in source clock ticks
+        fn set_carrier_duty(high: u16, low: u16);
+        /// Number of transmitted items triggering the threshold interrupt
+        fn set_tx_limit(limit: u16);
+    }
+}
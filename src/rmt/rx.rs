@@ -0,0 +1,208 @@
+//! RMT receiver
+//!
+//! The receiver stores the durations of the levels of the input in the RAM of the channel,
+//! starting at the first edge. Reception ends when the input has not changed for the idle
+//! threshold, after which [Rx::read] copies the received items and restarts the receiver.
+//! The last item of a reception contains the idle level with a duration of 0.
+//!
+//! # Example:
+//! Receive IR frames with a 1MHz channel clock, ending after 12ms idle:
+//! ```no_run
+//! let mut rx = Rx::new(
+//!     channels.channel2,
+//!     pins.gpio15,
+//!     clock_control_config,
+//!     rmt::config::RxConfig::default().idle_threshold(12_000),
+//! )
+//! .unwrap();
+//! rx.start();
+//!
+//! let mut items = [Item::default(); 64];
+//! let len = nb::block!(rx.read(&mut items)).unwrap();
+//! ```
+
+use super::config::RxConfig;
+use super::private::Instance;
+use super::{
+    clear_interrupt, enable_interrupt, is_interrupt_set, read_item, ChannelState, Error, Interrupt,
+    Item, CONF0_CARRIER_EN, CONF0_CLK_EN, CONF0_IDLE_THRES_SHIFT, CONF1_MEM_OWNER,
+    CONF1_MEM_WR_RST, CONF1_RX_EN, CONF1_RX_FILTER_EN, CONF1_RX_FILTER_THRES_SHIFT,
+    CONF1_TX_CONTI_MODE, CONF1_TX_START,
+};
+use crate::clock_control::ClockControlConfig;
+use crate::gpio::InputPin;
+use crate::units::*;
+
+/// Receiver events
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum RxEvent {
+    /// A reception has ended
+    End,
+    /// The received items did not fit in the RAM
+    Error,
+}
+
+impl From<RxEvent> for Interrupt {
+    fn from(event: RxEvent) -> Self {
+        match event {
+            RxEvent::End => Interrupt::RxEnd,
+            RxEvent::Error => Interrupt::Error,
+        }
+    }
+}
+
+/// RMT channel configured as receiver
+pub struct Rx<CH: Instance, PIN: InputPin> {
+    channel: CH,
+    pin: PIN,
+    state: ChannelState,
+}
+
+impl<CH: Instance, PIN: InputPin> Rx<CH, PIN> {
+    /// Configure a channel as receiver on a pin
+    ///
+    /// The receiver is started with [Self::start].
+    pub fn new(
+        channel: CH,
+        mut pin: PIN,
+        clock_control_config: ClockControlConfig,
+        config: RxConfig,
+    ) -> Result<Self, Error> {
+        let state = ChannelState::new::<CH>(
+            clock_control_config,
+            config.clock_source,
+            config.frequency,
+            config.memory_blocks,
+        )?;
+
+        CH::modify_conf0(
+            0xffff << CONF0_IDLE_THRES_SHIFT | CONF0_CARRIER_EN,
+            (config.idle_threshold as u32) << CONF0_IDLE_THRES_SHIFT,
+        );
+
+        let filter = match config.filter {
+            Some(threshold) => {
+                CONF1_RX_FILTER_EN | (threshold as u32) << CONF1_RX_FILTER_THRES_SHIFT
+            }
+            None => 0,
+        };
+        CH::modify_conf1(
+            CONF1_TX_START
+                | CONF1_RX_EN
+                | CONF1_MEM_OWNER
+                | CONF1_TX_CONTI_MODE
+                | CONF1_RX_FILTER_EN
+                | 0xff << CONF1_RX_FILTER_THRES_SHIFT,
+            CONF1_MEM_OWNER | filter,
+        );
+
+        pin.set_to_input()
+            .connect_input_to_peripheral(CH::INPUT_SIGNAL);
+
+        Ok(Rx {
+            channel,
+            pin,
+            state,
+        })
+    }
+
+    /// Frequency of the channel clock, the unit of the item durations
+    pub fn tick_frequency(&self) -> Hertz {
+        self.state.frequency
+    }
+
+    /// Number of items fitting in the RAM of the channel
+    pub fn capacity(&self) -> usize {
+        self.state.capacity()
+    }
+
+    /// Start receiving
+    pub fn start(&mut self) {
+        clear_interrupt::<CH>(Interrupt::RxEnd);
+        clear_interrupt::<CH>(Interrupt::Error);
+        CH::modify_conf1(
+            CONF1_MEM_OWNER | CONF1_MEM_WR_RST,
+            CONF1_MEM_OWNER | CONF1_MEM_WR_RST,
+        );
+        CH::modify_conf1(CONF1_MEM_WR_RST | CONF1_RX_EN, CONF1_RX_EN);
+    }
+
+    /// Stop receiving
+    pub fn stop(&mut self) {
+        CH::modify_conf1(CONF1_RX_EN, 0);
+    }
+
+    /// Copy the items of a finished reception into the buffer and restart the receiver
+    ///
+    /// Returns the number of items, including the last item with the idle level. Returns
+    /// [Error::Overflow] if the reception did not fit in the RAM and [Error::BufferTooSmall]
+    /// if the buffer was filled before the end of the reception.
+    pub fn read(&mut self, buffer: &mut [Item]) -> nb::Result<usize, Error> {
+        if is_interrupt_set::<CH>(Interrupt::Error) {
+            self.stop();
+            self.start();
+            return Err(nb::Error::Other(Error::Overflow));
+        }
+
+        if !is_interrupt_set::<CH>(Interrupt::RxEnd) {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.stop();
+        clear_interrupt::<CH>(Interrupt::RxEnd);
+        // hand the RAM to the CPU while reading
+        CH::modify_conf1(CONF1_MEM_OWNER, 0);
+
+        let mut result = Err(nb::Error::Other(Error::BufferTooSmall));
+        for index in 0..self.state.capacity() {
+            let item = read_item::<CH>(index);
+            if item.duration0() == 0 {
+                result = Ok(index);
+                break;
+            }
+            if index >= buffer.len() {
+                break;
+            }
+            buffer[index] = item;
+            if item.duration1() == 0 {
+                result = Ok(index + 1);
+                break;
+            }
+        }
+
+        self.start();
+        result
+    }
+
+    /// Start listening for an event
+    pub fn listen(&mut self, event: RxEvent) {
+        enable_interrupt::<CH>(event.into(), true);
+    }
+
+    /// Stop listening for an event
+    pub fn unlisten(&mut self, event: RxEvent) {
+        enable_interrupt::<CH>(event.into(), false);
+    }
+
+    /// Check if the interrupt of an event is set
+    pub fn is_interrupt_set(&self, event: RxEvent) -> bool {
+        is_interrupt_set::<CH>(event.into())
+    }
+
+    /// Clear the interrupt of an event
+    ///
+    /// Note: [Self::read] clears the interrupts itself.
+    pub fn clear_interrupt(&mut self, event: RxEvent) {
+        clear_interrupt::<CH>(event.into());
+    }
+
+    /// Stop the receiver and release the channel and pin
+    pub fn release(mut self) -> (CH, PIN) {
+        self.stop();
+        self.unlisten(RxEvent::End);
+        self.unlisten(RxEvent::Error);
+        CH::modify_conf0(CONF0_CLK_EN, 0);
+        self.state.release::<CH>();
+        (self.channel, self.pin)
+    }
+}
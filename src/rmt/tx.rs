@@ -0,0 +1,319 @@
+//! RMT transmitter
+//!
+//! The items of a transmission are written to the RAM of the channel. When a sequence is longer
+//! than the RAM, the RAM is used as a ring buffer: each time half of it has been transmitted,
+//! the threshold event fires and [Transmission::poll] refills that half from the iterator.
+//! [Transmission::poll] therefore needs to be called (e.g. from the RMT interrupt handler after
+//! [Tx::listen] for [TxEvent::Threshold]) before the other half has been transmitted.
+//!
+//! # Example:
+//! ```no_run
+//! let mut tx = Tx::new(
+//!     channels.channel1,
+//!     pins.gpio5,
+//!     clock_control_config,
+//!     rmt::config::TxConfig::default().frequency(10.MHz().into()),
+//! )
+//! .unwrap();
+//!
+//! // 1000 pulses of 1us, generated while transmitting
+//! let mut transmission = tx.start((0..1000).map(|_| Item::new(true, 10, false, 10)));
+//! transmission.wait().unwrap();
+//! ```
+
+use super::config::TxConfig;
+use super::private::Instance;
+use super::{
+    clear_interrupt, enable_interrupt, is_interrupt_set, write_item, ChannelState, Error,
+    Interrupt, Item, CONF0_CARRIER_EN, CONF0_CARRIER_OUT_LV, CONF0_CLK_EN, CONF1_IDLE_OUT_EN,
+    CONF1_IDLE_OUT_LV, CONF1_MEM_OWNER, CONF1_MEM_RD_RST, CONF1_RX_EN, CONF1_TX_CONTI_MODE,
+    CONF1_TX_START,
+};
+use crate::clock_control::ClockControlConfig;
+use crate::gpio::OutputPin;
+use crate::units::*;
+
+/// Transmitter events
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum TxEvent {
+    /// The transmission has ended
+    End,
+    /// Half of the RAM has been transmitted and can be refilled
+    Threshold,
+}
+
+impl From<TxEvent> for Interrupt {
+    fn from(event: TxEvent) -> Self {
+        match event {
+            TxEvent::End => Interrupt::TxEnd,
+            TxEvent::Threshold => Interrupt::TxThreshold,
+        }
+    }
+}
+
+/// RMT channel configured as transmitter
+pub struct Tx<CH: Instance, PIN: OutputPin> {
+    channel: CH,
+    pin: PIN,
+    state: ChannelState,
+}
+
+impl<CH: Instance, PIN: OutputPin> Tx<CH, PIN> {
+    /// Configure a channel as transmitter on a pin
+    pub fn new(
+        channel: CH,
+        mut pin: PIN,
+        clock_control_config: ClockControlConfig,
+        config: TxConfig,
+    ) -> Result<Self, Error> {
+        let state = ChannelState::new::<CH>(
+            clock_control_config,
+            config.clock_source,
+            config.frequency,
+            config.memory_blocks,
+        )?;
+
+        if let Some(carrier) = config.carrier {
+            match Self::carrier_duty(state.source_frequency, carrier.frequency, carrier.duty) {
+                Ok((high, low)) => CH::set_carrier_duty(high, low),
+                Err(err) => {
+                    state.release::<CH>();
+                    return Err(err);
+                }
+            }
+        }
+
+        CH::modify_conf0(
+            CONF0_CARRIER_EN | CONF0_CARRIER_OUT_LV,
+            match config.carrier {
+                Some(carrier) if carrier.level => CONF0_CARRIER_EN | CONF0_CARRIER_OUT_LV,
+                Some(_) => CONF0_CARRIER_EN,
+                None => 0,
+            },
+        );
+
+        let idle = match config.idle_level {
+            Some(true) => CONF1_IDLE_OUT_EN | CONF1_IDLE_OUT_LV,
+            Some(false) => CONF1_IDLE_OUT_EN,
+            None => 0,
+        };
+        CH::modify_conf1(
+            CONF1_TX_START
+                | CONF1_RX_EN
+                | CONF1_MEM_OWNER
+                | CONF1_TX_CONTI_MODE
+                | CONF1_IDLE_OUT_EN
+                | CONF1_IDLE_OUT_LV,
+            idle,
+        );
+        CH::set_tx_limit((state.capacity() / 2) as u16);
+        write_item::<CH>(0, Item::END);
+
+        pin.set_to_push_pull_output()
+            .connect_peripheral_to_output(CH::OUTPUT_SIGNAL);
+
+        Ok(Tx {
+            channel,
+            pin,
+            state,
+        })
+    }
+
+    /// Carrier high and low time in source clock ticks
+    fn carrier_duty(source: Hertz, frequency: Hertz, duty: f32) -> Result<(u16, u16), Error> {
+        if frequency == Hertz(0) || duty <= 0.0 || duty >= 1.0 {
+            return Err(Error::Carrier);
+        }
+        let period = u32::from(source) / u32::from(frequency);
+        let high = (period as f32 * duty + 0.5) as u32;
+        let low = period.saturating_sub(high);
+        if high == 0 || low == 0 || high > u16::MAX as u32 || low > u16::MAX as u32 {
+            return Err(Error::Carrier);
+        }
+        Ok((high as u16, low as u16))
+    }
+
+    /// Frequency of the channel clock, the unit of the item durations
+    pub fn tick_frequency(&self) -> Hertz {
+        self.state.frequency
+    }
+
+    /// Number of items fitting in the RAM of the channel
+    pub fn capacity(&self) -> usize {
+        self.state.capacity()
+    }
+
+    /// Start transmitting the items of an iterator
+    ///
+    /// The transmission ends at the end of the iterator or at the first item with a duration of
+    /// 0. Dropping the returned [Transmission] before it has finished stops the transmission.
+    pub fn start<I>(&mut self, items: I) -> Transmission<'_, CH, PIN, I::IntoIter>
+    where
+        I: IntoIterator<Item = Item>,
+    {
+        self.stop();
+        clear_interrupt::<CH>(Interrupt::TxEnd);
+        clear_interrupt::<CH>(Interrupt::TxThreshold);
+        clear_interrupt::<CH>(Interrupt::Error);
+
+        let mut transmission = Transmission {
+            tx: self,
+            items: items.into_iter(),
+            offset: 0,
+            filled: false,
+            done: false,
+        };
+        let capacity = transmission.tx.state.capacity();
+        transmission.fill(capacity);
+
+        CH::modify_conf1(CONF1_TX_START, CONF1_TX_START);
+
+        transmission
+    }
+
+    /// Transmit the items and wait for the end of the transmission
+    pub fn write(&mut self, items: &[Item]) -> Result<(), Error> {
+        self.start(items.iter().copied()).wait()
+    }
+
+    /// Transmit the items repeatedly until [Self::stop] is called
+    ///
+    /// The items (plus end marker) need to fit in the RAM of the channel.
+    pub fn start_loop(&mut self, items: &[Item]) -> Result<(), Error> {
+        if items.len() >= self.state.capacity() {
+            return Err(Error::TooLong);
+        }
+
+        self.stop();
+        for (index, item) in items.iter().enumerate() {
+            write_item::<CH>(index, *item);
+        }
+        write_item::<CH>(items.len(), Item::END);
+
+        clear_interrupt::<CH>(Interrupt::TxEnd);
+        CH::modify_conf1(
+            CONF1_TX_CONTI_MODE | CONF1_TX_START,
+            CONF1_TX_CONTI_MODE | CONF1_TX_START,
+        );
+        Ok(())
+    }
+
+    /// Stop the transmission
+    pub fn stop(&mut self) {
+        CH::modify_conf1(CONF1_TX_CONTI_MODE | CONF1_TX_START, 0);
+        write_item::<CH>(0, Item::END);
+        CH::modify_conf1(CONF1_MEM_RD_RST, CONF1_MEM_RD_RST);
+        CH::modify_conf1(CONF1_MEM_RD_RST, 0);
+    }
+
+    /// Start listening for an event
+    pub fn listen(&mut self, event: TxEvent) {
+        enable_interrupt::<CH>(event.into(), true);
+    }
+
+    /// Stop listening for an event
+    pub fn unlisten(&mut self, event: TxEvent) {
+        enable_interrupt::<CH>(event.into(), false);
+    }
+
+    /// Check if the interrupt of an event is set
+    pub fn is_interrupt_set(&self, event: TxEvent) -> bool {
+        is_interrupt_set::<CH>(event.into())
+    }
+
+    /// Clear the interrupt of an event
+    ///
+    /// Note: [Transmission::poll] clears the interrupts itself.
+    pub fn clear_interrupt(&mut self, event: TxEvent) {
+        clear_interrupt::<CH>(event.into());
+    }
+
+    /// Stop the transmitter and release the channel and pin
+    pub fn release(mut self) -> (CH, PIN) {
+        self.stop();
+        self.unlisten(TxEvent::End);
+        self.unlisten(TxEvent::Threshold);
+        CH::modify_conf0(CONF0_CARRIER_EN | CONF0_CLK_EN, 0);
+        self.state.release::<CH>();
+        (self.channel, self.pin)
+    }
+}
+
+/// Ongoing transmission of the items of an iterator
+pub struct Transmission<'a, CH: Instance, PIN: OutputPin, I: Iterator<Item = Item>> {
+    tx: &'a mut Tx<CH, PIN>,
+    items: I,
+    /// Position of the next refill in the RAM
+    offset: usize,
+    /// All items are written to the RAM
+    filled: bool,
+    done: bool,
+}
+
+impl<'a, CH: Instance, PIN: OutputPin, I: Iterator<Item = Item>> Transmission<'a, CH, PIN, I> {
+    /// Write up to `count` items starting at the current offset
+    fn fill(&mut self, count: usize) {
+        if self.filled {
+            return;
+        }
+
+        for index in self.offset..self.offset + count {
+            match self.items.next() {
+                Some(item) if !item.is_end() => write_item::<CH>(index, item),
+                Some(item) => {
+                    write_item::<CH>(index, item);
+                    self.filled = true;
+                    return;
+                }
+                None => {
+                    write_item::<CH>(index, Item::END);
+                    self.filled = true;
+                    return;
+                }
+            }
+        }
+
+        self.offset = (self.offset + count) % self.tx.state.capacity();
+    }
+
+    /// Refill the RAM when needed and check for the end of the transmission
+    pub fn poll(&mut self) -> nb::Result<(), Error> {
+        if self.done {
+            return Ok(());
+        }
+
+        if is_interrupt_set::<CH>(Interrupt::TxThreshold) {
+            clear_interrupt::<CH>(Interrupt::TxThreshold);
+            let half = self.tx.state.capacity() / 2;
+            self.fill(half);
+        }
+
+        if is_interrupt_set::<CH>(Interrupt::TxEnd) {
+            clear_interrupt::<CH>(Interrupt::TxEnd);
+            self.done = true;
+            return Ok(());
+        }
+
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Check if the transmission has ended
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Wait for the end of the transmission
+    pub fn wait(&mut self) -> Result<(), Error> {
+        nb::block!(self.poll())
+    }
+}
+
+impl<'a, CH: Instance, PIN: OutputPin, I: Iterator<Item = Item>> Drop
+    for Transmission<'a, CH, PIN, I>
+{
+    fn drop(&mut self) {
+        if !self.done {
+            self.tx.stop();
+        }
+    }
+}
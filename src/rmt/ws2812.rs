@@ -0,0 +1,291 @@
+//! WS2812/SK6812 smart LEDs
+//!
+//! Drives a strip of smart LEDs with an RMT channel, implementing
+//! [SmartLedsWrite][smart_leds::SmartLedsWrite] for RGB ([RGB8]) and RGBW ([RGBW]) pixels.
+//! The pixels are converted to RMT items while transmitting, so the length of the strip is not
+//! limited by the RMT RAM. Only available with the `smart-leds` feature.
+//!
+//! [SmartLedsWrite::write][smart_leds::SmartLedsWrite::write] blocks until all pixels have been
+//! sent. [Ws2812::start] returns the ongoing [Transmission] instead, which allows driving
+//! several strips on different channels at once:
+//! ```no_run
+//! let mut strip0 = Ws2812::<_, _, RGB8>::new(
+//!     channels.channel0,
+//!     pins.gpio25,
+//!     clock_control_config,
+//!     ws2812::Config::default(),
+//! )
+//! .unwrap();
+//! let mut strip1 = Ws2812::<_, _, RGBW<u8>>::new(
+//!     channels.channel2,
+//!     pins.gpio26,
+//!     clock_control_config,
+//!     ws2812::Config::default().timing(ws2812::Timing::SK6812),
+//! )
+//! .unwrap();
+//!
+//! let mut transmission0 = strip0.start(data0.iter().cloned());
+//! let mut transmission1 = strip1.start(data1.iter().cloned());
+//! while transmission0.poll().is_err() | transmission1.poll().is_err() {}
+//! ```
+//!
+//! The RAM of the channel is refilled half by half, so [Transmission::poll] needs to be called
+//! at least every half RAM of items (32 bits, i.e. 40us, per RAM block). Using more RAM blocks
+//! gives more time between refills.
+
+use super::config::TxConfig;
+use super::private::Instance;
+use super::tx::{Transmission, Tx};
+use super::{Error, Item, MAX_DURATION};
+use crate::clock_control::ClockControlConfig;
+use crate::gpio::OutputPin;
+use crate::units::*;
+use core::marker::PhantomData;
+use smart_leds::{SmartLedsWrite, White, RGB8, RGBW};
+
+/// Frequency of the channel clock (25ns resolution)
+const TICK_FREQUENCY: Hertz = Hertz(40_000_000);
+
+/// Pulse timings of the LEDs
+#[derive(Copy, Clone, Debug)]
+pub struct Timing {
+    /// High time of a 0 bit
+    pub t0h: NanoSeconds,
+    /// Low time of a 0 bit
+    pub t0l: NanoSeconds,
+    /// High time of a 1 bit
+    pub t1h: NanoSeconds,
+    /// Low time of a 1 bit
+    pub t1l: NanoSeconds,
+    /// Low time latching the data
+    pub reset: NanoSeconds,
+}
+
+impl Timing {
+    /// WS2812(B) timing, with the longer reset of recent WS2812B revisions
+    pub const WS2812: Timing = Timing {
+        t0h: NanoSeconds(400),
+        t0l: NanoSeconds(850),
+        t1h: NanoSeconds(800),
+        t1l: NanoSeconds(450),
+        reset: NanoSeconds(280_000),
+    };
+
+    /// SK6812 timing
+    pub const SK6812: Timing = Timing {
+        t0h: NanoSeconds(300),
+        t0l: NanoSeconds(900),
+        t1h: NanoSeconds(600),
+        t1l: NanoSeconds(600),
+        reset: NanoSeconds(80_000),
+    };
+}
+
+/// Strip configuration
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    pub timing: Timing,
+    /// Number of RAM blocks of 64 items (bits) used for the refill buffer
+    pub memory_blocks: u8,
+}
+
+impl Config {
+    pub fn timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    pub fn memory_blocks(mut self, memory_blocks: u8) -> Self {
+        self.memory_blocks = memory_blocks;
+        self
+    }
+}
+
+impl Default for Config {
+    /// WS2812 timing with 2 RAM blocks
+    fn default() -> Config {
+        Config {
+            timing: Timing::WS2812,
+            memory_blocks: 2,
+        }
+    }
+}
+
+/// Pixel format of a strip
+pub trait Color: Copy {
+    /// Number of bits per pixel
+    const BITS: u8;
+
+    /// Bits of the pixel in transmission order, starting at bit 31
+    fn bits(&self) -> u32;
+}
+
+/// RGB pixel, sent in GRB order
+impl Color for RGB8 {
+    const BITS: u8 = 24;
+
+    fn bits(&self) -> u32 {
+        (self.g as u32) << 24 | (self.r as u32) << 16 | (self.b as u32) << 8
+    }
+}
+
+/// RGBW pixel, sent in GRBW order
+impl Color for RGBW<u8> {
+    const BITS: u8 = 32;
+
+    fn bits(&self) -> u32 {
+        let White(w) = self.a;
+        (self.g as u32) << 24 | (self.r as u32) << 16 | (self.b as u32) << 8 | w as u32
+    }
+}
+
+/// Items of the bits of a pixel and the reset
+#[derive(Copy, Clone, Debug)]
+struct Symbols {
+    zero: Item,
+    one: Item,
+    reset: Item,
+}
+
+impl Symbols {
+    fn new(timing: Timing, frequency: Hertz) -> Result<Self, Error> {
+        let ticks = |time: NanoSeconds| {
+            let ticks = (u32::from(time) as u64 * u32::from(frequency) as u64 + 500_000_000)
+                / 1_000_000_000;
+            if ticks == 0 || ticks > MAX_DURATION as u64 {
+                Err(Error::Duration)
+            } else {
+                Ok(ticks as u16)
+            }
+        };
+
+        // the reset is split over both halves of an item to allow longer resets
+        let reset = ticks(NanoSeconds(u32::from(timing.reset) / 2))?;
+        Ok(Symbols {
+            zero: Item::new(true, ticks(timing.t0h)?, false, ticks(timing.t0l)?),
+            one: Item::new(true, ticks(timing.t1h)?, false, ticks(timing.t1l)?),
+            reset: Item::new(false, reset, false, reset),
+        })
+    }
+}
+
+/// Iterator converting pixels into RMT items
+pub struct Items<T, C> {
+    pixels: T,
+    symbols: Symbols,
+    bits: u32,
+    remaining: u8,
+    reset: bool,
+    _color: PhantomData<C>,
+}
+
+impl<T, I, C> Iterator for Items<T, C>
+where
+    T: Iterator<Item = I>,
+    I: Into<C>,
+    C: Color,
+{
+    type Item = Item;
+
+    fn next(&mut self) -> Option<Item> {
+        if self.remaining == 0 {
+            match self.pixels.next() {
+                Some(pixel) => {
+                    self.bits = pixel.into().bits();
+                    self.remaining = C::BITS;
+                }
+                None if !self.reset => {
+                    self.reset = true;
+                    return Some(self.symbols.reset);
+                }
+                None => return None,
+            }
+        }
+
+        let bit = self.bits & (1 << 31) != 0;
+        self.bits <<= 1;
+        self.remaining -= 1;
+        Some(if bit {
+            self.symbols.one
+        } else {
+            self.symbols.zero
+        })
+    }
+}
+
+/// Strip of smart LEDs on an RMT channel
+pub struct Ws2812<CH: Instance, PIN: OutputPin, C: Color> {
+    tx: Tx<CH, PIN>,
+    symbols: Symbols,
+    _color: PhantomData<C>,
+}
+
+impl<CH: Instance, PIN: OutputPin, C: Color> Ws2812<CH, PIN, C> {
+    /// Configure a channel to drive a strip on a pin
+    pub fn new(
+        channel: CH,
+        pin: PIN,
+        clock_control_config: ClockControlConfig,
+        config: Config,
+    ) -> Result<Self, Error> {
+        let tx = Tx::new(
+            channel,
+            pin,
+            clock_control_config,
+            TxConfig::default()
+                .frequency(TICK_FREQUENCY)
+                .memory_blocks(config.memory_blocks)
+                .idle_level(Some(false)),
+        )?;
+
+        match Symbols::new(config.timing, tx.tick_frequency()) {
+            Ok(symbols) => Ok(Ws2812 {
+                tx,
+                symbols,
+                _color: PhantomData,
+            }),
+            Err(err) => {
+                tx.release();
+                Err(err)
+            }
+        }
+    }
+
+    /// Start sending the pixels, followed by the reset
+    ///
+    /// The returned [Transmission] needs to be polled until it has finished.
+    pub fn start<T, I>(&mut self, pixels: T) -> Transmission<'_, CH, PIN, Items<T, C>>
+    where
+        T: Iterator<Item = I>,
+        I: Into<C>,
+    {
+        let items = Items {
+            pixels,
+            symbols: self.symbols,
+            bits: 0,
+            remaining: 0,
+            reset: false,
+            _color: PhantomData,
+        };
+        self.tx.start(items)
+    }
+
+    /// Release the channel and pin
+    pub fn release(self) -> (CH, PIN) {
+        self.tx.release()
+    }
+}
+
+impl<CH: Instance, PIN: OutputPin, C: Color> SmartLedsWrite for Ws2812<CH, PIN, C> {
+    type Error = Error;
+    type Color = C;
+
+    /// Send the pixels and wait until they have been sent
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: Iterator<Item = I>,
+        I: Into<C>,
+    {
+        self.start(iterator).wait()
+    }
+}
@@ -0,0 +1,224 @@
+//! Sigma-delta modulated output (GPIO_SD)
+//!
+//! The GPIO block contains 8 sigma-delta modulation channels. Each channel outputs a pulse
+//! density modulated signal with a density set by a signed 8 bit value: -128 results in an
+//! output which is almost always low, 0 in a 50% density and 127 in an output which is almost
+//! always high. The modulator is clocked by the APB clock divided by `prescale + 1`.
+//!
+//! The output can be routed to any output pin via the GPIO matrix. With an external RC low pass
+//! filter it can be used as a simple DAC, e.g. for LED dimming or low quality audio.
+//!
+//! # Example:
+//! ```no_run
+//! let channels = dp.GPIO_SD.split();
+//!
+//! let mut led = SigmaDelta::new(
+//!     channels.channel0,
+//!     pins.gpio2.into_push_pull_output(),
+//!     sigma_delta::config::Config::default().prescale(79),
+//! );
+//!
+//! led.set_density(-64);
+//! ```
+
+use crate::gpio::{route, OutputPin};
+use crate::target::GPIO_SD;
+use core::marker::PhantomData;
+
+use private::Instance;
+
+/// Sigma-delta configuration
+pub mod config {
+    /// Sigma-delta configuration
+    #[derive(Copy, Clone, Debug)]
+    pub struct Config {
+        /// Pulse density (-128 to 127)
+        pub density: i8,
+        /// Modulator clock prescaler: APB clock / (prescale + 1)
+        pub prescale: u8,
+    }
+
+    impl Config {
+        pub fn density(mut self, density: i8) -> Self {
+            self.density = density;
+            self
+        }
+
+        pub fn prescale(mut self, prescale: u8) -> Self {
+            self.prescale = prescale;
+            self
+        }
+    }
+
+    impl Default for Config {
+        fn default() -> Config {
+            Config {
+                density: 0,
+                prescale: 255,
+            }
+        }
+    }
+}
+
+/// Extension trait to split the GPIO_SD peripheral into independent channels
+pub trait SigmaDeltaExt {
+    fn split(self) -> Channels;
+}
+
+/// Sigma-delta channels
+pub struct Channels {
+    pub channel0: Channel0,
+    pub channel1: Channel1,
+    pub channel2: Channel2,
+    pub channel3: Channel3,
+    pub channel4: Channel4,
+    pub channel5: Channel5,
+    pub channel6: Channel6,
+    pub channel7: Channel7,
+}
+
+impl SigmaDeltaExt for GPIO_SD {
+    fn split(self) -> Channels {
+        // enable the modulator clock
+        self.sigmadelta_cg
+            .modify(|r, w| unsafe { w.bits(r.bits() | SD_CLK_EN) });
+
+        Channels {
+            channel0: Channel0 {
+                _private: PhantomData,
+            },
+            channel1: Channel1 {
+                _private: PhantomData,
+            },
+            channel2: Channel2 {
+                _private: PhantomData,
+            },
+            channel3: Channel3 {
+                _private: PhantomData,
+            },
+            channel4: Channel4 {
+                _private: PhantomData,
+            },
+            channel5: Channel5 {
+                _private: PhantomData,
+            },
+            channel6: Channel6 {
+                _private: PhantomData,
+            },
+            channel7: Channel7 {
+                _private: PhantomData,
+            },
+        }
+    }
+}
+
+/// Clock enable bit in the `sigmadelta_cg` register
+const SD_CLK_EN: u32 = 1 << 31;
+
+/// Sigma-delta channel attached to an output pin
+pub struct SigmaDelta<CH: Instance, PIN: OutputPin> {
+    channel: CH,
+    pin: PIN,
+}
+
+impl<CH: Instance, PIN: OutputPin> SigmaDelta<CH, PIN> {
+    /// Configure the channel and route its output to the pin
+    pub fn new(channel: CH, mut pin: PIN, config: config::Config) -> Self {
+        CH::write(config.density, config.prescale);
+
+        pin.set_to_push_pull_output()
+            .connect_peripheral_to_output(CH::SIGNAL);
+
+        SigmaDelta { channel, pin }
+    }
+
+    /// Set the pulse density (-128 to 127, 0 is 50%)
+    pub fn set_density(&mut self, density: i8) {
+        CH::write(density, CH::prescale());
+    }
+
+    /// Get the pulse density
+    pub fn density(&self) -> i8 {
+        CH::density()
+    }
+
+    /// Set the modulator clock prescaler: APB clock / (prescale + 1)
+    pub fn set_prescale(&mut self, prescale: u8) {
+        CH::write(CH::density(), prescale);
+    }
+
+    /// Get the modulator clock prescaler
+    pub fn prescale(&self) -> u8 {
+        CH::prescale()
+    }
+
+    /// Stop driving the pin and release the channel and pin
+    ///
+    /// The pin is switched back to GPIO function, driven by its output register.
+    pub fn release(mut self) -> (CH, PIN) {
+        CH::write(0, CH::prescale());
+        route::disconnect_output(&mut self.pin);
+        (self.channel, self.pin)
+    }
+}
+
+macro_rules! channels {
+    ($($channel:ident: ($register:ident, $signal:ident),)+) => {
+        $(
+            /// Sigma-delta channel
+            pub struct $channel {
+                _private: PhantomData<()>,
+            }
+
+            impl Instance for $channel {
+                const SIGNAL: crate::gpio::OutputSignal = crate::gpio::OutputSignal::$signal;
+
+                fn read() -> u32 {
+                    unsafe { (*GPIO_SD::ptr()).$register.read().bits() }
+                }
+
+                fn write(density: i8, prescale: u8) {
+                    unsafe {
+                        (*GPIO_SD::ptr()).$register.write(|w| {
+                            w.bits(density as u8 as u32 | (prescale as u32) << 8)
+                        })
+                    }
+                }
+            }
+        )+
+    };
+}
+
+channels! {
+    Channel0: (sigmadelta0, GPIO_SD0),
+    Channel1: (sigmadelta1, GPIO_SD1),
+    Channel2: (sigmadelta2, GPIO_SD2),
+    Channel3: (sigmadelta3, GPIO_SD3),
+    Channel4: (sigmadelta4, GPIO_SD4),
+    Channel5: (sigmadelta5, GPIO_SD5),
+    Channel6: (sigmadelta6, GPIO_SD6),
+    Channel7: (sigmadelta7, GPIO_SD7),
+}
+
+mod private {
+    use crate::gpio::OutputSignal;
+
+    pub trait Instance {
+        /// Output matrix signal of the channel
+        const SIGNAL: OutputSignal;
+
+        /// Read the raw channel register
+        fn read() -> u32;
+
+        /// Write density (bits 0-7) and prescale (bits 8-15)
+        fn write(density: i8, prescale: u8);
+
+        fn density() -> i8 {
+            Self::read() as u8 as i8
+        }
+
+        fn prescale() -> u8 {
+            (Self::read() >> 8) as u8
+        }
+    }
+}